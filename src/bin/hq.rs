@@ -5,31 +5,36 @@ use clap::{Clap, ValueHint};
 use cli_table::ColorChoice;
 
 use anyhow::bail;
+use hyperqueue::client::commands::autoalloc::{command_autoalloc, AutoAllocOpts};
+use hyperqueue::client::commands::info::print_server_info;
 use hyperqueue::client::commands::jobs::{
-    cancel_job, get_last_job_id, output_job_detail, output_job_list,
+    cancel_job, hold_job, output_job_detail, output_job_list, requeue_tasks, update_job,
 };
 use hyperqueue::client::commands::log::{command_log, LogOpts};
+use hyperqueue::client::commands::pools::{set_resource_pool, ArgPoolAmount};
 use hyperqueue::client::commands::stats::print_server_stats;
 use hyperqueue::client::commands::stop::stop_server;
 use hyperqueue::client::commands::submit::{
     resubmit_computation, submit_computation, ResubmitOpts, SubmitOpts,
 };
 use hyperqueue::client::commands::wait::wait_for_job_with_selector;
-use hyperqueue::client::commands::worker::{get_worker_info, get_worker_list, stop_worker};
+use hyperqueue::client::commands::worker::{
+    drain_worker, get_worker_info, get_worker_list, stop_worker,
+};
 use hyperqueue::client::globalsettings::GlobalSettings;
 use hyperqueue::client::status::Status;
 use hyperqueue::client::worker::print_worker_info;
+use hyperqueue::common::arraydef::ArrayDef;
 use hyperqueue::common::fsutils::absolute_path;
+use hyperqueue::common::labels::worker_labels;
 use hyperqueue::common::setup::setup_logging;
 use hyperqueue::common::timeutils::ArgDuration;
-use hyperqueue::server::bootstrap::{
-    get_client_connection, init_hq_server, print_server_info, ServerConfig,
-};
+use hyperqueue::server::bootstrap::{get_client_connection, init_hq_server, ServerConfig};
 use hyperqueue::transfer::messages::{JobSelector, WorkerSelector};
 use hyperqueue::worker::hwdetect::{detect_resource, print_resource_descriptor};
 use hyperqueue::worker::output::print_worker_configuration;
 use hyperqueue::worker::start::{start_hq_worker, WorkerStartOpts};
-use hyperqueue::{JobId, WorkerId};
+use hyperqueue::{JobId, JobTaskCount, WorkerId};
 
 #[global_allocator]
 static ALLOC: jemallocator::Jemalloc = jemallocator::Jemalloc;
@@ -67,12 +72,14 @@ enum SubCommand {
     Server(ServerOpts),
     /// Display information about all jobs
     Jobs(JobListOpts),
-    /// Display detailed information about a specific job
+    /// Display detailed information about a specific job or control jobs
     Job(JobDetailOpts),
     /// Submit a job to HyperQueue
     Submit(SubmitOpts),
     /// Cancel a specific job
     Cancel(CancelOpts),
+    /// Commands for controlling individual tasks of a job
+    Task(TaskOpts),
     /// Commands for controlling HyperQueue workers
     Worker(WorkerOpts),
     /// Resubmits all filtered tasks within a job
@@ -81,6 +88,8 @@ enum SubCommand {
     Wait(WaitOpts),
     /// Operations with log
     Log(LogOpts),
+    /// Automatic allocation of workers through PBS/SLURM
+    Alloc(AutoAllocOpts),
 }
 
 // Server CLI options
@@ -93,6 +102,14 @@ struct ServerStartOpts {
 
     #[clap(long)]
     idle_timeout: Option<ArgDuration>,
+
+    /// Restore jobs from the journal of the previous server
+    #[clap(long)]
+    restore: bool,
+
+    /// How often is the status of automatic allocations refreshed
+    #[clap(long, default_value = "1m")]
+    autoalloc_interval: ArgDuration,
 }
 
 #[derive(Clap)]
@@ -122,12 +139,36 @@ enum ServerCommand {
     Stop(ServerStopOpts),
     /// Show info of running HyperQueue server
     Info(ServerInfoOpts),
+    /// Manage resource pools of the server
+    Resource(ServerResourceOpts),
+}
+
+#[derive(Clap)]
+#[clap(setting = clap::AppSettings::ColoredHelp)]
+struct ServerResourceOpts {
+    #[clap(subcommand)]
+    subcmd: ServerResourceCommand,
+}
+
+#[derive(Clap)]
+enum ServerResourceCommand {
+    /// Create a resource pool or change the size of an existing pool
+    Add(ServerResourceAddOpts),
+}
+
+#[derive(Clap)]
+#[clap(setting = clap::AppSettings::ColoredHelp)]
+struct ServerResourceAddOpts {
+    /// Name and size of the pool, e.g. `matlab=10`
+    pool: ArgPoolAmount,
 }
 
 #[derive(Clap)]
 #[clap(setting = clap::AppSettings::ColoredHelp)]
 pub struct WaitOpts {
-    selector: JobSelectorArg,
+    /// Job selector, e.g. `3`, `3-10`, `1,5,9`, `last`, `last 5`, `all`, `name:<pattern>`
+    /// or `status:<states>`
+    selector: JobSelector,
 }
 
 enum WorkerSelectorArg {
@@ -162,6 +203,12 @@ struct WorkerStopOpts {
     selector: WorkerSelectorArg,
 }
 
+#[derive(Clap)]
+#[clap(setting = clap::AppSettings::ColoredHelp)]
+struct WorkerDrainOpts {
+    selector: WorkerSelectorArg,
+}
+
 #[derive(Clap)]
 #[clap(setting = clap::AppSettings::ColoredHelp)]
 struct WorkerListOpts {
@@ -172,6 +219,10 @@ struct WorkerListOpts {
     /// shows offline workers
     #[clap(long)]
     offline: bool,
+
+    /// shows only workers that have the given label, it can be used multiple times
+    #[clap(long = "label", multiple_occurrences(true))]
+    labels: Vec<String>,
 }
 
 #[derive(Clap)]
@@ -199,6 +250,8 @@ enum WorkerCommand {
     Start(WorkerStartOpts),
     /// Stop worker
     Stop(WorkerStopOpts),
+    /// Stop starting new tasks on worker(s), they stop when their running tasks end
+    Drain(WorkerDrainOpts),
     /// Display information about all workers
     List(WorkerListOpts),
     /// Hwdetect
@@ -215,41 +268,94 @@ enum WorkerCommand {
 #[clap(setting = clap::AppSettings::ColoredHelp)]
 struct JobListOpts {
     job_filters: Vec<Status>,
+
+    /// Show only jobs with a task that failed with the given exit code
+    #[clap(long, allow_hyphen_values(true))]
+    exit_code: Option<i32>,
 }
 
-enum JobSelectorArg {
-    All,
-    Last,
-    Id(JobId),
+#[derive(Clap)]
+#[clap(setting = clap::AppSettings::ColoredHelp)]
+struct JobDetailOpts {
+    #[clap(subcommand)]
+    subcmd: Option<JobCommand>,
+
+    /// Job selector, e.g. `3`, `3-10`, `1,5,9`, `last`, `last 5`, `all`, `name:<pattern>`
+    /// or `status:<states>`
+    job_specifier: Option<JobSelector>,
+
+    // Include task info in the output
+    #[clap(long)]
+    tasks: bool,
+
+    /// Show only tasks that failed with the given exit code
+    #[clap(long, allow_hyphen_values(true))]
+    exit_code: Option<i32>,
 }
 
-impl FromStr for JobSelectorArg {
-    type Err = anyhow::Error;
+#[derive(Clap)]
+enum JobCommand {
+    /// Hold jobs, their waiting tasks are not started until the jobs are released
+    Hold(JobHoldOpts),
+    /// Release held jobs
+    Release(JobHoldOpts),
+    /// Change the priority or the maximal number of failed tasks of a submitted job
+    Update(JobUpdateOpts),
+}
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "last" => Ok(JobSelectorArg::Last),
-            "all" => Ok(JobSelectorArg::All),
-            _ => Ok(JobSelectorArg::Id(FromStr::from_str(s)?)),
-        }
-    }
+#[derive(Clap)]
+#[clap(setting = clap::AppSettings::ColoredHelp)]
+struct JobHoldOpts {
+    /// Job selector, e.g. `3`, `3-10`, `last`, `all`, `name:<pattern>` or `status:<states>`
+    selector: JobSelector,
 }
 
 #[derive(Clap)]
 #[clap(setting = clap::AppSettings::ColoredHelp)]
-struct JobDetailOpts {
-    /// Numeric job id or `last` to display the most recently submitted job
-    job_specifier: JobSelectorArg,
+struct JobUpdateOpts {
+    job_id: JobId,
 
-    // Include task info in the output
+    /// New priority of waiting tasks of the job
+    #[clap(long, allow_hyphen_values(true))]
+    priority: Option<tako::Priority>,
+
+    /// New maximal number of failed tasks, the job is canceled when it is exceeded
     #[clap(long)]
-    tasks: bool,
+    max_fails: Option<JobTaskCount>,
 }
 
 #[derive(Clap)]
 #[clap(setting = clap::AppSettings::ColoredHelp)]
 struct CancelOpts {
-    job_specifier: JobSelectorArg,
+    /// Job selector, e.g. `3`, `3-10`, `last`, `all`, `name:<pattern>` or `status:<states>`
+    job_specifier: JobSelector,
+
+    /// Cancel only the given tasks of the selected jobs, e.g. `100-200` or `1,5,9`
+    #[clap(long)]
+    tasks: Option<ArrayDef>,
+}
+
+#[derive(Clap)]
+#[clap(setting = clap::AppSettings::ColoredHelp)]
+struct TaskOpts {
+    #[clap(subcommand)]
+    subcmd: TaskCommand,
+}
+
+#[derive(Clap)]
+enum TaskCommand {
+    /// Restart running tasks of a job, they are submitted again with the same ids
+    Requeue(TaskRequeueOpts),
+}
+
+#[derive(Clap)]
+#[clap(setting = clap::AppSettings::ColoredHelp)]
+struct TaskRequeueOpts {
+    job_id: JobId,
+
+    /// Tasks that should be restarted, e.g. `5,9` or `10-20`
+    #[clap(long)]
+    tasks: ArrayDef,
 }
 
 // Commands
@@ -262,6 +368,8 @@ async fn command_server_start(
             .host
             .unwrap_or_else(|| gethostname::gethostname().into_string().unwrap()),
         idle_timeout: opts.idle_timeout.map(|x| x.into_duration()),
+        restore: opts.restore,
+        autoalloc_interval: opts.autoalloc_interval.into_duration(),
     };
     init_hq_server(&gsettings, server_cfg).await
 }
@@ -287,38 +395,70 @@ async fn command_server_info(
     }
 }
 
-async fn command_job_list(gsettings: GlobalSettings, opts: JobListOpts) -> anyhow::Result<()> {
+async fn command_server_resource_add(
+    gsettings: GlobalSettings,
+    opts: ServerResourceAddOpts,
+) -> anyhow::Result<()> {
     let mut connection = get_client_connection(gsettings.server_directory()).await?;
-    output_job_list(&gsettings, &mut connection, opts.job_filters)
-        .await
-        .map_err(|e| e.into())
+    set_resource_pool(&gsettings, &mut connection, opts.pool).await
 }
 
-async fn command_job_detail(gsettings: GlobalSettings, opts: JobDetailOpts) -> anyhow::Result<()> {
+async fn command_job_list(gsettings: GlobalSettings, opts: JobListOpts) -> anyhow::Result<()> {
     let mut connection = get_client_connection(gsettings.server_directory()).await?;
+    output_job_list(
+        &gsettings,
+        &mut connection,
+        opts.job_filters,
+        opts.exit_code,
+    )
+    .await
+    .map_err(|e| e.into())
+}
 
-    let job_id = match opts.job_specifier {
-        JobSelectorArg::Id(job_id) => job_id,
-        JobSelectorArg::Last => {
-            let id = get_last_job_id(&mut connection).await?;
-            match id {
-                Some(id) => id,
-                None => {
-                    log::warn!("No jobs were found");
-                    return Ok(());
-                }
-            }
-        }
-        JobSelectorArg::All => {
-            bail!("Specifier all is not implemented for job details, did you mean: job list?")
-        }
+async fn command_job_detail(gsettings: GlobalSettings, opts: JobDetailOpts) -> anyhow::Result<()> {
+    let selector = match opts.job_specifier {
+        Some(selector) => selector,
+        None => bail!("Job selector is missing, use `hq job <job-id>`"),
     };
-
-    output_job_detail(&gsettings, &mut connection, job_id, opts.tasks)
+    let mut connection = get_client_connection(gsettings.server_directory()).await?;
+    output_job_detail(
+        &gsettings,
+        &mut connection,
+        selector,
+        opts.tasks,
+        opts.exit_code,
+    )
+    .await
+    .map_err(|e| e.into())
+}
+
+async fn command_job_hold(
+    gsettings: GlobalSettings,
+    opts: JobHoldOpts,
+    hold: bool,
+) -> anyhow::Result<()> {
+    let mut connection = get_client_connection(gsettings.server_directory()).await?;
+    hold_job(&gsettings, &mut connection, opts.selector, hold)
         .await
         .map_err(|e| e.into())
 }
 
+async fn command_job_update(gsettings: GlobalSettings, opts: JobUpdateOpts) -> anyhow::Result<()> {
+    if opts.priority.is_none() && opts.max_fails.is_none() {
+        bail!("Nothing to update, use --priority or --max-fails");
+    }
+    let mut connection = get_client_connection(gsettings.server_directory()).await?;
+    update_job(
+        &gsettings,
+        &mut connection,
+        opts.job_id,
+        opts.priority,
+        opts.max_fails,
+    )
+    .await
+    .map_err(|e| e.into())
+}
+
 async fn command_submit(gsettings: GlobalSettings, opts: SubmitOpts) -> anyhow::Result<()> {
     let mut connection = get_client_connection(gsettings.server_directory()).await?;
     submit_computation(&gsettings, &mut connection, opts).await
@@ -327,13 +467,17 @@ async fn command_submit(gsettings: GlobalSettings, opts: SubmitOpts) -> anyhow::
 async fn command_cancel(gsettings: GlobalSettings, opts: CancelOpts) -> anyhow::Result<()> {
     let mut connection = get_client_connection(gsettings.server_directory()).await?;
 
-    let selector: JobSelector = match opts.job_specifier {
-        JobSelectorArg::Id(job_id) => JobSelector::Specific(vec![job_id]),
-        JobSelectorArg::Last => JobSelector::LastN(1),
-        JobSelectorArg::All => JobSelector::All,
-    };
+    cancel_job(&gsettings, &mut connection, opts.job_specifier, opts.tasks)
+        .await
+        .map_err(|e| e.into())
+}
 
-    cancel_job(&gsettings, &mut connection, selector)
+async fn command_task_requeue(
+    gsettings: GlobalSettings,
+    opts: TaskRequeueOpts,
+) -> anyhow::Result<()> {
+    let mut connection = get_client_connection(gsettings.server_directory()).await?;
+    requeue_tasks(&gsettings, &mut connection, opts.job_id, opts.tasks)
         .await
         .map_err(|e| e.into())
 }
@@ -354,6 +498,15 @@ async fn command_worker_stop(
     Ok(())
 }
 
+async fn command_worker_drain(
+    gsettings: GlobalSettings,
+    opts: WorkerDrainOpts,
+) -> anyhow::Result<()> {
+    let mut connection = get_client_connection(gsettings.server_directory()).await?;
+    drain_worker(&mut connection, opts.selector.into()).await?;
+    Ok(())
+}
+
 async fn command_worker_list(
     gsettings: GlobalSettings,
     opts: WorkerListOpts,
@@ -366,7 +519,13 @@ async fn command_worker_list(
     } else {
         (opts.running, opts.offline)
     };
-    let workers = get_worker_list(&mut connection, online, offline).await?;
+    let mut workers = get_worker_list(&mut connection, online, offline).await?;
+    if !opts.labels.is_empty() {
+        workers.retain(|w| {
+            let labels = worker_labels(&w.configuration);
+            opts.labels.iter().all(|l| labels.contains(&l.as_str()))
+        });
+    }
     print_worker_info(workers, &gsettings);
     Ok(())
 }
@@ -415,13 +574,7 @@ async fn command_worker_address(
 async fn command_wait(gsettings: GlobalSettings, opts: WaitOpts) -> anyhow::Result<()> {
     let mut connection = get_client_connection(gsettings.server_directory()).await?;
 
-    let selector: JobSelector = match opts.selector {
-        JobSelectorArg::Id(job_id) => JobSelector::Specific(vec![job_id]),
-        JobSelectorArg::Last => JobSelector::LastN(1),
-        JobSelectorArg::All => JobSelector::All,
-    };
-
-    wait_for_job_with_selector(&mut connection, selector).await
+    wait_for_job_with_selector(&mut connection, opts.selector).await
 }
 
 pub enum ColorPolicy {
@@ -496,6 +649,12 @@ async fn main() -> hyperqueue::Result<()> {
         SubCommand::Server(ServerOpts {
             subcmd: ServerCommand::Info(opts),
         }) => command_server_info(gsettings, opts).await,
+        SubCommand::Server(ServerOpts {
+            subcmd:
+                ServerCommand::Resource(ServerResourceOpts {
+                    subcmd: ServerResourceCommand::Add(opts),
+                }),
+        }) => command_server_resource_add(gsettings, opts).await,
 
         SubCommand::Worker(WorkerOpts {
             subcmd: WorkerCommand::Start(opts),
@@ -503,6 +662,9 @@ async fn main() -> hyperqueue::Result<()> {
         SubCommand::Worker(WorkerOpts {
             subcmd: WorkerCommand::Stop(opts),
         }) => command_worker_stop(gsettings, opts).await,
+        SubCommand::Worker(WorkerOpts {
+            subcmd: WorkerCommand::Drain(opts),
+        }) => command_worker_drain(gsettings, opts).await,
         SubCommand::Worker(WorkerOpts {
             subcmd: WorkerCommand::List(opts),
         }) => command_worker_list(gsettings, opts).await,
@@ -516,12 +678,28 @@ async fn main() -> hyperqueue::Result<()> {
             subcmd: WorkerCommand::Address(opts),
         }) => command_worker_address(gsettings, opts).await,
         SubCommand::Jobs(opts) => command_job_list(gsettings, opts).await,
+        SubCommand::Job(JobDetailOpts {
+            subcmd: Some(JobCommand::Hold(opts)),
+            ..
+        }) => command_job_hold(gsettings, opts, true).await,
+        SubCommand::Job(JobDetailOpts {
+            subcmd: Some(JobCommand::Release(opts)),
+            ..
+        }) => command_job_hold(gsettings, opts, false).await,
+        SubCommand::Job(JobDetailOpts {
+            subcmd: Some(JobCommand::Update(opts)),
+            ..
+        }) => command_job_update(gsettings, opts).await,
         SubCommand::Job(opts) => command_job_detail(gsettings, opts).await,
         SubCommand::Submit(opts) => command_submit(gsettings, opts).await,
         SubCommand::Cancel(opts) => command_cancel(gsettings, opts).await,
+        SubCommand::Task(TaskOpts {
+            subcmd: TaskCommand::Requeue(opts),
+        }) => command_task_requeue(gsettings, opts).await,
         SubCommand::Resubmit(opts) => command_resubmit(gsettings, opts).await,
         SubCommand::Wait(opts) => command_wait(gsettings, opts).await,
         SubCommand::Log(opts) => command_log(gsettings, opts),
+        SubCommand::Alloc(opts) => command_autoalloc(gsettings, opts).await,
     };
     if let Err(e) = result {
         eprintln!("{:?}", e);
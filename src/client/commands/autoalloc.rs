@@ -0,0 +1,245 @@
+use chrono::{DateTime, Utc};
+use clap::Clap;
+use cli_table::format::Justify;
+use cli_table::{print_stdout, Cell, CellStruct, Color, Style, Table};
+use humantime::format_duration;
+
+use crate::client::globalsettings::GlobalSettings;
+use crate::client::utils::format_datetime;
+use crate::common::timeutils::ArgDuration;
+use crate::rpc_call;
+use crate::server::autoalloc::QueueId;
+use crate::server::bootstrap::get_client_connection;
+use crate::transfer::connection::ClientConnection;
+use crate::transfer::messages::{
+    AddQueueRequest, AllocationInfo, AllocationQueueInfo, AllocationStatus, AutoAllocRequest,
+    AutoAllocResponse, FromClientMessage, ManagerType, ToClientMessage,
+};
+
+#[derive(Clap)]
+#[clap(setting = clap::AppSettings::ColoredHelp)]
+pub struct AutoAllocOpts {
+    #[clap(subcommand)]
+    subcmd: AutoAllocCommand,
+}
+
+#[derive(Clap)]
+enum AutoAllocCommand {
+    /// Display allocation queues
+    List,
+    /// Display allocations of an allocation queue
+    Info(QueueIdOpts),
+    /// Create an allocation queue that submits PBS/SLURM jobs with workers
+    Add(AddQueueOpts),
+    /// Remove an allocation queue, its submitted allocations are not affected
+    Remove(QueueIdOpts),
+}
+
+#[derive(Clap)]
+#[clap(setting = clap::AppSettings::ColoredHelp)]
+struct QueueIdOpts {
+    /// Id of the allocation queue
+    queue_id: QueueId,
+}
+
+#[derive(Clap)]
+#[clap(setting = clap::AppSettings::ColoredHelp)]
+struct AddQueueOpts {
+    #[clap(subcommand)]
+    manager: AddQueueCommand,
+}
+
+#[derive(Clap)]
+enum AddQueueCommand {
+    /// Submit allocations to PBS
+    Pbs(PbsQueueOpts),
+    /// Submit allocations to SLURM
+    Slurm(SlurmQueueOpts),
+}
+
+#[derive(Clap)]
+#[clap(setting = clap::AppSettings::ColoredHelp)]
+struct SharedQueueOpts {
+    /// Walltime of each allocation (e.g. "1h")
+    #[clap(long)]
+    time_limit: ArgDuration,
+
+    /// Maximal number of workers (allocations) that are queued or running at the same time
+    #[clap(long, default_value = "1")]
+    max_workers: u32,
+
+    /// Maximal number of allocations that wait in the queue of the manager at the same time
+    #[clap(long, default_value = "1")]
+    backlog: u32,
+
+    /// Additional arguments passed to `qsub`/`sbatch`
+    #[clap(last = true)]
+    additional_args: Vec<String>,
+}
+
+#[derive(Clap)]
+#[clap(setting = clap::AppSettings::ColoredHelp)]
+struct PbsQueueOpts {
+    /// PBS queue
+    #[clap(long)]
+    queue: String,
+
+    #[clap(flatten)]
+    shared: SharedQueueOpts,
+}
+
+#[derive(Clap)]
+#[clap(setting = clap::AppSettings::ColoredHelp)]
+struct SlurmQueueOpts {
+    /// SLURM partition
+    #[clap(long)]
+    partition: String,
+
+    #[clap(flatten)]
+    shared: SharedQueueOpts,
+}
+
+pub async fn command_autoalloc(
+    gsettings: GlobalSettings,
+    opts: AutoAllocOpts,
+) -> anyhow::Result<()> {
+    let mut connection = get_client_connection(gsettings.server_directory()).await?;
+    let request = match opts.subcmd {
+        AutoAllocCommand::List => AutoAllocRequest::List,
+        AutoAllocCommand::Info(opts) => AutoAllocRequest::Info {
+            queue_id: opts.queue_id,
+        },
+        AutoAllocCommand::Add(opts) => AutoAllocRequest::AddQueue(create_queue_request(opts)?),
+        AutoAllocCommand::Remove(opts) => AutoAllocRequest::RemoveQueue {
+            queue_id: opts.queue_id,
+        },
+    };
+    match send_autoalloc_request(&mut connection, request).await? {
+        AutoAllocResponse::Queues(queues) => print_allocation_queues(&gsettings, queues),
+        AutoAllocResponse::Allocations(allocations) => print_allocations(&gsettings, allocations),
+        AutoAllocResponse::QueueCreated(id) => log::info!("Allocation queue {} was created", id),
+        AutoAllocResponse::QueueRemoved(id) => log::info!("Allocation queue {} was removed", id),
+        AutoAllocResponse::InvalidQueue(id) => {
+            anyhow::bail!("Allocation queue {} does not exist", id)
+        }
+    }
+    Ok(())
+}
+
+fn create_queue_request(opts: AddQueueOpts) -> anyhow::Result<AddQueueRequest> {
+    let (manager, queue, shared) = match opts.manager {
+        AddQueueCommand::Pbs(opts) => (ManagerType::Pbs, opts.queue, opts.shared),
+        AddQueueCommand::Slurm(opts) => (ManagerType::Slurm, opts.partition, opts.shared),
+    };
+    let time_limit = shared.time_limit.into_duration();
+    if time_limit.as_secs() == 0 {
+        anyhow::bail!("--time-limit has to be at least one second");
+    }
+    if shared.max_workers == 0 {
+        anyhow::bail!("--max-workers has to be at least 1");
+    }
+    if shared.backlog == 0 {
+        anyhow::bail!("--backlog has to be at least 1");
+    }
+    Ok(AddQueueRequest {
+        manager,
+        queue,
+        time_limit,
+        max_workers: shared.max_workers,
+        backlog: shared.backlog,
+        additional_args: shared.additional_args,
+    })
+}
+
+async fn send_autoalloc_request(
+    connection: &mut ClientConnection,
+    request: AutoAllocRequest,
+) -> anyhow::Result<AutoAllocResponse> {
+    let response = rpc_call!(
+        connection,
+        FromClientMessage::AutoAlloc(request),
+        ToClientMessage::AutoAllocResponse(r) => r
+    )
+    .await?;
+    Ok(response)
+}
+
+fn print_allocation_queues(gsettings: &GlobalSettings, queues: Vec<AllocationQueueInfo>) {
+    let rows: Vec<_> = queues
+        .into_iter()
+        .map(|queue| {
+            vec![
+                queue.id.cell().justify(Justify::Right),
+                if queue.stopped {
+                    "STOPPED".cell().foreground_color(Some(Color::Red))
+                } else {
+                    "ACTIVE".cell().foreground_color(Some(Color::Green))
+                },
+                queue.manager.cell(),
+                queue.queue.cell(),
+                format_duration(queue.time_limit).cell(),
+                queue.max_workers.cell(),
+                queue.backlog.cell(),
+                queue.n_queued.cell(),
+                queue.n_running.cell(),
+                queue.additional_args.join(" ").cell(),
+            ]
+        })
+        .collect();
+
+    let table = rows
+        .table()
+        .color_choice(gsettings.color_policy())
+        .title(vec![
+            "Id".cell().bold(true),
+            "State".cell().bold(true),
+            "Manager".cell().bold(true),
+            "Queue".cell().bold(true),
+            "Time limit".cell().bold(true),
+            "Max workers".cell().bold(true),
+            "Backlog".cell().bold(true),
+            "Queued".cell().bold(true),
+            "Running".cell().bold(true),
+            "Args".cell().bold(true),
+        ]);
+    assert!(print_stdout(table).is_ok());
+}
+
+fn allocation_status(status: AllocationStatus) -> CellStruct {
+    match status {
+        AllocationStatus::Queued => "QUEUED".cell().foreground_color(Some(Color::Cyan)),
+        AllocationStatus::Running => "RUNNING".cell().foreground_color(Some(Color::Yellow)),
+        AllocationStatus::Finished => "FINISHED".cell().foreground_color(Some(Color::Green)),
+        AllocationStatus::Failed => "FAILED".cell().foreground_color(Some(Color::Red)),
+    }
+}
+
+fn print_allocations(gsettings: &GlobalSettings, allocations: Vec<AllocationInfo>) {
+    let format_time = |time: Option<DateTime<Utc>>| time.map(format_datetime).unwrap_or_default();
+    let rows: Vec<_> = allocations
+        .into_iter()
+        .map(|allocation| {
+            vec![
+                allocation.id.cell(),
+                allocation_status(allocation.status),
+                allocation.working_dir.display().cell(),
+                format_datetime(allocation.queued_at).cell(),
+                format_time(allocation.started_at).cell(),
+                format_time(allocation.ended_at).cell(),
+            ]
+        })
+        .collect();
+
+    let table = rows
+        .table()
+        .color_choice(gsettings.color_policy())
+        .title(vec![
+            "Id".cell().bold(true),
+            "State".cell().bold(true),
+            "Working directory".cell().bold(true),
+            "Queued at".cell().bold(true),
+            "Started at".cell().bold(true),
+            "Ended at".cell().bold(true),
+        ]);
+    assert!(print_stdout(table).is_ok());
+}
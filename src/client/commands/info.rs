@@ -0,0 +1,24 @@
+use cli_table::{print_stdout, Cell, Style, Table};
+
+use crate::client::commands::pools::{format_resource_pools, get_resource_pools};
+use crate::client::globalsettings::GlobalSettings;
+use crate::server::bootstrap::{
+    access_record_rows, get_client_connection, get_online_access_record,
+};
+
+pub async fn print_server_info(gsettings: &GlobalSettings) -> anyhow::Result<()> {
+    let record = get_online_access_record(gsettings.server_directory()).await?;
+    let mut connection = get_client_connection(gsettings.server_directory()).await?;
+    let pools = get_resource_pools(&mut connection).await?;
+
+    let mut rows = access_record_rows(gsettings.server_directory(), &record);
+    if !pools.is_empty() {
+        rows.push(vec![
+            "Resource pools".cell().bold(true),
+            format_resource_pools(&pools).cell(),
+        ]);
+    }
+    let table = rows.table().color_choice(gsettings.color_policy());
+    assert!(print_stdout(table).is_ok());
+    Ok(())
+}
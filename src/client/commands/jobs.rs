@@ -1,17 +1,21 @@
 use crate::client::globalsettings::GlobalSettings;
 use crate::client::job::{get_worker_map, print_job_detail, print_job_list};
 use crate::client::status::{job_status, Status};
+use crate::common::arraydef::ArrayDef;
 use crate::rpc_call;
+use crate::server::job::JobTaskState;
 use crate::transfer::connection::ClientConnection;
 use crate::transfer::messages::{
-    CancelJobResponse, CancelRequest, FromClientMessage, JobDetailRequest, JobInfoRequest,
-    JobSelector, ToClientMessage,
+    CancelJobResponse, CancelRequest, FromClientMessage, HoldJobResponse, HoldRequest,
+    JobDetailRequest, JobInfoRequest, JobSelector, RequeueRequest, RequeueResponse,
+    ToClientMessage, UpdateJobRequest, UpdateJobResponse,
 };
-use crate::JobId;
+use crate::{JobId, JobTaskCount, JobTaskId};
 
 pub async fn get_last_job_id(connection: &mut ClientConnection) -> crate::Result<Option<JobId>> {
     let message = FromClientMessage::JobInfo(JobInfoRequest {
         selector: JobSelector::LastN(1),
+        exit_code: None,
     });
     let response = rpc_call!(connection, message, ToClientMessage::JobInfoResponse(r) => r).await?;
 
@@ -21,6 +25,7 @@ pub async fn get_last_job_id(connection: &mut ClientConnection) -> crate::Result
 pub async fn get_job_ids(connection: &mut ClientConnection) -> crate::Result<Option<Vec<JobId>>> {
     let message = FromClientMessage::JobInfo(JobInfoRequest {
         selector: JobSelector::All,
+        exit_code: None,
     });
     let response = rpc_call!(connection, message, ToClientMessage::JobInfoResponse(r) => r).await?;
 
@@ -31,13 +36,38 @@ pub async fn get_job_ids(connection: &mut ClientConnection) -> crate::Result<Opt
     Ok(Option::from(ids))
 }
 
+/// Returns sorted ids of jobs chosen by a selector.
+/// Specific ids are returned even when the jobs do not exist, so that missing jobs can be reported,
+/// only ranges are cut at the last job id.
+pub async fn resolve_job_ids(
+    connection: &mut ClientConnection,
+    selector: JobSelector,
+) -> crate::Result<Vec<JobId>> {
+    if let JobSelector::Specific(ids) = selector {
+        let last_id = get_last_job_id(connection).await?.unwrap_or(0);
+        let mut ids: Vec<JobId> = ids.iter_up_to(last_id).collect();
+        ids.sort_unstable();
+        return Ok(ids);
+    }
+    let message = FromClientMessage::JobInfo(JobInfoRequest {
+        selector,
+        exit_code: None,
+    });
+    let response = rpc_call!(connection, message, ToClientMessage::JobInfoResponse(r) => r).await?;
+    let mut ids: Vec<JobId> = response.jobs.iter().map(|job| job.id).collect();
+    ids.sort_unstable();
+    Ok(ids)
+}
+
 pub async fn output_job_list(
     gsettings: &GlobalSettings,
     connection: &mut ClientConnection,
     job_filters: Vec<Status>,
+    exit_code: Option<i32>,
 ) -> crate::Result<()> {
     let message = FromClientMessage::JobInfo(JobInfoRequest {
         selector: JobSelector::All,
+        exit_code,
     });
     let mut response =
         rpc_call!(connection, message, ToClientMessage::JobInfoResponse(r) => r).await?;
@@ -55,26 +85,36 @@ pub async fn output_job_list(
 pub async fn output_job_detail(
     gsettings: &GlobalSettings,
     connection: &mut ClientConnection,
-    job_id: JobId,
+    selector: JobSelector,
     show_tasks: bool,
+    exit_code: Option<i32>,
 ) -> crate::Result<()> {
-    let message = FromClientMessage::JobDetail(JobDetailRequest {
-        job_id,
-        include_tasks: true,
-    });
-    let response =
-        rpc_call!(connection, message, ToClientMessage::JobDetailResponse(r) => r).await?;
-
-    if let Some(job) = response {
-        print_job_detail(
-            gsettings,
-            job,
-            false,
-            show_tasks,
-            get_worker_map(connection).await?,
-        );
-    } else {
-        log::error!("Job {} not found", job_id);
+    let job_ids = resolve_job_ids(connection, selector).await?;
+    if job_ids.is_empty() {
+        log::warn!("No jobs were found");
+        return Ok(());
+    }
+
+    let worker_map = get_worker_map(connection).await?;
+    for job_id in job_ids {
+        let message = FromClientMessage::JobDetail(JobDetailRequest {
+            job_id,
+            include_tasks: true,
+        });
+        let response =
+            rpc_call!(connection, message, ToClientMessage::JobDetailResponse(r) => r).await?;
+
+        if let Some(mut job) = response {
+            if let Some(code) = exit_code {
+                job.tasks.retain(|task| match &task.state {
+                    JobTaskState::Failed { failure, .. } => failure.exit_code() == Some(code),
+                    _ => false,
+                });
+            }
+            print_job_detail(gsettings, job, false, show_tasks, worker_map.clone());
+        } else {
+            log::error!("Job {} not found", job_id);
+        }
     }
     Ok(())
 }
@@ -83,9 +123,11 @@ pub async fn cancel_job(
     _gsettings: &GlobalSettings,
     connection: &mut ClientConnection,
     selector: JobSelector,
+    tasks: Option<ArrayDef>,
 ) -> crate::Result<()> {
     let mut responses = rpc_call!(connection, FromClientMessage::Cancel(CancelRequest {
          selector,
+         tasks,
     }), ToClientMessage::CancelJobResponse(r) => r)
     .await?;
     responses.sort_unstable_by_key(|x| x.0);
@@ -96,19 +138,39 @@ pub async fn cancel_job(
 
     for (job_id, response) in responses {
         match response {
-            CancelJobResponse::Canceled(canceled, already_finished) if !canceled.is_empty() => {
-                log::info!(
-                    "Job {} canceled ({} tasks canceled, {} tasks already finished)",
-                    job_id,
-                    canceled.len(),
-                    already_finished
-                )
-            }
-            CancelJobResponse::Canceled(_, _) => {
-                log::error!(
-                    "Canceling job {} failed; all tasks are already finished",
-                    job_id
-                )
+            CancelJobResponse::Canceled {
+                canceled,
+                dependants,
+                already_finished,
+                invalid,
+            } => {
+                if !invalid.is_empty() {
+                    log::warn!(
+                        "Job {} does not contain tasks {}",
+                        job_id,
+                        format_task_ids(&invalid)
+                    )
+                }
+                if !canceled.is_empty() {
+                    log::info!(
+                        "Job {} canceled ({} tasks canceled, {} tasks already finished)",
+                        job_id,
+                        canceled.len(),
+                        already_finished.len()
+                    )
+                } else if !already_finished.is_empty() {
+                    log::error!(
+                        "Canceling job {} failed; all tasks are already finished",
+                        job_id
+                    )
+                }
+                if !dependants.is_empty() {
+                    log::info!(
+                        "{} dependent tasks of job {} were canceled too",
+                        dependants.len(),
+                        job_id
+                    )
+                }
             }
             CancelJobResponse::InvalidJob => {
                 log::error!("Canceling job {} failed; job not found", job_id)
@@ -120,3 +182,141 @@ pub async fn cancel_job(
     }
     Ok(())
 }
+
+/// Holds (`hold: true`) or releases (`hold: false`) selected jobs
+pub async fn hold_job(
+    _gsettings: &GlobalSettings,
+    connection: &mut ClientConnection,
+    selector: JobSelector,
+    hold: bool,
+) -> crate::Result<()> {
+    let mut responses = rpc_call!(connection, FromClientMessage::Hold(HoldRequest {
+         selector,
+         hold,
+    }), ToClientMessage::HoldJobResponse(r) => r)
+    .await?;
+    responses.sort_unstable_by_key(|x| x.0);
+
+    let action = if hold { "Holding" } else { "Releasing" };
+    if responses.is_empty() {
+        log::info!(
+            "There is no job to {}",
+            if hold { "hold" } else { "release" }
+        )
+    }
+
+    for (job_id, response) in responses {
+        match response {
+            HoldJobResponse::Held(count) => {
+                log::info!("Job {} held ({} tasks held)", job_id, count)
+            }
+            HoldJobResponse::Released(count) => {
+                log::info!("Job {} released ({} tasks released)", job_id, count)
+            }
+            HoldJobResponse::NotHeld => {
+                log::error!("{} job {} failed; job is not held", action, job_id)
+            }
+            HoldJobResponse::Terminated => {
+                log::error!(
+                    "{} job {} failed; all tasks are already finished",
+                    action,
+                    job_id
+                )
+            }
+            HoldJobResponse::InvalidJob => {
+                log::error!("{} job {} failed; job not found", action, job_id)
+            }
+            HoldJobResponse::Failed(msg) => {
+                log::error!("{} job {} failed; {}", action, job_id, msg)
+            }
+        }
+    }
+    Ok(())
+}
+
+pub async fn update_job(
+    _gsettings: &GlobalSettings,
+    connection: &mut ClientConnection,
+    job_id: JobId,
+    priority: Option<tako::Priority>,
+    max_fails: Option<JobTaskCount>,
+) -> crate::Result<()> {
+    let response = rpc_call!(connection, FromClientMessage::UpdateJob(UpdateJobRequest {
+         job_id,
+         priority,
+         max_fails,
+    }), ToClientMessage::UpdateJobResponse(r) => r)
+    .await?;
+
+    match response {
+        UpdateJobResponse::Updated(count) => {
+            log::info!(
+                "Job {} updated ({} waiting tasks reprioritized)",
+                job_id,
+                count
+            )
+        }
+        UpdateJobResponse::InvalidJob => {
+            log::error!("Updating job {} failed; job not found", job_id)
+        }
+        UpdateJobResponse::Failed(msg) => {
+            log::error!("Updating job {} failed; {}", job_id, msg)
+        }
+    }
+    Ok(())
+}
+
+/// Restarts selected running tasks of a job
+pub async fn requeue_tasks(
+    _gsettings: &GlobalSettings,
+    connection: &mut ClientConnection,
+    job_id: JobId,
+    tasks: ArrayDef,
+) -> crate::Result<()> {
+    let response = rpc_call!(connection, FromClientMessage::Requeue(RequeueRequest {
+         job_id,
+         tasks,
+    }), ToClientMessage::RequeueResponse(r) => r)
+    .await?;
+
+    match response {
+        RequeueResponse::Requeued {
+            requeued,
+            not_running,
+            invalid,
+        } => {
+            if !invalid.is_empty() {
+                log::warn!(
+                    "Job {} does not contain tasks {}",
+                    job_id,
+                    format_task_ids(&invalid)
+                )
+            }
+            if !not_running.is_empty() {
+                log::warn!(
+                    "Tasks {} of job {} are not running",
+                    format_task_ids(&not_running),
+                    job_id
+                )
+            }
+            log::info!("Job {}: {} tasks requeued", job_id, requeued.len())
+        }
+        RequeueResponse::InvalidJob => {
+            log::error!("Requeuing tasks of job {} failed; job not found", job_id)
+        }
+        RequeueResponse::Failed(msg) => {
+            log::error!("Requeuing tasks of job {} failed; {}", job_id, msg)
+        }
+    }
+    Ok(())
+}
+
+fn format_task_ids(task_ids: &[JobTaskId]) -> String {
+    let mut task_ids = task_ids.to_vec();
+    task_ids.sort_unstable();
+    task_ids
+        .iter()
+        .map(|id| id.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
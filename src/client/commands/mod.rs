@@ -1,5 +1,8 @@
+pub mod autoalloc;
+pub mod info;
 pub mod jobs;
 pub mod log;
+pub mod pools;
 pub mod stats;
 pub mod stop;
 pub mod submit;
@@ -0,0 +1,111 @@
+use std::str::FromStr;
+
+use cli_table::{print_stdout, Cell, Style, Table};
+
+use crate::client::globalsettings::GlobalSettings;
+use crate::rpc_call;
+use crate::server::pools::PoolAmount;
+use crate::transfer::connection::ClientConnection;
+use crate::transfer::messages::{
+    FromClientMessage, ResourcePoolInfo, ResourcePoolRequest, ToClientMessage,
+};
+
+/// Amount of a resource pool in the format `<name>=<amount>`
+#[derive(Debug)]
+pub struct ArgPoolAmount {
+    pub name: String,
+    pub amount: PoolAmount,
+}
+
+impl FromStr for ArgPoolAmount {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let position = match s.find('=') {
+            Some(position) => position,
+            None => anyhow::bail!("Resource pool has to be in the format <name>=<amount>"),
+        };
+        let name = &s[..position];
+        if name.is_empty() || name.contains(',') {
+            anyhow::bail!("Invalid resource pool name '{}'", name);
+        }
+        let amount = s[position + 1..]
+            .parse::<PoolAmount>()
+            .map_err(|_| anyhow::anyhow!("Invalid amount '{}'", &s[position + 1..]))?;
+        Ok(ArgPoolAmount {
+            name: name.to_string(),
+            amount,
+        })
+    }
+}
+
+pub async fn get_resource_pools(
+    connection: &mut ClientConnection,
+) -> anyhow::Result<Vec<ResourcePoolInfo>> {
+    let pools = rpc_call!(
+        connection,
+        FromClientMessage::ResourcePools,
+        ToClientMessage::ResourcePoolsResponse(r) => r
+    )
+    .await?;
+    Ok(pools)
+}
+
+pub async fn set_resource_pool(
+    gsettings: &GlobalSettings,
+    connection: &mut ClientConnection,
+    pool: ArgPoolAmount,
+) -> anyhow::Result<()> {
+    let pools = rpc_call!(
+        connection,
+        FromClientMessage::SetResourcePool(ResourcePoolRequest {
+            name: pool.name,
+            size: pool.amount,
+        }),
+        ToClientMessage::ResourcePoolsResponse(r) => r
+    )
+    .await?;
+    print_resource_pools(gsettings, &pools);
+    Ok(())
+}
+
+pub fn format_resource_pools(pools: &[ResourcePoolInfo]) -> String {
+    pools
+        .iter()
+        .map(|pool| format!("{}: {}/{} used", pool.name, pool.used, pool.size))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+pub fn print_resource_pools(gsettings: &GlobalSettings, pools: &[ResourcePoolInfo]) {
+    let rows: Vec<_> = pools
+        .iter()
+        .map(|pool| vec![pool.name.cell(), pool.size.cell(), pool.used.cell()])
+        .collect();
+    let table = rows
+        .table()
+        .color_choice(gsettings.color_policy())
+        .title(vec![
+            "Name".cell().bold(true),
+            "Size".cell().bold(true),
+            "Used".cell().bold(true),
+        ]);
+    assert!(print_stdout(table).is_ok());
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::ArgPoolAmount;
+
+    #[test]
+    fn test_parse_pool_amount() {
+        let pool = ArgPoolAmount::from_str("matlab=10").unwrap();
+        assert_eq!(pool.name, "matlab");
+        assert_eq!(pool.amount, 10);
+        assert!(ArgPoolAmount::from_str("matlab").is_err());
+        assert!(ArgPoolAmount::from_str("=1").is_err());
+        assert!(ArgPoolAmount::from_str("matlab=x").is_err());
+    }
+}
@@ -4,23 +4,32 @@ use std::str::FromStr;
 use std::{fs, io};
 
 use anyhow::anyhow;
-use bstr::BString;
+use bstr::{BString, ByteSlice};
 use clap::Clap;
 use hashbrown::HashMap;
 use tako::common::resources::{CpuRequest, ResourceRequest};
 use tako::messages::common::{ProgramDefinition, StdioDef};
 
+use crate::client::commands::jobs::resolve_job_ids;
+use crate::client::commands::pools::ArgPoolAmount;
 use crate::client::commands::wait::wait_for_job_with_info;
 use crate::client::globalsettings::GlobalSettings;
 use crate::client::job::{get_worker_map, print_job_detail};
-use crate::client::resources::parse_cpu_request;
+use crate::client::jobfile::{read_job_file, JobFile};
+use crate::client::resources::{parse_cpu_request, parse_resource_request, GenericResourceRequest};
 use crate::client::status::StatusList;
 use crate::common::arraydef::ArrayDef;
+use crate::common::arrayparser::parse_task_dependency;
+use crate::common::entries::{parse_csv_record, parse_entry_fields, EntryFormat};
+use crate::common::labels::validate_label;
+use crate::common::timeutils::ArgDuration;
+use crate::server::pools::PoolUsage;
 use crate::transfer::connection::ClientConnection;
 use crate::transfer::messages::{
-    FromClientMessage, JobType, ResubmitRequest, SubmitRequest, ToClientMessage,
+    FromClientMessage, JobDependency, JobDependencyMode, JobSelector, JobType, ResubmitOverrides,
+    ResubmitRequest, SubmitRequest, TaskDependency, TaskGroup, ToClientMessage,
 };
-use crate::{rpc_call, JobId, JobTaskCount};
+use crate::{rpc_call, JobTaskCount, JobTaskId, Set};
 
 const DEFAULT_STDOUT_PATH: &str = "stdout.%{JOB_ID}.%{TASK_ID}";
 const DEFAULT_STDERR_PATH: &str = "stderr.%{JOB_ID}.%{TASK_ID}";
@@ -35,6 +44,16 @@ impl FromStr for ArgCpuRequest {
     }
 }
 
+struct ArgResourceRequest(GenericResourceRequest);
+
+impl FromStr for ArgResourceRequest {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_resource_request(s).map(ArgResourceRequest)
+    }
+}
+
 #[derive(Debug)]
 pub struct ArgEnvironmentVar {
     key: BString,
@@ -62,6 +81,129 @@ impl FromStr for ArgEnvironmentVar {
     }
 }
 
+/// Dependency of a task in the format `<task-id>:<task-id>,<task-id>,...`
+struct ArgTaskDependency(TaskDependency);
+
+impl FromStr for ArgTaskDependency {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (task_id, depends_on) = parse_task_dependency(s)?;
+        Ok(ArgTaskDependency(TaskDependency {
+            task_id,
+            depends_on,
+        }))
+    }
+}
+
+/// Task array in the format of `ArrayDef`, optionally followed by `%<n>` that limits
+/// the number of tasks that run at the same time (e.g. `1-1000%20`)
+struct ArgArray {
+    def: ArrayDef,
+    max_parallel: Option<JobTaskCount>,
+}
+
+impl FromStr for ArgArray {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (def, max_parallel) = match s.rfind('%') {
+            Some(position) => {
+                let max_parallel = s[position + 1..]
+                    .parse::<JobTaskCount>()
+                    .map_err(|_| anyhow!("Invalid parallelism limit '{}'", &s[position + 1..]))?;
+                (&s[..position], Some(max_parallel))
+            }
+            None => (s, None),
+        };
+        Ok(ArgArray {
+            def: ArrayDef::from_str(def)?,
+            max_parallel,
+        })
+    }
+}
+
+/// Maximal number of tasks created by `--param`
+const MAX_PARAM_COMBINATIONS: usize = 1_000_000;
+
+/// Parameter of a parameter sweep in the format `<name>=<value>,<value>,...`,
+/// a value may be also an inclusive range of integers `<start>..<end>`
+#[derive(Debug)]
+struct ArgParam {
+    name: String,
+    values: Vec<String>,
+}
+
+impl FromStr for ArgParam {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let position = match s.find('=') {
+            Some(position) => position,
+            None => anyhow::bail!("Parameter has to be in the format <name>=<values>"),
+        };
+        let name = &s[..position];
+        if !is_valid_param_name(name) {
+            anyhow::bail!(
+                "Invalid parameter name '{}', it has to match [A-Za-z_][A-Za-z0-9_]*",
+                name
+            );
+        }
+        let mut values = Vec::new();
+        for item in s[position + 1..].split(',') {
+            match item.find("..") {
+                Some(range_pos) => {
+                    let (start, end) = (&item[..range_pos], &item[range_pos + 2..]);
+                    match (start.parse::<i64>(), end.parse::<i64>()) {
+                        (Ok(start), Ok(end)) if start <= end => {
+                            if end as i128 - start as i128 >= MAX_PARAM_COMBINATIONS as i128 {
+                                anyhow::bail!(
+                                    "Range '{}' has more than {} values",
+                                    item,
+                                    MAX_PARAM_COMBINATIONS
+                                );
+                            }
+                            values.extend((start..=end).map(|v| v.to_string()))
+                        }
+                        _ => anyhow::bail!("Invalid range '{}'", item),
+                    }
+                }
+                None => values.push(item.to_string()),
+            }
+        }
+        Ok(ArgParam {
+            name: name.to_string(),
+            values,
+        })
+    }
+}
+
+/// Parameter names are passed in environment variables, so they have to be valid identifiers
+fn is_valid_param_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Required outcome of jobs referenced by `--after`
+struct ArgJobDependencyMode(JobDependencyMode);
+
+impl FromStr for ArgJobDependencyMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(ArgJobDependencyMode(match s {
+            "ok" => JobDependencyMode::Ok,
+            "any" => JobDependencyMode::Any,
+            "notok" => JobDependencyMode::NotOk,
+            _ => anyhow::bail!("Invalid dependency mode, use one of: ok, any, notok"),
+        }))
+    }
+}
+
 /// Represents a filepath. If "none" is passed to it, it will behave as if no path is needed.
 struct StdioArg(StdioDef);
 
@@ -79,9 +221,14 @@ impl FromStr for StdioArg {
 #[derive(Clap)]
 #[clap(setting = clap::AppSettings::ColoredHelp)]
 pub struct SubmitOpts {
-    command: String,
+    command: Option<String>,
     args: Vec<String>,
 
+    /// Submit a job defined by a TOML (or JSON) file with groups of tasks.
+    /// Options given on the command line are used for groups that do not specify them
+    #[clap(long, value_hint = clap::ValueHint::FilePath)]
+    job_file: Option<PathBuf>,
+
     /// Number and placement of CPUs for each job
     #[clap(long, default_value = "1")]
     cpus: ArgCpuRequest,
@@ -122,6 +269,28 @@ pub struct SubmitOpts {
     #[clap(long, conflicts_with("array"), value_hint = clap::ValueHint::FilePath)]
     each_line: Option<PathBuf>,
 
+    /// Create a task array where a task will be created for each JSON object in the given file
+    /// (one object per line). The line is passed in `HQ_ENTRY` and each key of the object
+    /// in a variable `HQ_ENTRY_<key>`, it can be also used as a placeholder `%{ENTRY:<key>}`.
+    #[clap(long, value_hint = clap::ValueHint::FilePath)]
+    from_json: Option<PathBuf>,
+
+    /// Create a task array where a task will be created for each record of the given CSV file.
+    /// The first line is a header with column names. The record is passed in `HQ_ENTRY` and each
+    /// column in a variable `HQ_ENTRY_<column>`, it can be also used as a placeholder
+    /// `%{ENTRY:<column>}`.
+    #[clap(long, value_hint = clap::ValueHint::FilePath)]
+    from_csv: Option<PathBuf>,
+
+    /// Create a task array from a Cartesian product of parameter values.
+    /// You can pass this flag multiple times to define more parameters.
+    /// Each parameter is passed in a variable `HQ_PARAM_<name>`, it can be also used
+    /// as a placeholder `%{PARAM:<name>}`.
+    ///
+    /// `--param lr=0.1,0.01 --param depth=3..8` - create 12 tasks
+    #[clap(long, multiple_occurrences(true))]
+    param: Vec<ArgParam>,
+
     #[clap(long)]
     /// Create a task array where a task will be created for each number in the specified number range.
     /// Each task will be passed an environment variable `HQ_TASK_ID`.
@@ -129,14 +298,88 @@ pub struct SubmitOpts {
     /// `--array=5` - create task array with one job with task ID 5
     ///
     /// `--array=3-5` - create task array with three jobs with task IDs 3, 4, 5
-    array: Option<ArrayDef>,
+    ///
+    /// `--array=1-1000%20` - create task array with 1000 tasks, at most 20 of them run at once
+    array: Option<ArgArray>,
+
+    /// Task dependency within a task array.
+    /// You can pass this flag multiple times to define more dependencies
+    ///
+    /// `--task-dep=5:1,2` - task 5 is started after tasks 1 and 2 are finished
+    #[clap(long, multiple_occurrences(true))]
+    task_dep: Vec<ArgTaskDependency>,
+
+    /// Hold the job until the given jobs are terminated
+    ///
+    /// `--after=12,13` - the job is started after jobs 12 and 13 are terminated
+    #[clap(long)]
+    after: Option<ArrayDef>,
+
+    /// Required outcome of jobs given by `--after`
+    ///
+    /// `ok` - all jobs have to finish successfully (default)
+    ///
+    /// `any` - jobs have to terminate, their outcome does not matter
+    ///
+    /// `notok` - all jobs have to terminate unsuccessfully
+    ///
+    /// The job is canceled when the required outcome cannot be reached anymore.
+    #[clap(long, default_value = "ok")]
+    after_mode: ArgJobDependencyMode,
 
     #[clap(long)]
     max_fails: Option<JobTaskCount>,
 
+    /// How many times a failed task is submitted again before it is considered as failed
+    #[clap(long, default_value = "0")]
+    max_retries: u32,
+
+    /// Delay before a failed task is submitted again
+    ///
+    /// `--retry-delay=30s` - a failed task is submitted again after 30 seconds
+    #[clap(long)]
+    retry_delay: Option<ArgDuration>,
+
+    /// Time limit of each task, a task that runs longer is terminated and it fails
+    ///
+    /// `--time-limit=2h` - each task may run at most two hours
+    #[clap(long)]
+    time_limit: Option<ArgDuration>,
+
+    /// Minimal remaining lifetime of a worker that starts a task,
+    /// tasks are not started on workers that would stop before the given time
+    ///
+    /// `--time-request=30m` - each task is started only on a worker that runs at least 30 more minutes
+    #[clap(long)]
+    time_request: Option<ArgDuration>,
+
     #[clap(long, default_value = "0")]
     priority: tako::Priority,
 
+    /// Maximal number of tasks of the job that run at the same time,
+    /// remaining tasks are kept by the server until some tasks are finished
+    #[clap(long)]
+    max_parallel: Option<JobTaskCount>,
+
+    /// Units of a resource pool of the server that are used by each task.
+    /// You can pass this flag multiple times to use more pools
+    ///
+    /// `--use matlab=1` - each task takes one unit of the pool `matlab`
+    #[clap(long = "use", multiple_occurrences(true))]
+    uses: Vec<ArgPoolAmount>,
+
+    /// Generic resource of a worker that is taken by each task, it can be used multiple times.
+    /// A task is started only on a worker that has enough free units of the resource
+    ///
+    /// `--resource gpu=2` - each task takes two units of the resource `gpu`
+    #[clap(long = "resource", multiple_occurrences(true))]
+    generic_resources: Vec<ArgResourceRequest>,
+
+    /// Label that a worker needs to have to run tasks of the job, it can be used multiple times.
+    /// Tasks wait until a worker with all required labels is available
+    #[clap(long = "require", multiple_occurrences(true))]
+    required_labels: Vec<String>,
+
     /// Wait on the job(s) execution.
     #[clap(long)]
     wait: bool,
@@ -158,32 +401,89 @@ pub async fn submit_computation(
 ) -> anyhow::Result<()> {
     let resources = opts.resource_request();
     resources.validate()?;
-    let (job_type, entries) = if let Some(filename) = opts.each_line {
+    let job_file = match &opts.job_file {
+        Some(path) => {
+            if opts.command.is_some() {
+                anyhow::bail!("A command cannot be used together with --job-file");
+            }
+            if opts.array.is_some()
+                || opts.each_line.is_some()
+                || opts.from_json.is_some()
+                || opts.from_csv.is_some()
+                || !opts.param.is_empty()
+            {
+                anyhow::bail!(
+                    "--array, --each-line, --from-json, --from-csv and --param cannot be used together with --job-file"
+                );
+            }
+            Some(read_job_file(path)?)
+        }
+        None if opts.command.is_none() => anyhow::bail!("No command was given"),
+        None => None,
+    };
+    let max_parallel = match (
+        opts.max_parallel,
+        opts.array.as_ref().and_then(|a| a.max_parallel),
+    ) {
+        (Some(n), Some(m)) if n != m => {
+            anyhow::bail!("--max-parallel does not match the limit given in --array")
+        }
+        (n, m) => n.or(m),
+    };
+    if max_parallel == Some(0) {
+        anyhow::bail!("--max-parallel has to be at least 1");
+    }
+    let pool_usage = get_pool_usage(&opts.uses)?;
+    let generic_resources = get_generic_resources(&opts.generic_resources)?;
+    let required_labels = get_required_labels(&opts.required_labels)?;
+    let n_entry_sources = [&opts.each_line, &opts.from_json, &opts.from_csv]
+        .iter()
+        .filter(|source| source.is_some())
+        .count()
+        + if opts.param.is_empty() { 0 } else { 1 };
+    if n_entry_sources > 1 || (n_entry_sources == 1 && opts.array.is_some()) {
+        anyhow::bail!(
+            "Only one of --array, --each-line, --from-json, --from-csv and --param can be used"
+        );
+    }
+    let (job_type, entries, entry_format) = if let Some(filename) = opts.each_line {
         let lines = read_lines(&filename)?;
         let def = ArrayDef::simple_range(0, lines.len() as JobTaskCount);
-        (JobType::Array(def), Some(lines))
+        (JobType::Array(def), Some(lines), EntryFormat::Line)
+    } else if let Some(filename) = opts.from_json {
+        let (lines, format) = read_json_entries(&filename)?;
+        let def = ArrayDef::simple_range(0, lines.len() as JobTaskCount);
+        (JobType::Array(def), Some(lines), format)
+    } else if let Some(filename) = opts.from_csv {
+        let (lines, format) = read_csv_entries(&filename)?;
+        let def = ArrayDef::simple_range(0, lines.len() as JobTaskCount);
+        (JobType::Array(def), Some(lines), format)
+    } else if !opts.param.is_empty() {
+        let entries = expand_params(&opts.param)?;
+        let def = ArrayDef::simple_range(0, entries.len() as JobTaskCount);
+        (JobType::Array(def), Some(entries), EntryFormat::Params)
     } else {
         (
-            opts.array.map(JobType::Array).unwrap_or(JobType::Simple),
+            opts.array
+                .map(|array| JobType::Array(array.def))
+                .unwrap_or(JobType::Simple),
             None,
+            EntryFormat::Line,
         )
     };
 
-    let name = if let Some(name) = opts.name {
-        validate_name(name)?
-    } else {
-        PathBuf::from(&opts.command)
-            .file_name()
-            .and_then(|t| t.to_str().map(|s| s.to_string()))
-            .unwrap_or_else(|| "job".to_string())
+    let name_given = opts.name.is_some();
+    let name = match opts.name {
+        Some(name) => validate_name(name)?,
+        None => default_job_name(opts.command.as_deref().unwrap_or_default()),
     };
 
-    let mut args: Vec<BString> = opts
-        .args
+    let args: Vec<BString> = opts
+        .command
         .iter()
+        .chain(opts.args.iter())
         .map(|x| BString::from(x.as_str()))
         .collect();
-    args.insert(0, opts.command.into());
 
     let cwd = Some(opts.cwd);
     let log = opts.log;
@@ -215,7 +515,7 @@ pub async fn submit_computation(
         )
     }
 
-    let message = FromClientMessage::Submit(SubmitRequest {
+    let mut request = SubmitRequest {
         job_type,
         name,
         spec: ProgramDefinition {
@@ -228,11 +528,33 @@ pub async fn submit_computation(
         resources,
         pin: opts.pin,
         entries,
+        entry_format,
         max_fails: opts.max_fails,
         submit_dir: std::env::current_dir().unwrap().to_str().unwrap().into(),
         priority: opts.priority,
         log,
-    });
+        task_deps: opts.task_dep.into_iter().map(|d| d.0).collect(),
+        after: opts.after.map(|jobs| JobDependency {
+            jobs: jobs.iter().collect(),
+            mode: opts.after_mode.0,
+        }),
+        max_retries: opts.max_retries,
+        retry_delay: opts
+            .retry_delay
+            .map(|d| d.into_duration())
+            .unwrap_or_default(),
+        time_limit: opts.time_limit.map(|d| d.into_duration()),
+        time_request: opts.time_request.map(|d| d.into_duration()),
+        task_groups: Vec::new(),
+        max_parallel,
+        pool_usage,
+        generic_resources,
+        required_labels,
+    };
+    if let Some(job_file) = job_file {
+        apply_job_file(&mut request, job_file, name_given)?;
+    }
+    let message = FromClientMessage::Submit(request);
 
     let response = rpc_call!(connection, message, ToClientMessage::SubmitResponse(r) => r).await?;
     let info = response.job.info.clone();
@@ -250,14 +572,295 @@ pub async fn submit_computation(
     Ok(())
 }
 
+/// Units of resource pools used by each task, pools with zero units are omitted
+fn get_pool_usage(uses: &[ArgPoolAmount]) -> anyhow::Result<PoolUsage> {
+    let mut pool_names = Set::new();
+    if let Some(pool) = uses.iter().find(|p| !pool_names.insert(p.name.as_str())) {
+        anyhow::bail!("Resource pool {} is used more than once", pool.name);
+    }
+    Ok(uses
+        .iter()
+        .filter(|p| p.amount > 0)
+        .map(|p| (p.name.clone(), p.amount))
+        .collect())
+}
+
+fn get_generic_resources(
+    requests: &[ArgResourceRequest],
+) -> anyhow::Result<Vec<GenericResourceRequest>> {
+    let mut resource_names = Set::new();
+    if let Some(request) = requests
+        .iter()
+        .find(|r| !resource_names.insert(r.0.name.as_str()))
+    {
+        anyhow::bail!("Resource {} is requested more than once", request.0.name);
+    }
+    Ok(requests.iter().map(|r| r.0.clone()).collect())
+}
+
+fn get_required_labels(labels: &[String]) -> anyhow::Result<Vec<String>> {
+    for label in labels {
+        validate_label(label)?;
+    }
+    let mut required_labels = labels.to_vec();
+    required_labels.sort_unstable();
+    required_labels.dedup();
+    Ok(required_labels)
+}
+
+fn default_job_name(command: &str) -> String {
+    PathBuf::from(command)
+        .file_name()
+        .and_then(|t| t.to_str().map(|s| s.to_string()))
+        .unwrap_or_else(|| "job".to_string())
+}
+
+/// Replaces tasks of a submit request by task groups of a job file,
+/// options of the request are used for groups that do not specify them
+fn apply_job_file(
+    request: &mut SubmitRequest,
+    job_file: JobFile,
+    name_given: bool,
+) -> anyhow::Result<()> {
+    let JobFile {
+        name,
+        max_fails,
+        groups: group_defs,
+    } = job_file;
+    let n_groups = group_defs.len();
+    let mut task_ids: Set<JobTaskId> = Set::new();
+    let mut groups: Vec<TaskGroup> = Vec::with_capacity(n_groups);
+    let mut has_array = false;
+    for (index, group) in group_defs.into_iter().enumerate() {
+        let tasks = match &group.array {
+            Some(array) => {
+                has_array = true;
+                ArrayDef::from_str(array)
+                    .map_err(|e| anyhow!("Invalid array of task group {}: {}", index + 1, e))?
+            }
+            None if n_groups == 1 => ArrayDef::simple_range(0, 1),
+            None => anyhow::bail!(
+                "Task group {} has no array, it is required when the job has more task groups",
+                index + 1
+            ),
+        };
+        if let Some(task_id) = tasks.iter().find(|task_id| !task_ids.insert(*task_id)) {
+            anyhow::bail!("Task {} is defined in more task groups", task_id);
+        }
+        if let Some(deps) = &group.deps {
+            let depends_on: Vec<JobTaskId> = ArrayDef::from_str(deps)
+                .map_err(|e| anyhow!("Invalid deps of task group {}: {}", index + 1, e))?
+                .iter()
+                .collect();
+            request
+                .task_deps
+                .extend(tasks.iter().map(|task_id| TaskDependency {
+                    task_id,
+                    depends_on: depends_on.clone(),
+                }));
+        }
+
+        let mut spec = request.spec.clone();
+        spec.args = group
+            .command
+            .iter()
+            .map(|x| BString::from(x.as_str()))
+            .collect();
+        spec.env.extend(
+            group
+                .env
+                .into_iter()
+                .map(|(key, value)| (BString::from(key), BString::from(value))),
+        );
+        if let Some(cwd) = group.cwd {
+            spec.cwd = Some(cwd);
+        }
+        let resources = match &group.cpus {
+            Some(cpus) => {
+                let resources = ResourceRequest::new(parse_cpu_request(cpus)?);
+                resources.validate()?;
+                resources
+            }
+            None => request.resources.clone(),
+        };
+        groups.push(TaskGroup {
+            tasks,
+            spec,
+            resources,
+            pin: group.pin.unwrap_or(request.pin),
+            priority: group.priority.unwrap_or(request.priority),
+        });
+    }
+
+    // The first group also defines the configuration shown for the whole job
+    let first = &groups[0];
+    request.spec = first.spec.clone();
+    request.resources = first.resources.clone();
+    request.pin = first.pin;
+    request.priority = first.priority;
+    if !name_given {
+        request.name = match name {
+            Some(name) => validate_name(name)?,
+            None => default_job_name(&first.spec.args[0].to_string()),
+        };
+    }
+    if request.max_fails.is_none() {
+        request.max_fails = max_fails;
+    }
+    if groups.len() == 1 {
+        request.job_type = if has_array {
+            JobType::Array(groups.pop().unwrap().tasks)
+        } else {
+            JobType::Simple
+        };
+    } else {
+        let mut task_ids: Vec<JobTaskId> = task_ids.into_iter().collect();
+        task_ids.sort_unstable();
+        request.job_type = JobType::Array(ArrayDef::new_tasks(task_ids));
+        request.task_groups = groups;
+    }
+    Ok(())
+}
+
 #[derive(Clap)]
 #[clap(setting = clap::AppSettings::ColoredHelp)]
 pub struct ResubmitOpts {
-    job_id: JobId,
+    /// Job selector, e.g. `3`, `3-10`, `last`, `all`, `name:<pattern>` or `status:<states>`
+    selector: JobSelector,
 
     ///  Filter only tasks in a given status
     #[clap(long)]
     status: Option<StatusList>,
+
+    /// New command of the resubmitted job, e.g. `hq resubmit 5 -- ./program --arg`
+    #[clap(last = true)]
+    command: Vec<String>,
+
+    /// Number and placement of CPUs for each task
+    #[clap(long)]
+    cpus: Option<ArgCpuRequest>,
+
+    /// Name of the resubmitted job
+    #[clap(long)]
+    name: Option<String>,
+
+    /// Pin the tasks to the cores specified in `--cpus`
+    #[clap(long)]
+    pin: bool,
+
+    /// Working directory of the resubmitted tasks
+    #[clap(long)]
+    cwd: Option<PathBuf>,
+
+    /// Path where the standard output of tasks will be stored
+    #[clap(long)]
+    stdout: Option<StdioArg>,
+
+    /// Path where the standard error of tasks will be stored
+    #[clap(long)]
+    stderr: Option<StdioArg>,
+
+    /// Additional environment variable, it is added to the environment of the original job
+    /// You can pass this flag multiple times to pass multiple variables
+    #[clap(long, multiple_occurrences(true))]
+    env: Vec<ArgEnvironmentVar>,
+
+    #[clap(long)]
+    max_fails: Option<JobTaskCount>,
+
+    /// How many times a failed task is submitted again before it is considered as failed
+    #[clap(long)]
+    max_retries: Option<u32>,
+
+    /// Delay before a failed task is submitted again
+    #[clap(long)]
+    retry_delay: Option<ArgDuration>,
+
+    /// Time limit of each task
+    #[clap(long)]
+    time_limit: Option<ArgDuration>,
+
+    #[clap(long, allow_hyphen_values(true))]
+    priority: Option<tako::Priority>,
+
+    /// Maximal number of tasks of the resubmitted job that run at the same time
+    #[clap(long)]
+    max_parallel: Option<JobTaskCount>,
+
+    /// Minimal remaining lifetime of a worker that starts a task
+    #[clap(long)]
+    time_request: Option<ArgDuration>,
+
+    /// Units of a resource pool of the server that are used by each task,
+    /// it replaces pools used by the original job
+    #[clap(long = "use", multiple_occurrences(true))]
+    uses: Vec<ArgPoolAmount>,
+
+    /// Generic resource of a worker that is taken by each task,
+    /// it replaces resources requested by the original job
+    #[clap(long = "resource", multiple_occurrences(true))]
+    generic_resources: Vec<ArgResourceRequest>,
+
+    /// Label that a worker needs to have to run tasks of the job,
+    /// it replaces labels required by the original job
+    #[clap(long = "require", multiple_occurrences(true))]
+    required_labels: Vec<String>,
+}
+
+impl ResubmitOpts {
+    fn overrides(&self) -> anyhow::Result<ResubmitOverrides> {
+        if let Some(cpus) = &self.cpus {
+            ResourceRequest::new(cpus.0.clone()).validate()?;
+        }
+        if self.max_parallel == Some(0) {
+            anyhow::bail!("--max-parallel has to be at least 1");
+        }
+        Ok(ResubmitOverrides {
+            name: self.name.clone().map(validate_name).transpose()?,
+            args: if self.command.is_empty() {
+                None
+            } else {
+                Some(
+                    self.command
+                        .iter()
+                        .map(|x| BString::from(x.as_str()))
+                        .collect(),
+                )
+            },
+            env: self
+                .env
+                .iter()
+                .map(|env| (env.key.clone(), env.value.clone()))
+                .collect(),
+            cwd: self.cwd.clone(),
+            stdout: self.stdout.as_ref().map(|x| x.0.clone()),
+            stderr: self.stderr.as_ref().map(|x| x.0.clone()),
+            cpus: self.cpus.as_ref().map(|x| x.0.clone()),
+            pin: if self.pin { Some(true) } else { None },
+            priority: self.priority,
+            max_fails: self.max_fails,
+            max_retries: self.max_retries,
+            retry_delay: self.retry_delay.map(|d| d.into_duration()),
+            time_limit: self.time_limit.map(|d| d.into_duration()),
+            max_parallel: self.max_parallel,
+            pool_usage: if self.uses.is_empty() {
+                None
+            } else {
+                Some(get_pool_usage(&self.uses)?)
+            },
+            generic_resources: if self.generic_resources.is_empty() {
+                None
+            } else {
+                Some(get_generic_resources(&self.generic_resources)?)
+            },
+            required_labels: if self.required_labels.is_empty() {
+                None
+            } else {
+                Some(get_required_labels(&self.required_labels)?)
+            },
+            time_request: self.time_request.map(|d| d.into_duration()),
+        })
+    }
 }
 
 pub async fn resubmit_computation(
@@ -265,18 +868,39 @@ pub async fn resubmit_computation(
     connection: &mut ClientConnection,
     opts: ResubmitOpts,
 ) -> anyhow::Result<()> {
-    let message = FromClientMessage::Resubmit(ResubmitRequest {
-        job_id: opts.job_id,
-        status: opts.status.map(|x| x.to_vec()),
-    });
-    let response = rpc_call!(connection, message, ToClientMessage::SubmitResponse(r) => r).await?;
-    print_job_detail(
-        gsettings,
-        response.job,
-        true,
-        false,
-        get_worker_map(connection).await?,
-    );
+    let overrides = opts.overrides()?;
+    let job_ids = resolve_job_ids(connection, opts.selector).await?;
+    if job_ids.is_empty() {
+        log::warn!("No jobs were found");
+        return Ok(());
+    }
+
+    let status = opts.status.map(|x| x.to_vec());
+    let mut n_failed = 0;
+    for job_id in job_ids {
+        let message = FromClientMessage::Resubmit(ResubmitRequest {
+            job_id,
+            status: status.clone(),
+            overrides: overrides.clone(),
+        });
+        match connection.send_and_receive(message).await? {
+            ToClientMessage::SubmitResponse(response) => print_job_detail(
+                gsettings,
+                response.job,
+                true,
+                false,
+                get_worker_map(connection).await?,
+            ),
+            ToClientMessage::Error(e) => {
+                log::error!("Resubmitting job {} failed: {}", job_id, e);
+                n_failed += 1;
+            }
+            msg => anyhow::bail!("Received an invalid message {:?}", msg),
+        }
+    }
+    if n_failed > 0 {
+        anyhow::bail!("Resubmitting of {} job(s) failed", n_failed);
+    }
     Ok(())
 }
 
@@ -304,11 +928,141 @@ fn read_lines(filename: &Path) -> anyhow::Result<Vec<BString>> {
     Ok(results?)
 }
 
+/// Creates an entry for each combination of parameter values,
+/// values of the first parameter change the slowest
+fn expand_params(params: &[ArgParam]) -> anyhow::Result<Vec<BString>> {
+    let mut names = Set::new();
+    if let Some(param) = params.iter().find(|p| !names.insert(p.name.as_str())) {
+        anyhow::bail!("Parameter {} is defined more than once", param.name);
+    }
+    let n_combinations = params.iter().try_fold(1usize, |count, param| {
+        count
+            .checked_mul(param.values.len())
+            .filter(|count| *count <= MAX_PARAM_COMBINATIONS)
+    });
+    if n_combinations.is_none() {
+        anyhow::bail!(
+            "Parameters have more than {} combinations",
+            MAX_PARAM_COMBINATIONS
+        );
+    }
+    let mut entries: Vec<Vec<String>> = vec![Vec::new()];
+    for param in params {
+        entries = entries
+            .into_iter()
+            .flat_map(|entry| {
+                param.values.iter().map(move |value| {
+                    let mut entry = entry.clone();
+                    entry.push(format!("{}={}", param.name, value));
+                    entry
+                })
+            })
+            .collect();
+    }
+    Ok(entries
+        .into_iter()
+        .map(|entry| BString::from(entry.join(",")))
+        .collect())
+}
+
+/// Reads a file with one JSON object per line, empty lines are skipped
+fn read_json_entries(filename: &Path) -> anyhow::Result<(Vec<BString>, EntryFormat)> {
+    let format = EntryFormat::Json;
+    let entries = read_structured_entries(filename, &format, read_lines(filename)?)?;
+    Ok((entries, format))
+}
+
+/// Reads a CSV file, its first line is a header with names of columns
+fn read_csv_entries(filename: &Path) -> anyhow::Result<(Vec<BString>, EntryFormat)> {
+    let mut lines = read_lines(filename)?.into_iter();
+    let header = match lines.next() {
+        Some(header) => parse_csv_record(&header.to_str_lossy())
+            .map_err(|e| anyhow!("Invalid header of {}: {}", filename.display(), e))?,
+        None => anyhow::bail!("File {} is empty", filename.display()),
+    };
+    let format = EntryFormat::Csv(header);
+    let entries = read_structured_entries(filename, &format, lines.collect())?;
+    Ok((entries, format))
+}
+
+fn read_structured_entries(
+    filename: &Path,
+    format: &EntryFormat,
+    lines: Vec<BString>,
+) -> anyhow::Result<Vec<BString>> {
+    let entries: Vec<BString> = lines
+        .into_iter()
+        .filter(|line| !line.trim().is_empty())
+        .collect();
+    for (index, entry) in entries.iter().enumerate() {
+        parse_entry_fields(format, entry)
+            .map_err(|e| anyhow!("Invalid entry {} in {}: {}", index, filename.display(), e))?;
+    }
+    Ok(entries)
+}
+
 #[cfg(test)]
 mod tests {
     use std::str::FromStr;
 
-    use super::ArgEnvironmentVar;
+    use super::{expand_params, ArgArray, ArgEnvironmentVar, ArgParam};
+
+    #[test]
+    fn test_parse_param() {
+        let param: ArgParam = FromStr::from_str("lr=0.1,0.01").unwrap();
+        assert_eq!(param.name, "lr");
+        assert_eq!(param.values, vec!["0.1", "0.01"]);
+        let param: ArgParam = FromStr::from_str("depth=1,3..5").unwrap();
+        assert_eq!(param.values, vec!["1", "3", "4", "5"]);
+        assert!(ArgParam::from_str("depth").is_err());
+        assert!(ArgParam::from_str("depth=5..3").is_err());
+        assert!(ArgParam::from_str("depth=a..3").is_err());
+        assert!(ArgParam::from_str("depth=0..1000000000").is_err());
+    }
+
+    #[test]
+    fn test_parse_param_name() {
+        assert!(ArgParam::from_str("_lr2=1").is_ok());
+        assert!(ArgParam::from_str("=1").is_err());
+        assert!(ArgParam::from_str("2lr=1").is_err());
+        assert!(ArgParam::from_str("l-r=1").is_err());
+        assert!(ArgParam::from_str("a,b=1").is_err());
+    }
+
+    #[test]
+    fn test_parse_array_with_limit() {
+        let array: ArgArray = FromStr::from_str("1-1000%20").unwrap();
+        assert_eq!(array.def.task_count(), 1000);
+        assert_eq!(array.max_parallel, Some(20));
+        let array: ArgArray = FromStr::from_str("3,5").unwrap();
+        assert_eq!(array.def.task_count(), 2);
+        assert!(array.max_parallel.is_none());
+        assert!(ArgArray::from_str("1-10%").is_err());
+        assert!(ArgArray::from_str("1-10%x").is_err());
+    }
+
+    #[test]
+    fn test_expand_params() {
+        let params = vec![
+            ArgParam::from_str("a=1,2").unwrap(),
+            ArgParam::from_str("b=x,y").unwrap(),
+        ];
+        assert_eq!(
+            expand_params(&params).unwrap(),
+            vec!["a=1,b=x", "a=1,b=y", "a=2,b=x", "a=2,b=y"]
+        );
+        let params = vec![
+            ArgParam::from_str("a=1").unwrap(),
+            ArgParam::from_str("a=2").unwrap(),
+        ];
+        assert!(expand_params(&params).is_err());
+        let params = vec![
+            ArgParam::from_str("a=1..1000").unwrap(),
+            ArgParam::from_str("b=1..1000").unwrap(),
+            ArgParam::from_str("c=1,2").unwrap(),
+        ];
+        assert!(expand_params(&params).is_err());
+    }
 
     #[test]
     fn test_parse_env_empty() {
@@ -1,4 +1,5 @@
 use crate::client::status::is_terminated;
+use crate::common::arraydef::ArrayDef;
 use crate::transfer::connection::ClientConnection;
 use crate::transfer::messages::{
     FromClientMessage, JobInfo, JobInfoRequest, JobSelector, ToClientMessage,
@@ -6,7 +7,7 @@ use crate::transfer::messages::{
 use crate::{rpc_call, JobId, JobTaskCount, Set};
 
 use crate::client::utils::{
-    job_progress_bar, TASK_COLOR_CANCELED, TASK_COLOR_FAILED, TASK_COLOR_FINISHED,
+    job_progress_bar, TASK_COLOR_CANCELED, TASK_COLOR_FAILED, TASK_COLOR_FINISHED, TASK_COLOR_HELD,
     TASK_COLOR_RUNNING,
 };
 use crate::server::job::JobTaskCounters;
@@ -31,6 +32,7 @@ pub async fn wait_for_job_with_selector(
         connection,
         FromClientMessage::JobInfo(JobInfoRequest {
             selector,
+            exit_code: None,
         }),
         ToClientMessage::JobInfoResponse(r) => r
     )
@@ -66,7 +68,10 @@ async fn wait_for_jobs(
             let response = rpc_call!(
                 connection,
                 FromClientMessage::JobInfo(JobInfoRequest {
-                    selector: JobSelector::Specific(ids_ref.iter().copied().collect()),
+                    selector: JobSelector::Specific(ArrayDef::new_tasks(
+                        ids_ref.iter().copied().collect(),
+                    )),
+                    exit_code: None,
                 }),
                 ToClientMessage::JobInfoResponse(r) => r
             )
@@ -98,6 +103,7 @@ async fn wait_for_jobs(
                 "RUNNING",
                 TASK_COLOR_RUNNING,
             );
+            add_count(current_counters.n_held_tasks, "HELD", TASK_COLOR_HELD);
             add_count(
                 current_counters.n_finished_tasks,
                 "FINISHED",
@@ -1,8 +1,8 @@
 use crate::rpc_call;
 use crate::transfer::connection::ClientConnection;
 use crate::transfer::messages::{
-    FromClientMessage, StopWorkerMessage, StopWorkerResponse, ToClientMessage, WorkerInfo,
-    WorkerInfoRequest, WorkerSelector,
+    DrainWorkerMessage, DrainWorkerResponse, FromClientMessage, StopWorkerMessage,
+    StopWorkerResponse, ToClientMessage, WorkerInfo, WorkerInfoRequest, WorkerSelector,
 };
 use crate::WorkerId;
 
@@ -74,3 +74,35 @@ pub async fn stop_worker(
 
     Ok(())
 }
+
+pub async fn drain_worker(
+    connection: &mut ClientConnection,
+    selector: WorkerSelector,
+) -> crate::Result<()> {
+    let message = FromClientMessage::DrainWorker(DrainWorkerMessage { selector });
+    let mut responses =
+        rpc_call!(connection, message, ToClientMessage::DrainWorkerResponse(r) => r).await?;
+
+    responses.sort_unstable_by_key(|x| x.0);
+    for (id, response) in responses {
+        match response {
+            DrainWorkerResponse::InvalidWorker => {
+                log::error!("Draining worker {} failed; worker not found", id);
+            }
+            DrainWorkerResponse::AlreadyStopped => {
+                log::warn!("Draining worker {} failed; worker is already stopped", id);
+            }
+            DrainWorkerResponse::AlreadyDraining => {
+                log::warn!("Worker {} is already draining", id);
+            }
+            DrainWorkerResponse::Draining => {
+                log::info!(
+                    "Worker {} is draining, it will stop when its running tasks end",
+                    id
+                )
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -1,21 +1,27 @@
 use std::collections::BTreeSet;
 use std::fmt::Write;
 
+use chrono::Utc;
 use cli_table::format::Justify;
 use cli_table::{print_stdout, Cell, CellStruct, Color, Style, Table};
 use colored::Colorize;
+use humantime::format_duration;
 use tako::messages::common::StdioDef;
 
 use crate::client::globalsettings::GlobalSettings;
 use crate::client::resources::cpu_request_to_string;
 use crate::client::status::{job_status, status_cell, task_status};
 use crate::client::utils;
+use crate::common::entries::{parse_entry_fields, EntryFormat};
 use crate::common::env::is_hq_env;
 use crate::rpc_call;
 use crate::server::job::{JobTaskCounters, JobTaskInfo, JobTaskState};
 use crate::transfer::connection::ClientConnection;
-use crate::transfer::messages::{FromClientMessage, JobDetail, JobInfo, JobType, ToClientMessage};
-use crate::{JobTaskCount, Map, WorkerId};
+use crate::transfer::messages::{
+    FromClientMessage, JobDependency, JobDependencyMode, JobDetail, JobInfo, JobType,
+    ToClientMessage,
+};
+use crate::{JobTaskCount, JobTaskId, Map, WorkerId};
 
 /// Maps worker IDs to hostnames.
 type WorkerMap = Map<WorkerId, String>;
@@ -67,6 +73,12 @@ fn job_status_with_counts_cells(info: &JobInfo) -> String {
         info.counters.n_canceled_tasks,
         colored::Color::Magenta,
     );
+    row(
+        &mut result,
+        "HELD",
+        info.counters.n_held_tasks,
+        colored::Color::Blue,
+    );
     row(
         &mut result,
         "WAITING",
@@ -117,6 +129,7 @@ pub fn print_job_detail(
     show_tasks: bool,
     worker_map: WorkerMap,
 ) {
+    let task_params = format_task_params(&job);
     let mut rows = vec![
         vec!["Id".cell().bold(true), job.info.id.cell()],
         vec!["Name".cell().bold(true), job.info.name.as_str().cell()],
@@ -139,12 +152,35 @@ pub fn print_job_detail(
     }
 
     rows.push(vec!["Tasks".cell().bold(true), n_tasks.cell()]);
+    if let Some(max_parallel) = job.max_parallel {
+        rows.push(vec!["Max parallel".cell().bold(true), max_parallel.cell()]);
+    }
+    if !job.pool_usage.is_empty() {
+        rows.push(vec![
+            "Resource pools".cell().bold(true),
+            job.pool_usage
+                .iter()
+                .map(|(name, amount)| format!("{}={}", name, amount))
+                .collect::<Vec<_>>()
+                .join(", ")
+                .cell(),
+        ]);
+    }
+    if let Some(after) = &job.after {
+        rows.push(vec![
+            "Dependencies".cell().bold(true),
+            format_job_dependency(after).cell(),
+        ]);
+    }
     rows.push(vec![
         "Workers".cell().bold(true),
         format_job_workers(&job, &worker_map).cell(),
     ]);
 
-    let resources = cpu_request_to_string(job.resources.cpus());
+    let mut resources = cpu_request_to_string(job.resources.cpus());
+    for request in &job.generic_resources {
+        write!(resources, ", {}", request).unwrap();
+    }
 
     rows.push(vec![
         "Resources".cell().bold(true),
@@ -156,7 +192,33 @@ pub fn print_job_detail(
         .cell(),
     ]);
 
+    if !job.required_labels.is_empty() {
+        rows.push(vec![
+            "Required labels".cell().bold(true),
+            job.required_labels.join(", ").cell(),
+        ]);
+    }
+
     rows.push(vec!["Priority".cell().bold(true), job.priority.cell()]);
+    if job.max_retries > 0 {
+        let mut retries = job.max_retries.to_string();
+        if job.retry_delay.as_nanos() > 0 {
+            write!(retries, " (delay {})", format_duration(job.retry_delay)).unwrap();
+        }
+        rows.push(vec!["Retries".cell().bold(true), retries.cell()]);
+    }
+    if let Some(time_limit) = job.time_limit {
+        rows.push(vec![
+            "Time limit".cell().bold(true),
+            format_duration(time_limit).cell(),
+        ]);
+    }
+    if let Some(time_request) = job.time_request {
+        rows.push(vec![
+            "Time request".cell().bold(true),
+            format_duration(time_request).cell(),
+        ]);
+    }
 
     let program_def = job.program_def;
     rows.push(vec![
@@ -169,6 +231,31 @@ pub fn print_job_detail(
             .join("\n")
             .cell(),
     ]);
+    if !job.task_groups.is_empty() {
+        rows.push(vec![
+            "Task groups".cell().bold(true),
+            job.task_groups
+                .iter()
+                .map(|group| {
+                    format!(
+                        "{}: {} [cpus {}, priority {}]",
+                        group.tasks,
+                        group
+                            .spec
+                            .args
+                            .iter()
+                            .map(|x| x.to_string())
+                            .collect::<Vec<String>>()
+                            .join(" "),
+                        cpu_request_to_string(group.resources.cpus()),
+                        group.priority
+                    )
+                })
+                .collect::<Vec<String>>()
+                .join("\n")
+                .cell(),
+        ]);
+    }
     rows.push(vec![
         "Stdout".cell().bold(true),
         stdio_to_cell(&program_def.stdout),
@@ -202,6 +289,21 @@ pub fn print_job_detail(
             .cell(),
     ]);
 
+    rows.push(vec![
+        "Submission date".cell().bold(true),
+        utils::format_datetime(job.info.submitted_at).cell(),
+    ]);
+    rows.push(vec![
+        "Makespan".cell().bold(true),
+        match job.info.started_at {
+            Some(started_at) => {
+                utils::format_elapsed(started_at, job.info.ended_at.unwrap_or_else(Utc::now))
+            }
+            None => "".to_string(),
+        }
+        .cell(),
+    ]);
+
     let table = rows.table().color_choice(gsettings.color_policy());
     assert!(print_stdout(table).is_ok());
 
@@ -212,10 +314,78 @@ pub fn print_job_detail(
             show_tasks,
             &job.info.counters,
             &worker_map,
+            &task_params,
         );
     }
 }
 
+fn format_job_dependency(after: &JobDependency) -> String {
+    let mode = match after.mode {
+        JobDependencyMode::Ok => "ok",
+        JobDependencyMode::Any => "any",
+        JobDependencyMode::NotOk => "notok",
+    };
+    format!(
+        "{} (after {})",
+        after
+            .jobs
+            .iter()
+            .map(|id| id.to_string())
+            .collect::<Vec<_>>()
+            .join(", "),
+        mode
+    )
+}
+
+/// Shows start and end of a task together with its duration
+fn format_task_times(task: &JobTaskInfo) -> CellStruct {
+    let started_at = match task.started_at {
+        Some(started_at) => started_at,
+        None => return "".cell(),
+    };
+    let mut result = format!("Start: {}", utils::format_datetime(started_at));
+    match (&task.state, task.ended_at) {
+        (JobTaskState::Running { .. }, _) | (_, None) => {
+            write!(
+                result,
+                "\nRunning: {}",
+                utils::format_elapsed(started_at, Utc::now())
+            )
+            .unwrap();
+        }
+        (_, Some(ended_at)) => {
+            write!(
+                result,
+                "\nEnd: {}\nDuration: {}",
+                utils::format_datetime(ended_at),
+                utils::format_elapsed(started_at, ended_at)
+            )
+            .unwrap();
+        }
+    }
+    result.cell()
+}
+
+/// Parameter sets of tasks of a parameter sweep
+fn format_task_params(job: &JobDetail) -> Map<JobTaskId, String> {
+    match (&job.job_type, &job.entries, &job.entry_format) {
+        (JobType::Array(array_def), Some(entries), EntryFormat::Params) => array_def
+            .iter()
+            .zip(entries.iter())
+            .map(|(task_id, entry)| {
+                let params = parse_entry_fields(&job.entry_format, entry)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|(name, value)| format!("{}={}", name, value))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                (task_id, params)
+            })
+            .collect(),
+        _ => Map::new(),
+    }
+}
+
 const MAX_DISPLAYED_WORKERS: usize = 2;
 
 fn format_job_workers(job: &JobDetail, worker_map: &WorkerMap) -> String {
@@ -254,11 +424,12 @@ fn print_job_tasks(
     show_tasks: bool,
     counters: &JobTaskCounters,
     worker_map: &WorkerMap,
+    task_params: &Map<JobTaskId, String>,
 ) {
     tasks.sort_unstable_by_key(|t| t.task_id);
 
     let make_error_row = |t: &JobTaskInfo| match &t.state {
-        JobTaskState::Failed { worker, error } => Some(vec![
+        JobTaskState::Failed { worker, error, .. } => Some(vec![
             t.task_id.cell(),
             format_worker(*worker, worker_map).cell(),
             error.to_owned().cell().foreground_color(Some(Color::Red)),
@@ -270,32 +441,50 @@ fn print_job_tasks(
         let rows: Vec<_> = tasks
             .iter()
             .map(|t| {
-                vec![
+                let mut row = vec![
                     t.task_id.cell(),
                     status_cell(task_status(&t.state)),
+                    t.attempt.cell(),
                     match t.state.get_worker() {
                         Some(worker) => format_worker(worker, worker_map),
                         _ => "",
                     }
                     .cell(),
+                    format_task_times(t),
                     match &t.state {
                         JobTaskState::Failed { error, .. } => {
                             error.to_owned().cell().foreground_color(Some(Color::Red))
                         }
                         _ => "".cell(),
                     },
-                ]
+                ];
+                if !task_params.is_empty() {
+                    row.push(
+                        task_params
+                            .get(&t.task_id)
+                            .map(|p| p.as_str())
+                            .unwrap_or("")
+                            .cell(),
+                    );
+                }
+                row
             })
             .collect();
+        let mut title = vec![
+            "Task Id".cell().bold(true),
+            "State".cell().bold(true),
+            "Attempt".cell().bold(true),
+            "Worker".cell().bold(true),
+            "Times".cell().bold(true),
+            "Message".cell().bold(true),
+        ];
+        if !task_params.is_empty() {
+            title.push("Parameters".cell().bold(true));
+        }
         let table = rows
             .table()
             .color_choice(gsettings.color_policy())
-            .title(vec![
-                "Task Id".cell().bold(true),
-                "State".cell().bold(true),
-                "Worker".cell().bold(true),
-                "Message".cell().bold(true),
-            ]);
+            .title(title);
         assert!(print_stdout(table).is_ok());
     } else {
         const SHOWN_TASKS: usize = 5;
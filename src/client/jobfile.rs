@@ -0,0 +1,123 @@
+use std::path::{Path, PathBuf};
+
+use hashbrown::HashMap;
+use serde::Deserialize;
+
+use crate::JobTaskCount;
+
+/// Declarative definition of a job that is submitted by `hq submit --job-file`.
+///
+/// The file is read as JSON when it has the `.json` extension, otherwise it is read as TOML.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct JobFile {
+    pub name: Option<String>,
+    pub max_fails: Option<JobTaskCount>,
+    /// Groups of tasks, all of them are submitted as a single job
+    #[serde(rename = "group")]
+    pub groups: Vec<TaskGroupDef>,
+}
+
+/// Tasks with the same configuration, options that are not specified
+/// are taken from the command line of `hq submit`
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct TaskGroupDef {
+    pub command: Vec<String>,
+    /// Task ids of the group in the format of `--array`, it may be omitted
+    /// when the job has only one group
+    pub array: Option<String>,
+    /// Task ids (in the format of `--array`) that have to finish before tasks of the group start
+    pub deps: Option<String>,
+    pub cpus: Option<String>,
+    pub pin: Option<bool>,
+    pub priority: Option<tako::Priority>,
+    pub cwd: Option<PathBuf>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+}
+
+pub fn read_job_file(path: &Path) -> anyhow::Result<JobFile> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Cannot read job file {}: {}", path.display(), e))?;
+    let is_json = path.extension().map(|ext| ext == "json").unwrap_or(false);
+    let job_file = if is_json {
+        parse_json_job_file(&content)
+    } else {
+        parse_toml_job_file(&content)
+    }
+    .map_err(|e| anyhow::anyhow!("Invalid job file {}: {}", path.display(), e))?;
+    if job_file.groups.is_empty() {
+        anyhow::bail!("Job file {} does not define any task group", path.display());
+    }
+    if let Some(index) = job_file.groups.iter().position(|g| g.command.is_empty()) {
+        anyhow::bail!(
+            "Task group {} in job file {} has an empty command",
+            index + 1,
+            path.display()
+        );
+    }
+    Ok(job_file)
+}
+
+fn parse_json_job_file(content: &str) -> anyhow::Result<JobFile> {
+    Ok(serde_json::from_str(content)?)
+}
+
+fn parse_toml_job_file(content: &str) -> anyhow::Result<JobFile> {
+    Ok(toml::from_str(content)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::client::jobfile::{parse_json_job_file, parse_toml_job_file};
+
+    #[test]
+    fn test_parse_toml() {
+        let job = parse_toml_job_file(
+            r#"
+name = "pipeline"
+max_fails = 2
+
+[[group]]
+command = ["python3", "prepare.py"]
+array = "0-9"
+cpus = "2 scatter"
+priority = 5
+env = { MODE = "fast" }
+
+[[group]]
+command = ["./reduce"]
+array = "10"
+deps = "0-9"
+cwd = "/tmp"
+"#,
+        )
+        .unwrap();
+        assert_eq!(job.name.as_deref(), Some("pipeline"));
+        assert_eq!(job.max_fails, Some(2));
+        assert_eq!(job.groups.len(), 2);
+        assert_eq!(job.groups[0].command, vec!["python3", "prepare.py"]);
+        assert_eq!(job.groups[0].array.as_deref(), Some("0-9"));
+        assert_eq!(job.groups[0].cpus.as_deref(), Some("2 scatter"));
+        assert_eq!(job.groups[0].priority, Some(5));
+        assert_eq!(job.groups[0].env["MODE"], "fast");
+        assert!(job.groups[0].deps.is_none());
+        assert_eq!(job.groups[1].deps.as_deref(), Some("0-9"));
+        assert!(job.groups[1].env.is_empty());
+        assert_eq!(job.groups[1].cwd.as_ref().unwrap().to_str(), Some("/tmp"));
+    }
+
+    #[test]
+    fn test_parse_json() {
+        let job = parse_json_job_file(r#"{"group": [{"command": ["hostname"]}]}"#).unwrap();
+        assert!(job.name.is_none());
+        assert_eq!(job.groups.len(), 1);
+        assert!(job.groups[0].array.is_none());
+    }
+
+    #[test]
+    fn test_parse_unknown_field() {
+        assert!(parse_toml_job_file("[[group]]\ncommand = [\"ls\"]\ncpu = \"1\"\n").is_err());
+    }
+}
@@ -1,7 +1,9 @@
 pub mod commands;
 pub mod globalsettings;
 pub mod job;
+pub mod jobfile;
 pub mod resources;
+pub mod selector;
 pub mod status;
 pub mod utils;
 pub mod worker;
@@ -1,9 +1,10 @@
-use crate::common::parser::{format_parse_error, p_uint, NomResult};
+use crate::common::parser::{format_parse_error, p_resource_name, p_uint, NomResult};
 use nom::branch::alt;
 use nom::bytes::complete::tag;
-use nom::character::complete::multispace1;
+use nom::character::complete::{char, multispace1};
 use nom::combinator::{all_consuming, map, map_res, opt};
-use nom::sequence::{preceded, tuple};
+use nom::sequence::{preceded, separated_pair, tuple};
+use serde::{Deserialize, Serialize};
 use tako::common::resources::CpuRequest;
 
 fn p_cpu_request(input: &str) -> NomResult<CpuRequest> {
@@ -38,6 +39,40 @@ pub fn parse_cpu_request(input: &str) -> anyhow::Result<CpuRequest> {
         .map_err(format_parse_error)
 }
 
+/// Request of a generic resource in the format `<name>=<amount>`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GenericResourceRequest {
+    pub name: String,
+    pub amount: u32,
+}
+
+fn p_resource_request(input: &str) -> NomResult<GenericResourceRequest> {
+    map_res(
+        separated_pair(p_resource_name, char('='), p_uint),
+        |(name, amount)| {
+            if amount == 0 {
+                return Err(anyhow::anyhow!("Requesting zero units is not allowed"));
+            }
+            Ok(GenericResourceRequest {
+                name: name.to_string(),
+                amount,
+            })
+        },
+    )(input)
+}
+
+pub fn parse_resource_request(input: &str) -> anyhow::Result<GenericResourceRequest> {
+    all_consuming(p_resource_request)(input)
+        .map(|r| r.1)
+        .map_err(format_parse_error)
+}
+
+impl std::fmt::Display for GenericResourceRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}={}", self.name, self.amount)
+    }
+}
+
 pub fn cpu_request_to_string(cr: &CpuRequest) -> String {
     match cr {
         CpuRequest::Compact(n_cpus) => {
@@ -79,4 +114,18 @@ mod test {
     fn test_parse_zero_cpus() {
         assert!(parse_cpu_request("0").is_err());
     }
+
+    #[test]
+    fn test_parse_resource_request() {
+        assert_eq!(
+            parse_resource_request("mem=4000").unwrap(),
+            GenericResourceRequest {
+                name: "mem".to_string(),
+                amount: 4000
+            }
+        );
+        assert!(parse_resource_request("gpu=0").is_err());
+        assert!(parse_resource_request("gpu").is_err());
+        assert!(parse_resource_request("gpu=[0]").is_err());
+    }
 }
@@ -0,0 +1,88 @@
+use std::str::FromStr;
+
+use nom::branch::alt;
+use nom::bytes::complete::tag;
+use nom::character::complete::{anychar, space0};
+use nom::combinator::{all_consuming, map, opt, recognize};
+use nom::multi::many1;
+use nom::sequence::{preceded, tuple};
+
+use crate::client::status::p_status_list;
+use crate::common::arrayparser::p_array_def;
+use crate::common::parser::{format_parse_error, p_uint, NomResult};
+use crate::transfer::messages::JobSelector;
+
+fn p_job_selector(input: &str) -> NomResult<JobSelector> {
+    alt((
+        map(tag("all"), |_| JobSelector::All),
+        map(
+            preceded(tag("last"), opt(preceded(space0, p_uint))),
+            |count| JobSelector::LastN(count.unwrap_or(1)),
+        ),
+        map(
+            preceded(tag("name:"), recognize(many1(anychar))),
+            |pattern: &str| JobSelector::Name(pattern.to_string()),
+        ),
+        map(preceded(tag("status:"), p_status_list), JobSelector::Status),
+        map(p_array_def, JobSelector::Specific),
+    ))(input)
+}
+
+/// Parses a job selector, e.g. `all`, `last`, `last 5`, `3-10`, `1,5,9`,
+/// `name:preprocess*` or `status:failed,canceled`
+pub fn parse_job_selector(input: &str) -> anyhow::Result<JobSelector> {
+    all_consuming(tuple((space0, p_job_selector, space0)))(input)
+        .map(|r| (r.1).1)
+        .map_err(format_parse_error)
+}
+
+impl FromStr for JobSelector {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_job_selector(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::status::Status;
+
+    #[test]
+    fn test_parse_job_selector() {
+        assert!(matches!(
+            parse_job_selector("all").unwrap(),
+            JobSelector::All
+        ));
+        assert!(matches!(
+            parse_job_selector("last").unwrap(),
+            JobSelector::LastN(1)
+        ));
+        assert!(matches!(
+            parse_job_selector("last 5").unwrap(),
+            JobSelector::LastN(5)
+        ));
+        assert!(matches!(parse_job_selector("7").unwrap(),
+            JobSelector::Specific(ids) if ids.iter().collect::<Vec<_>>() == vec![7]));
+        assert!(matches!(parse_job_selector("3-6").unwrap(),
+            JobSelector::Specific(ids) if ids.iter().collect::<Vec<_>>() == vec![3, 4, 5, 6]));
+        assert!(matches!(parse_job_selector("1,5,9").unwrap(),
+            JobSelector::Specific(ids) if ids.iter().collect::<Vec<_>>() == vec![1, 5, 9]));
+        assert!(matches!(parse_job_selector("name:pre*").unwrap(),
+            JobSelector::Name(pattern) if pattern == "pre*"));
+        assert!(
+            matches!(parse_job_selector("status:failed,canceled").unwrap(),
+            JobSelector::Status(s) if s == vec![Status::Failed, Status::Canceled])
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_job_selector() {
+        assert!(parse_job_selector("").is_err());
+        assert!(parse_job_selector("last x").is_err());
+        assert!(parse_job_selector("name:").is_err());
+        assert!(parse_job_selector("status:unknown").is_err());
+        assert!(parse_job_selector("5-2").is_err());
+    }
+}
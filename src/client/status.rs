@@ -12,9 +12,11 @@ use serde::Deserialize;
 use serde::Serialize;
 use std::str::FromStr;
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub enum Status {
     Waiting,
+    Blocked,
+    Held,
     Running,
     Finished,
     Failed,
@@ -27,6 +29,8 @@ impl FromStr for Status {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         Ok(match s {
             "waiting" => Self::Waiting,
+            "blocked" => Self::Blocked,
+            "held" => Self::Held,
             "running" => Self::Running,
             "finished" => Self::Finished,
             "failed" => Self::Failed,
@@ -58,6 +62,8 @@ pub fn job_status(info: &JobInfo) -> Status {
 
     if info.counters.n_running_tasks > 0 {
         Status::Running
+    } else if info.counters.n_held_tasks > 0 {
+        Status::Held
     } else if has_waiting {
         Status::Waiting
     } else if info.counters.n_canceled_tasks > 0 {
@@ -71,12 +77,16 @@ pub fn job_status(info: &JobInfo) -> Status {
 }
 
 pub fn is_terminated(info: &JobInfo) -> bool {
-    info.counters.n_running_tasks == 0 && info.counters.n_waiting_tasks(info.n_tasks) == 0
+    info.counters.n_running_tasks == 0
+        && info.counters.n_held_tasks == 0
+        && info.counters.n_waiting_tasks(info.n_tasks) == 0
 }
 
 pub fn task_status(status: &JobTaskState) -> Status {
     match status {
         JobTaskState::Waiting => Status::Waiting,
+        JobTaskState::Blocked => Status::Blocked,
+        JobTaskState::Held => Status::Held,
         JobTaskState::Running { .. } => Status::Running,
         JobTaskState::Finished { .. } => Status::Finished,
         JobTaskState::Failed { .. } => Status::Failed,
@@ -87,6 +97,8 @@ pub fn task_status(status: &JobTaskState) -> Status {
 pub fn status_cell(status: Status) -> CellStruct {
     match status {
         Status::Waiting => "WAITING".cell().foreground_color(Some(Color::Cyan)),
+        Status::Blocked => "BLOCKED".cell().foreground_color(Some(Color::Blue)),
+        Status::Held => "HELD".cell().foreground_color(Some(Color::Blue)),
         Status::Finished => "FINISHED".cell().foreground_color(Some(Color::Green)),
         Status::Failed => "FAILED".cell().foreground_color(Some(Color::Red)),
         Status::Running => "RUNNING".cell().foreground_color(Some(Color::Yellow)),
@@ -97,6 +109,8 @@ pub fn status_cell(status: Status) -> CellStruct {
 fn p_status(input: &str) -> NomResult<Status> {
     alt((
         map(tag("waiting"), |_| Status::Waiting),
+        map(tag("blocked"), |_| Status::Blocked),
+        map(tag("held"), |_| Status::Held),
         map(tag("running"), |_| Status::Running),
         map(tag("finished"), |_| Status::Finished),
         map(tag("failed"), |_| Status::Failed),
@@ -104,7 +118,7 @@ fn p_status(input: &str) -> NomResult<Status> {
     ))(input)
 }
 
-fn p_status_list(input: &str) -> NomResult<Vec<Status>> {
+pub(crate) fn p_status_list(input: &str) -> NomResult<Vec<Status>> {
     separated_list1(tuple((tag(","), space0)), p_status)(input)
 }
 
@@ -1,7 +1,9 @@
 use crate::server::job::JobTaskCounters;
 use crate::JobTaskCount;
+use chrono::{DateTime, Local, Utc};
 use colored::{Color, Colorize};
 use std::fmt::Write;
+use std::time::Duration;
 
 #[macro_export]
 macro_rules! rpc_call {
@@ -21,6 +23,20 @@ pub const TASK_COLOR_CANCELED: Color = Color::Magenta;
 pub const TASK_COLOR_FAILED: Color = Color::Red;
 pub const TASK_COLOR_FINISHED: Color = Color::Green;
 pub const TASK_COLOR_RUNNING: Color = Color::Yellow;
+pub const TASK_COLOR_HELD: Color = Color::Blue;
+
+/// Formats a timestamp in the local timezone
+pub fn format_datetime(time: DateTime<Utc>) -> String {
+    time.with_timezone(&Local)
+        .format("%d.%m.%Y %H:%M:%S")
+        .to_string()
+}
+
+/// Formats the time elapsed between two timestamps with a precision of milliseconds
+pub fn format_elapsed(start: DateTime<Utc>, end: DateTime<Utc>) -> String {
+    let duration = (end - start).to_std().unwrap_or_default();
+    humantime::format_duration(Duration::from_millis(duration.as_millis() as u64)).to_string()
+}
 
 /// Draws a colored progress bar that depicts counts of tasks with individual states
 pub fn job_progress_bar(counters: JobTaskCounters, n_tasks: JobTaskCount, width: usize) -> String {
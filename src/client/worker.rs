@@ -1,7 +1,10 @@
 use cli_table::format::Justify;
 use cli_table::{print_stdout, Cell, CellStruct, Color, Style, Table};
 
+use tako::messages::common::WorkerConfiguration;
+
 use crate::client::globalsettings::GlobalSettings;
+use crate::common::labels::worker_labels;
 use crate::transfer::messages::{LostWorkerReasonInfo, WorkerExitInfo, WorkerInfo};
 
 pub enum WorkerState {
@@ -12,6 +15,7 @@ pub enum WorkerState {
 
 fn worker_state(worker: &WorkerInfo) -> CellStruct {
     match worker.ended {
+        None if worker.draining => "DRAINING".cell().foreground_color(Some(Color::Yellow)),
         None => "RUNNING".cell().foreground_color(Some(Color::Green)),
         Some(WorkerExitInfo {
             reason: LostWorkerReasonInfo::ConnectionLost,
@@ -32,6 +36,15 @@ fn worker_state(worker: &WorkerInfo) -> CellStruct {
     }
 }
 
+pub fn format_worker_labels(configuration: &WorkerConfiguration) -> String {
+    let labels = worker_labels(configuration);
+    if labels.is_empty() {
+        "None".to_string()
+    } else {
+        labels.join(", ")
+    }
+}
+
 pub fn print_worker_info(workers: Vec<WorkerInfo>, gsettings: &GlobalSettings) {
     let rows: Vec<_> = workers
         .into_iter()
@@ -41,6 +54,7 @@ pub fn print_worker_info(workers: Vec<WorkerInfo>, gsettings: &GlobalSettings) {
                 worker_state(&w),
                 w.configuration.hostname.cell(),
                 w.configuration.resources.summary().cell(),
+                format_worker_labels(&w.configuration).cell(),
                 w.configuration
                     .extra
                     .get("MANAGER")
@@ -65,6 +79,7 @@ pub fn print_worker_info(workers: Vec<WorkerInfo>, gsettings: &GlobalSettings) {
             "State".cell().bold(true),
             "Hostname".cell().bold(true),
             "Resources".cell().bold(true),
+            "Labels".cell().bold(true),
             "Manager".cell().bold(true),
             "Manager Job Id".cell().bold(true),
         ]);
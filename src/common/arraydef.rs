@@ -22,6 +22,15 @@ impl TaskIdRange {
     pub fn iter(&self) -> impl Iterator<Item = JobTaskId> {
         (self.start..self.start + self.count).step_by(self.step as usize)
     }
+
+    /// Iterates ids of the range that are not greater than `max_id`,
+    /// the first id of the range is always included
+    pub fn iter_up_to(&self, max_id: JobTaskId) -> impl Iterator<Item = JobTaskId> {
+        let end = (self.start as u64 + self.count as u64).min(max_id.max(self.start) as u64 + 1);
+        (self.start as u64..end)
+            .step_by(self.step as usize)
+            .map(|id| id as JobTaskId)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -63,6 +72,12 @@ impl ArrayDef {
     pub fn iter(&self) -> impl Iterator<Item = JobTaskId> + '_ {
         self.ranges.iter().flat_map(|x| x.iter())
     }
+
+    /// Iterates ids that are not greater than `max_id`, so that a huge range is not expanded.
+    /// The first id of each range is always included (e.g. to report a missing job).
+    pub fn iter_up_to(&self, max_id: JobTaskId) -> impl Iterator<Item = JobTaskId> + '_ {
+        self.ranges.iter().flat_map(move |x| x.iter_up_to(max_id))
+    }
 }
 
 impl FromStr for ArrayDef {
@@ -93,3 +108,22 @@ impl fmt::Display for ArrayDef {
         write!(f, "{}", &str[0..str.len() - 2])
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::common::arraydef::ArrayDef;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_iter_up_to() {
+        let array = ArrayDef::from_str("1-5,8,20-4000000000:2").unwrap();
+        assert_eq!(
+            array.iter_up_to(4).collect::<Vec<_>>(),
+            vec![1, 2, 3, 4, 8, 20]
+        );
+        assert_eq!(
+            array.iter_up_to(25).collect::<Vec<_>>(),
+            vec![1, 2, 3, 4, 5, 8, 20, 22, 24]
+        );
+    }
+}
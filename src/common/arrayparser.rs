@@ -1,13 +1,13 @@
 use anyhow::anyhow;
 use nom::bytes::complete::tag;
 use nom::combinator::all_consuming;
-use nom::combinator::{map_res, opt};
+use nom::combinator::{map, map_res, opt};
 use nom::multi::separated_list1;
 use nom::sequence::{preceded, tuple};
 
 use crate::common::arraydef::{ArrayDef, TaskIdRange};
 use crate::common::parser::{format_parse_error, p_uint, NomResult};
-use crate::Set;
+use crate::{JobTaskId, Set};
 
 fn p_task_id_range(input: &str) -> NomResult<TaskIdRange> {
     map_res(
@@ -27,7 +27,7 @@ fn p_task_id_range(input: &str) -> NomResult<TaskIdRange> {
     )(input)
 }
 
-fn p_array_def(input: &str) -> NomResult<ArrayDef> {
+pub(crate) fn p_array_def(input: &str) -> NomResult<ArrayDef> {
     map_res(p_task_id_ranges, |r| match r {
         res if !is_overlapping(res.clone()) => Ok(ArrayDef::new(res)),
         _ => Err(anyhow!("Ranges overlap")),
@@ -44,6 +44,21 @@ pub fn parse_array_def(input: &str) -> anyhow::Result<ArrayDef> {
         .map_err(format_parse_error)
 }
 
+fn p_task_dependency(input: &str) -> NomResult<(JobTaskId, Vec<JobTaskId>)> {
+    map(
+        tuple((p_uint, tag(":"), separated_list1(tag(","), p_uint))),
+        |(task_id, _, depends_on)| (task_id, depends_on),
+    )(input)
+}
+
+/// Parses a dependency of a task in the format `<task-id>:<task-id>,<task-id>,...`,
+/// returns the dependent task and tasks that it depends on
+pub fn parse_task_dependency(input: &str) -> anyhow::Result<(JobTaskId, Vec<JobTaskId>)> {
+    all_consuming(p_task_dependency)(input)
+        .map(|r| r.1)
+        .map_err(format_parse_error)
+}
+
 fn is_overlapping(mut ranges: Vec<TaskIdRange>) -> bool {
     ranges.sort_unstable_by_key(|range| range.start);
     let mut ids = Set::new();
@@ -113,4 +128,17 @@ mod test {
         );
         assert!(parse_array_def("0-10, 5").is_err());
     }
+
+    #[test]
+    fn test_parse_task_dependency() {
+        assert_eq!(parse_task_dependency("5:1").unwrap(), (5, vec![1]));
+        assert_eq!(
+            parse_task_dependency("3:0,1,2").unwrap(),
+            (3, vec![0, 1, 2])
+        );
+
+        assert!(parse_task_dependency("3").is_err());
+        assert!(parse_task_dependency("3:").is_err());
+        assert!(parse_task_dependency(":1").is_err());
+    }
 }
@@ -0,0 +1,202 @@
+use bstr::{BString, ByteSlice};
+use serde::{Deserialize, Serialize};
+
+use crate::common::env::{HQ_ENTRY_FIELD_PREFIX, HQ_PARAM_PREFIX};
+
+/// Format of entries of an array job, it determines how an entry is split into named fields
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum EntryFormat {
+    /// Entry is a plain line (`--each-line`), it has no fields
+    Line,
+    /// Entry is a JSON object (`--from-json`), fields are its keys
+    Json,
+    /// Entry is a CSV record (`--from-csv`), fields are named by the header of the file
+    Csv(Vec<String>),
+    /// Entry is a combination of parameters of a parameter sweep (`--param`)
+    /// in the format `<name>=<value>,<name>=<value>,...`
+    Params,
+}
+
+impl EntryFormat {
+    /// Prefix of environment variables that contain fields of an entry
+    pub fn field_prefix(&self) -> &'static str {
+        match self {
+            EntryFormat::Params => HQ_PARAM_PREFIX,
+            _ => HQ_ENTRY_FIELD_PREFIX,
+        }
+    }
+}
+
+impl Default for EntryFormat {
+    fn default() -> Self {
+        EntryFormat::Line
+    }
+}
+
+/// Splits an entry into named fields
+pub fn parse_entry_fields(
+    format: &EntryFormat,
+    entry: &BString,
+) -> Result<Vec<(String, BString)>, String> {
+    let fields = match format {
+        EntryFormat::Line => return Ok(Vec::new()),
+        EntryFormat::Json => {
+            let text = entry
+                .to_str()
+                .map_err(|_| "Entry is not a valid UTF-8 string".to_string())?;
+            match serde_json::from_str(text) {
+                Ok(serde_json::Value::Object(object)) => object
+                    .into_iter()
+                    .map(|(key, value)| {
+                        let value = match value {
+                            serde_json::Value::String(s) => s,
+                            value => value.to_string(),
+                        };
+                        (key, BString::from(value))
+                    })
+                    .collect(),
+                Ok(_) => return Err("Entry is not a JSON object".to_string()),
+                Err(e) => return Err(format!("Invalid JSON: {}", e)),
+            }
+        }
+        EntryFormat::Csv(header) => {
+            let text = entry
+                .to_str()
+                .map_err(|_| "Entry is not a valid UTF-8 string".to_string())?;
+            let values = parse_csv_record(text)?;
+            if values.len() != header.len() {
+                return Err(format!(
+                    "Record has {} columns, but the header has {} columns",
+                    values.len(),
+                    header.len()
+                ));
+            }
+            header
+                .iter()
+                .cloned()
+                .zip(values.into_iter().map(BString::from))
+                .collect()
+        }
+        EntryFormat::Params => {
+            let text = entry
+                .to_str()
+                .map_err(|_| "Entry is not a valid UTF-8 string".to_string())?;
+            text.split(',')
+                .map(|param| match param.find('=') {
+                    Some(position) => Ok((
+                        param[..position].to_string(),
+                        BString::from(&param[position + 1..]),
+                    )),
+                    None => Err(format!("Invalid parameter '{}'", param)),
+                })
+                .collect::<Result<_, _>>()?
+        }
+    };
+    if let Some((key, _)) = fields
+        .iter()
+        .find(|(key, _)| key.is_empty() || key.contains('='))
+    {
+        return Err(format!("Invalid field name '{}'", key));
+    }
+    Ok(fields)
+}
+
+/// Parses a single line of a CSV file, fields may be quoted by `"`
+/// (a quote inside a quoted field is written as `""`)
+pub fn parse_csv_record(line: &str) -> Result<Vec<String>, String> {
+    let line = line.strip_suffix('\r').unwrap_or(line);
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut chars = line.chars().peekable();
+    let mut quoted = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted => {
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    field.push('"');
+                } else {
+                    quoted = false;
+                }
+            }
+            '"' if field.is_empty() => quoted = true,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    if quoted {
+        return Err("Unterminated quoted field".to_string());
+    }
+    fields.push(field);
+    Ok(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use bstr::BString;
+
+    use crate::common::entries::{parse_csv_record, parse_entry_fields, EntryFormat};
+
+    fn fields(format: &EntryFormat, entry: &str) -> Vec<(String, String)> {
+        let mut fields: Vec<_> = parse_entry_fields(format, &BString::from(entry))
+            .unwrap()
+            .into_iter()
+            .map(|(k, v)| (k, v.to_string()))
+            .collect();
+        fields.sort();
+        fields
+    }
+
+    #[test]
+    fn test_line_entry() {
+        assert!(fields(&EntryFormat::Line, "a,b").is_empty());
+    }
+
+    #[test]
+    fn test_json_entry() {
+        assert_eq!(
+            fields(&EntryFormat::Json, r#"{"input": "a.txt", "size": 10}"#),
+            vec![
+                ("input".to_string(), "a.txt".to_string()),
+                ("size".to_string(), "10".to_string())
+            ]
+        );
+        assert!(parse_entry_fields(&EntryFormat::Json, &BString::from("[1, 2]")).is_err());
+        assert!(parse_entry_fields(&EntryFormat::Json, &BString::from("{x")).is_err());
+    }
+
+    #[test]
+    fn test_csv_entry() {
+        let format = EntryFormat::Csv(vec!["input".to_string(), "size".to_string()]);
+        assert_eq!(
+            fields(&format, "a.txt,10"),
+            vec![
+                ("input".to_string(), "a.txt".to_string()),
+                ("size".to_string(), "10".to_string())
+            ]
+        );
+        assert!(parse_entry_fields(&format, &BString::from("a.txt")).is_err());
+    }
+
+    #[test]
+    fn test_params_entry() {
+        assert_eq!(
+            fields(&EntryFormat::Params, "lr=0.1,opt=a=b"),
+            vec![
+                ("lr".to_string(), "0.1".to_string()),
+                ("opt".to_string(), "a=b".to_string())
+            ]
+        );
+        assert!(parse_entry_fields(&EntryFormat::Params, &BString::from("lr")).is_err());
+    }
+
+    #[test]
+    fn test_csv_record() {
+        assert_eq!(parse_csv_record("a,,b").unwrap(), vec!["a", "", "b"]);
+        assert_eq!(
+            parse_csv_record("\"a,b\",\"x \"\"y\"\"\"\r").unwrap(),
+            vec!["a,b", "x \"y\""]
+        );
+        assert!(parse_csv_record("\"a,b").is_err());
+    }
+}
@@ -18,5 +18,12 @@ pub const HQ_TASK_ID: &str = create_hq_env!("TASK_ID");
 pub const HQ_INSTANCE_ID: &str = create_hq_env!("INSTANCE_ID");
 pub const HQ_SUBMIT_DIR: &str = create_hq_env!("SUBMIT_DIR");
 pub const HQ_ENTRY: &str = create_hq_env!("ENTRY");
+/// Prefix of variables with fields of a structured entry (`HQ_ENTRY_<field>`)
+pub const HQ_ENTRY_FIELD_PREFIX: &str = create_hq_env!("ENTRY_");
+/// Prefix of variables with parameters of a parameter sweep (`HQ_PARAM_<name>`)
+pub const HQ_PARAM_PREFIX: &str = create_hq_env!("PARAM_");
 pub const HQ_PIN: &str = create_hq_env!("PIN");
 pub const HQ_CPUS: &str = create_hq_env!("CPUS");
+/// Prefix of variables with indices of generic resources assigned to a task
+/// (`HQ_RESOURCE_VALUES_<name>`)
+pub const HQ_RESOURCE_VALUES_PREFIX: &str = create_hq_env!("RESOURCE_VALUES_");
@@ -0,0 +1,55 @@
+use tako::messages::common::WorkerConfiguration;
+
+/// Key of `WorkerConfiguration::extra` that contains comma-separated labels of a worker
+pub const WORKER_LABELS_KEY: &str = "LABELS";
+
+pub fn validate_label(label: &str) -> anyhow::Result<()> {
+    if label.is_empty()
+        || !label
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
+    {
+        anyhow::bail!(
+            "Invalid label '{}', a label may contain only letters, digits, '_', '-' and '.'",
+            label
+        );
+    }
+    Ok(())
+}
+
+/// Joins labels into the value stored in `WorkerConfiguration::extra`, duplicates are removed
+pub fn format_labels(labels: &[String]) -> String {
+    let mut labels: Vec<&str> = labels.iter().map(|l| l.as_str()).collect();
+    labels.sort_unstable();
+    labels.dedup();
+    labels.join(",")
+}
+
+pub fn worker_labels(configuration: &WorkerConfiguration) -> Vec<&str> {
+    configuration
+        .extra
+        .get(WORKER_LABELS_KEY)
+        .map(|labels| labels.split(',').filter(|l| !l.is_empty()).collect())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::labels::{format_labels, validate_label};
+
+    #[test]
+    fn test_validate_label() {
+        assert!(validate_label("bigmem").is_ok());
+        assert!(validate_label("gpu-v100.x_1").is_ok());
+        assert!(validate_label("").is_err());
+        assert!(validate_label("a,b").is_err());
+        assert!(validate_label("a b").is_err());
+    }
+
+    #[test]
+    fn test_format_labels() {
+        let labels = vec!["ssd".to_string(), "bigmem".to_string(), "ssd".to_string()];
+        assert_eq!(format_labels(&labels), "bigmem,ssd");
+        assert_eq!(format_labels(&[]), "");
+    }
+}
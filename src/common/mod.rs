@@ -2,9 +2,11 @@ pub use wrapped::WrappedRcRefCell;
 
 pub mod arraydef;
 pub mod arrayparser;
+pub mod entries;
 pub mod env;
 pub mod error;
 pub mod fsutils;
+pub mod labels;
 pub mod parser;
 pub mod serverdir;
 pub mod setup;
@@ -1,5 +1,6 @@
 use std::fmt::{Debug, Display, Formatter};
 
+use nom::bytes::complete::take_while1;
 use nom::character::complete::digit1;
 use nom::combinator::map_res;
 use nom::error::{ErrorKind, FromExternalError, ParseError};
@@ -50,3 +51,8 @@ pub type NomResult<'a, Ret> = IResult<&'a str, Ret, ParserError<&'a str>>;
 pub fn p_uint(input: &str) -> NomResult<u32> {
     map_res(digit1, |digit_str: &str| digit_str.parse::<u32>())(input)
 }
+
+/// Name of a generic resource, e.g. `mem` or `gpu`
+pub fn p_resource_name(input: &str) -> NomResult<&str> {
+    take_while1(|c: char| c.is_ascii_alphanumeric() || c == '_' || c == '-')(input)
+}
@@ -19,6 +19,7 @@ pub struct ServerDir {
 
 pub const SYMLINK_PATH: &str = "hq-current";
 const ACCESS_FILE: &str = "access.json";
+const JOURNAL_FILE: &str = "journal.bin";
 
 impl ServerDir {
     pub fn open(directory: &Path) -> crate::Result<Self> {
@@ -32,8 +33,8 @@ impl ServerDir {
     }
 
     pub fn create(directory: &Path, record: &AccessRecord) -> crate::Result<ServerDir> {
-        let dir_path = directory.join(record.start_date().format("%Y-%m-%d-%H-%M-%S").to_string());
-        std::fs::create_dir_all(&dir_path)?;
+        let name = record.start_date().format("%Y-%m-%d-%H-%M-%S").to_string();
+        let dir_path = create_unique_dir(directory, &name)?;
 
         let server_dir = ServerDir::open(&dir_path)?;
         let access_file_path = server_dir.access_filename();
@@ -56,6 +57,10 @@ impl ServerDir {
         self.path(ACCESS_FILE)
     }
 
+    pub fn journal_filename(&self) -> PathBuf {
+        self.path(JOURNAL_FILE)
+    }
+
     pub fn read_access_record(&self) -> crate::Result<AccessRecord> {
         let record = load_access_file(self.access_filename())?;
         let version = env!("CARGO_PKG_VERSION");
@@ -71,6 +76,49 @@ impl ServerDir {
     }
 }
 
+/// Returns the most recently created server directory inside `directory`.
+///
+/// It does not depend on `SYMLINK_PATH`, so it also finds directories of servers that were
+/// already stopped.
+pub fn find_last_server_dir(directory: &Path) -> crate::Result<ServerDir> {
+    let mut last: Option<PathBuf> = None;
+    for entry in std::fs::read_dir(directory)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+        let path = entry.path();
+        if last.as_ref().map(|p| p < &path).unwrap_or(true) {
+            last = Some(path);
+        }
+    }
+    match last {
+        Some(path) => ServerDir::open(&path),
+        None => error(format!("No server directory found in {:?}", directory)),
+    }
+}
+
+/// Creates a new directory `name` inside `directory`.
+///
+/// If the directory already exists (e.g. when two servers were started within the same second),
+/// a numeric suffix is appended, so that the names still sort by the time of creation.
+fn create_unique_dir(directory: &Path, name: &str) -> std::io::Result<PathBuf> {
+    std::fs::create_dir_all(directory)?;
+    let mut index = 0;
+    loop {
+        let path = if index == 0 {
+            directory.join(name)
+        } else {
+            directory.join(format!("{}-{:02}", name, index))
+        };
+        match std::fs::create_dir(&path) {
+            Ok(()) => return Ok(path),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => index += 1,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
 /// Returns either `path` if it doesn't contain `SYMLINK_PATH` or the target of `SYMLINK_PATH`.
 fn resolve_active_directory(path: &Path) -> PathBuf {
     let symlink_path = path.join(SYMLINK_PATH);
@@ -194,7 +242,9 @@ pub fn load_access_file<P: AsRef<Path>>(path: P) -> crate::Result<AccessRecord>
 mod tests {
     use tempdir::TempDir;
 
-    use crate::common::serverdir::{load_access_file, store_access_record, AccessRecord};
+    use crate::common::serverdir::{
+        find_last_server_dir, load_access_file, store_access_record, AccessRecord, ServerDir,
+    };
 
     #[test]
     fn test_roundtrip() {
@@ -205,4 +255,18 @@ mod tests {
         let loaded = load_access_file(path).unwrap();
         assert!(record == loaded);
     }
+
+    #[test]
+    fn test_create_in_same_second() {
+        let record =
+            AccessRecord::new("foo".into(), 42, 43, Default::default(), Default::default());
+        let dir = TempDir::new("foo").unwrap();
+        let first = ServerDir::create(dir.path(), &record).unwrap();
+        let second = ServerDir::create(dir.path(), &record).unwrap();
+        assert_ne!(first.directory(), second.directory());
+        assert_eq!(
+            find_last_server_dir(dir.path()).unwrap().directory(),
+            second.directory()
+        );
+    }
 }
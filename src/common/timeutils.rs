@@ -1,6 +1,7 @@
 use std::str::FromStr;
 use std::time::Duration;
 
+#[derive(Clone, Copy)]
 pub struct ArgDuration(Duration);
 
 impl ArgDuration {
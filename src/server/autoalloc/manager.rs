@@ -0,0 +1,358 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::Context;
+use tokio::process::Command;
+
+use crate::server::autoalloc::QueueId;
+use crate::transfer::messages::{AddQueueRequest, AllocationStatus, ManagerType};
+use crate::worker::manager::parse_hms_duration;
+use crate::Map;
+
+/// Exit status of a PBS job that was killed because it exceeded its walltime
+const PBS_EXIT_WALLTIME: i64 = -29;
+
+/// Name of PBS/SLURM jobs submitted by the given allocation queue
+pub fn allocation_name(queue_id: QueueId) -> String {
+    format!("hq-alloc-{}", queue_id)
+}
+
+/// Formats a duration as `HH:MM:SS`, which is understood both by PBS and SLURM
+fn format_walltime(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        (seconds % 3600) / 60,
+        seconds % 60
+    )
+}
+
+/// Quotes an argument for a shell script
+fn quote_arg(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', "'\\''"))
+}
+
+async fn run_command(program: &str, args: &[String]) -> anyhow::Result<String> {
+    log::debug!("Running {} {:?}", program, args);
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .await
+        .with_context(|| format!("Cannot run {}", program))?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "{} exited with {}: {}",
+            program,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Creates a script that starts a worker in the working directory of an allocation
+/// and submits it, returns the id of the submitted PBS/SLURM job
+pub async fn submit_allocation(
+    params: &AddQueueRequest,
+    name: &str,
+    server_dir: &Path,
+    working_dir: &Path,
+) -> anyhow::Result<String> {
+    std::fs::create_dir_all(working_dir).with_context(|| {
+        format!(
+            "Cannot create allocation directory {}",
+            working_dir.display()
+        )
+    })?;
+    let hq_path = std::env::current_exe()?;
+    let manager = match params.manager {
+        ManagerType::Pbs => "pbs",
+        ManagerType::Slurm => "slurm",
+    };
+    let script = format!(
+        "#!/bin/bash\n{} --server-dir {} worker start --manager {}\n",
+        quote_arg(&hq_path.to_string_lossy()),
+        quote_arg(&server_dir.to_string_lossy()),
+        manager
+    );
+    let script_path = working_dir.join("hq-submit.sh");
+    std::fs::write(&script_path, script)
+        .with_context(|| format!("Cannot write {}", script_path.display()))?;
+
+    let path_arg = |file: &str| -> String { working_dir.join(file).to_string_lossy().into() };
+    let walltime = format_walltime(params.time_limit);
+    let (program, mut args) = match params.manager {
+        ManagerType::Pbs => (
+            "qsub",
+            vec![
+                "-N".to_string(),
+                name.to_string(),
+                "-q".to_string(),
+                params.queue.clone(),
+                "-l".to_string(),
+                format!("walltime={}", walltime),
+                "-o".to_string(),
+                path_arg("stdout"),
+                "-e".to_string(),
+                path_arg("stderr"),
+            ],
+        ),
+        ManagerType::Slurm => (
+            "sbatch",
+            vec![
+                format!("--job-name={}", name),
+                format!("--partition={}", params.queue),
+                format!("--time={}", walltime),
+                "--nodes=1".to_string(),
+                format!("--output={}", path_arg("stdout")),
+                format!("--error={}", path_arg("stderr")),
+            ],
+        ),
+    };
+    args.extend(params.additional_args.iter().cloned());
+    args.push(script_path.to_string_lossy().into());
+
+    let output = run_command(program, &args).await?;
+    let id = match params.manager {
+        ManagerType::Pbs => parse_qsub_output(&output),
+        ManagerType::Slurm => parse_sbatch_output(&output),
+    };
+    id.ok_or_else(|| anyhow::anyhow!("Cannot parse the output of {}: {}", program, output))
+}
+
+/// Returns the status of the given allocations, allocations that are not known
+/// to the manager anymore are considered to be finished.
+/// Allocations whose status cannot be determined are missing in the result.
+pub async fn get_allocation_status(
+    manager: ManagerType,
+    name: &str,
+    ids: &[String],
+) -> anyhow::Result<Map<String, AllocationStatus>> {
+    match manager {
+        ManagerType::Pbs => Ok(get_pbs_allocation_status(ids).await),
+        ManagerType::Slurm => get_slurm_allocation_status(name, ids).await,
+    }
+}
+
+/// Each allocation is queried separately, because `qstat` fails when any of the given jobs
+/// is not known (e.g. when it was removed from the history of PBS)
+async fn get_pbs_allocation_status(ids: &[String]) -> Map<String, AllocationStatus> {
+    let mut result = Map::new();
+    for id in ids {
+        let args = vec![
+            "-f".to_string(),
+            "-F".to_string(),
+            "json".to_string(),
+            "-x".to_string(),
+            id.clone(),
+        ];
+        let status = match run_command("qstat", &args).await {
+            Ok(output) => parse_qstat_status(&output).map(|status| {
+                status
+                    .get(id)
+                    .copied()
+                    .unwrap_or(AllocationStatus::Finished)
+            }),
+            Err(e) if e.to_string().contains("Unknown Job Id") => Ok(AllocationStatus::Finished),
+            Err(e) => Err(e),
+        };
+        match status {
+            Ok(status) => {
+                result.insert(id.clone(), status);
+            }
+            Err(e) => log::warn!("Cannot get status of allocation {}: {:?}", id, e),
+        }
+    }
+    result
+}
+
+/// `--states=all` is needed, otherwise `squeue` shows only pending and running jobs
+async fn get_slurm_allocation_status(
+    name: &str,
+    ids: &[String],
+) -> anyhow::Result<Map<String, AllocationStatus>> {
+    let args = vec![
+        "--noheader".to_string(),
+        "--states=all".to_string(),
+        "--format=%i %T".to_string(),
+        format!("--name={}", name),
+    ];
+    let known = parse_squeue_status(&run_command("squeue", &args).await?);
+    Ok(ids
+        .iter()
+        .map(|id| {
+            let status = known.get(id).copied().unwrap_or(AllocationStatus::Finished);
+            (id.clone(), status)
+        })
+        .collect())
+}
+
+/// `qsub` prints the job id, e.g. `123.pbs-server`
+fn parse_qsub_output(output: &str) -> Option<String> {
+    output
+        .lines()
+        .map(|line| line.trim())
+        .find(|line| !line.is_empty())
+        .map(|line| line.to_string())
+}
+
+/// `sbatch` prints `Submitted batch job <id>`
+fn parse_sbatch_output(output: &str) -> Option<String> {
+    output
+        .lines()
+        .find(|line| line.starts_with("Submitted batch job"))
+        .and_then(|line| line.split_whitespace().last())
+        .map(|id| id.to_string())
+}
+
+/// Checks whether a PBS job used all of its walltime, such job is killed by PBS
+/// and it ends with a nonzero exit status
+fn pbs_walltime_reached(job: &serde_json::Value) -> bool {
+    let get_walltime =
+        |value: &serde_json::Value| value["walltime"].as_str().and_then(parse_hms_duration);
+    match (
+        get_walltime(&job["resources_used"]),
+        get_walltime(&job["Resource_List"]),
+    ) {
+        (Some(used), Some(walltime)) => used >= walltime,
+        _ => false,
+    }
+}
+
+/// Parses the output of `qstat -f -F json -x <ids>`.
+/// Allocations that were killed at the end of their walltime are finished, not failed.
+fn parse_qstat_status(output: &str) -> anyhow::Result<Map<String, AllocationStatus>> {
+    let value: serde_json::Value = serde_json::from_str(output)?;
+    let jobs = match value["Jobs"].as_object() {
+        Some(jobs) => jobs,
+        None => return Ok(Map::new()),
+    };
+    Ok(jobs
+        .iter()
+        .filter_map(|(id, job)| {
+            let status = match job["job_state"].as_str()? {
+                "Q" | "H" | "W" | "T" => AllocationStatus::Queued,
+                "R" | "E" | "B" => AllocationStatus::Running,
+                "F" => match job["Exit_status"].as_i64() {
+                    Some(0) | Some(PBS_EXIT_WALLTIME) => AllocationStatus::Finished,
+                    _ if pbs_walltime_reached(job) => AllocationStatus::Finished,
+                    _ => AllocationStatus::Failed,
+                },
+                _ => return None,
+            };
+            Some((id.clone(), status))
+        })
+        .collect())
+}
+
+/// Parses the output of `squeue --noheader --format="%i %T"`.
+/// Allocations that reached their time limit or that were canceled are finished, not failed.
+fn parse_squeue_status(output: &str) -> Map<String, AllocationStatus> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            let id = parts.next()?;
+            let status = match parts.next()? {
+                "PENDING" | "CONFIGURING" | "REQUEUED" | "RESV_DEL_HOLD" | "REQUEUE_HOLD"
+                | "SUSPENDED" => AllocationStatus::Queued,
+                "RUNNING" | "COMPLETING" => AllocationStatus::Running,
+                "COMPLETED" | "TIMEOUT" | "CANCELLED" => AllocationStatus::Finished,
+                _ => AllocationStatus::Failed,
+            };
+            Some((id.to_string(), status))
+        })
+        .collect()
+}
+
+pub fn allocation_working_dir(work_dir: &Path, queue_id: QueueId, index: u32) -> PathBuf {
+    work_dir.join(queue_id.to_string()).join(index.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::server::autoalloc::manager::{
+        format_walltime, parse_qstat_status, parse_qsub_output, parse_sbatch_output,
+        parse_squeue_status, quote_arg,
+    };
+    use crate::transfer::messages::AllocationStatus;
+
+    #[test]
+    fn test_format_walltime() {
+        assert_eq!(format_walltime(Duration::from_secs(3600)), "01:00:00");
+        assert_eq!(
+            format_walltime(Duration::from_secs(100 * 3600 + 61)),
+            "100:01:01"
+        );
+    }
+
+    #[test]
+    fn test_quote_arg() {
+        assert_eq!(quote_arg("/tmp/a b"), "'/tmp/a b'");
+        assert_eq!(quote_arg("it's"), "'it'\\''s'");
+    }
+
+    #[test]
+    fn test_parse_submit_output() {
+        assert_eq!(
+            parse_qsub_output("123.pbs-server\n").as_deref(),
+            Some("123.pbs-server")
+        );
+        assert_eq!(parse_qsub_output("\n"), None);
+        assert_eq!(
+            parse_sbatch_output("Submitted batch job 5678\n").as_deref(),
+            Some("5678")
+        );
+        assert_eq!(parse_sbatch_output("error\n"), None);
+    }
+
+    #[test]
+    fn test_parse_squeue_status() {
+        let status = parse_squeue_status(
+            "1 PENDING\n2 RUNNING\n3 COMPLETED\n4 FAILED\n5 TIMEOUT\n6 CANCELLED\n",
+        );
+        assert_eq!(status["1"], AllocationStatus::Queued);
+        assert_eq!(status["2"], AllocationStatus::Running);
+        assert_eq!(status["3"], AllocationStatus::Finished);
+        assert_eq!(status["4"], AllocationStatus::Failed);
+        assert_eq!(status["5"], AllocationStatus::Finished);
+        assert_eq!(status["6"], AllocationStatus::Finished);
+    }
+
+    #[test]
+    fn test_parse_qstat_status() {
+        let status = parse_qstat_status(
+            r#"{"Jobs": {
+                "1.pbs": {"job_state": "Q"},
+                "2.pbs": {"job_state": "R"},
+                "3.pbs": {"job_state": "F", "Exit_status": 0},
+                "4.pbs": {"job_state": "F", "Exit_status": 1},
+                "5.pbs": {"job_state": "F", "Exit_status": -29},
+                "6.pbs": {
+                    "job_state": "F",
+                    "Exit_status": 271,
+                    "Resource_List": {"walltime": "01:00:00"},
+                    "resources_used": {"walltime": "01:00:02"}
+                },
+                "7.pbs": {
+                    "job_state": "F",
+                    "Exit_status": 271,
+                    "Resource_List": {"walltime": "01:00:00"},
+                    "resources_used": {"walltime": "00:10:00"}
+                }
+            }}"#,
+        )
+        .unwrap();
+        assert_eq!(status["1.pbs"], AllocationStatus::Queued);
+        assert_eq!(status["2.pbs"], AllocationStatus::Running);
+        assert_eq!(status["3.pbs"], AllocationStatus::Finished);
+        assert_eq!(status["4.pbs"], AllocationStatus::Failed);
+        assert_eq!(status["5.pbs"], AllocationStatus::Finished);
+        assert_eq!(status["6.pbs"], AllocationStatus::Finished);
+        assert_eq!(status["7.pbs"], AllocationStatus::Failed);
+        assert!(parse_qstat_status("{}").unwrap().is_empty());
+    }
+}
@@ -0,0 +1,11 @@
+//! Automatic allocation of workers.
+//!
+//! The server periodically submits PBS/SLURM jobs (allocations) that start HQ workers
+//! when there are waiting tasks and tracks the status of submitted allocations.
+
+mod manager;
+mod process;
+mod state;
+
+pub use process::{autoalloc_process, AutoAllocContext};
+pub use state::{AutoAllocState, QueueId};
@@ -0,0 +1,113 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use chrono::Utc;
+
+use crate::server::autoalloc::manager::{
+    allocation_name, allocation_working_dir, get_allocation_status, submit_allocation,
+};
+use crate::server::autoalloc::QueueId;
+use crate::server::state::StateRef;
+
+pub struct AutoAllocContext {
+    /// Server directory that is passed to workers started in allocations
+    pub server_dir: PathBuf,
+    /// Directory where working directories of allocations are created
+    pub work_dir: PathBuf,
+    /// How often the status of allocations is refreshed and new allocations are submitted
+    pub refresh_interval: Duration,
+}
+
+/// Periodically refreshes allocations of all allocation queues and submits new allocations
+pub async fn autoalloc_process(state_ref: StateRef, context: AutoAllocContext) {
+    let mut interval = tokio::time::interval(context.refresh_interval);
+    loop {
+        interval.tick().await;
+        let queue_ids = state_ref.get().autoalloc().queue_ids();
+        for queue_id in queue_ids {
+            refresh_allocations(&state_ref, queue_id).await;
+            submit_allocations(&state_ref, &context, queue_id).await;
+        }
+    }
+}
+
+async fn refresh_allocations(state_ref: &StateRef, queue_id: QueueId) {
+    let (manager, ids) = match state_ref.get().autoalloc().get_queue(queue_id) {
+        Some(queue) => (queue.params.manager, queue.active_allocation_ids()),
+        None => return,
+    };
+    if ids.is_empty() {
+        return;
+    }
+    match get_allocation_status(manager, &allocation_name(queue_id), &ids).await {
+        Ok(status) => {
+            let now = Utc::now();
+            let mut state = state_ref.get_mut();
+            if let Some(queue) = state.autoalloc_mut().get_queue_mut(queue_id) {
+                for (id, status) in status {
+                    queue.update_allocation_status(&id, status, now);
+                }
+            }
+        }
+        Err(e) => log::warn!(
+            "Cannot get status of allocations of queue {}: {:?}",
+            queue_id,
+            e
+        ),
+    }
+}
+
+async fn submit_allocations(state_ref: &StateRef, context: &AutoAllocContext, queue_id: QueueId) {
+    loop {
+        let (params, working_dir) = {
+            let mut state = state_ref.get_mut();
+            if state.n_waiting_tasks() == 0 {
+                return;
+            }
+            let queue = match state.autoalloc_mut().get_queue_mut(queue_id) {
+                Some(queue) if queue.n_allocations_to_submit() > 0 => queue,
+                _ => return,
+            };
+            let index = queue.next_allocation_index();
+            (
+                queue.params.clone(),
+                allocation_working_dir(&context.work_dir, queue_id, index),
+            )
+        };
+
+        let result = submit_allocation(
+            &params,
+            &allocation_name(queue_id),
+            &context.server_dir,
+            &working_dir,
+        )
+        .await;
+
+        let mut state = state_ref.get_mut();
+        let queue = match state.autoalloc_mut().get_queue_mut(queue_id) {
+            Some(queue) => queue,
+            None => return,
+        };
+        match result {
+            Ok(id) => {
+                log::info!("Allocation {} was submitted by queue {}", id, queue_id);
+                queue.add_allocation(id, working_dir, Utc::now());
+            }
+            Err(e) => {
+                log::error!(
+                    "Submitting an allocation of queue {} failed: {:?}",
+                    queue_id,
+                    e
+                );
+                queue.add_submission_failure();
+                if queue.is_stopped() {
+                    log::error!(
+                        "Queue {} does not submit allocations anymore because of repeated failures",
+                        queue_id
+                    );
+                }
+                return;
+            }
+        }
+    }
+}
@@ -0,0 +1,288 @@
+use std::path::PathBuf;
+
+use chrono::{DateTime, Utc};
+
+use crate::transfer::messages::{
+    AddQueueRequest, AllocationInfo, AllocationQueueInfo, AllocationStatus,
+};
+use crate::Map;
+
+pub type QueueId = u32;
+
+/// Allocations are not submitted anymore when this number of allocations
+/// (or their submissions) fail in a row
+const MAX_CONSECUTIVE_FAILURES: u32 = 3;
+
+pub struct Allocation {
+    /// Id of the allocation in PBS/SLURM
+    pub id: String,
+    pub status: AllocationStatus,
+    pub working_dir: PathBuf,
+    pub queued_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub ended_at: Option<DateTime<Utc>>,
+}
+
+impl Allocation {
+    pub fn is_active(&self) -> bool {
+        matches!(
+            self.status,
+            AllocationStatus::Queued | AllocationStatus::Running
+        )
+    }
+}
+
+pub struct AllocationQueue {
+    pub id: QueueId,
+    pub params: AddQueueRequest,
+    allocations: Vec<Allocation>,
+    /// Number of allocations that were submitted (including failed submissions),
+    /// it is used to create working directories of allocations
+    allocation_counter: u32,
+    consecutive_failures: u32,
+}
+
+impl AllocationQueue {
+    fn new(id: QueueId, params: AddQueueRequest) -> Self {
+        AllocationQueue {
+            id,
+            params,
+            allocations: Vec::new(),
+            allocation_counter: 0,
+            consecutive_failures: 0,
+        }
+    }
+
+    pub fn next_allocation_index(&mut self) -> u32 {
+        self.allocation_counter += 1;
+        self.allocation_counter
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.consecutive_failures >= MAX_CONSECUTIVE_FAILURES
+    }
+
+    fn count_allocations(&self, status: AllocationStatus) -> u32 {
+        self.allocations
+            .iter()
+            .filter(|a| a.status == status)
+            .count() as u32
+    }
+
+    pub fn n_queued(&self) -> u32 {
+        self.count_allocations(AllocationStatus::Queued)
+    }
+
+    pub fn n_running(&self) -> u32 {
+        self.count_allocations(AllocationStatus::Running)
+    }
+
+    /// Number of allocations that should be submitted now
+    pub fn n_allocations_to_submit(&self) -> u32 {
+        if self.is_stopped() {
+            return 0;
+        }
+        let n_queued = self.n_queued();
+        let n_active = n_queued + self.n_running();
+        std::cmp::min(
+            self.params.backlog.saturating_sub(n_queued),
+            self.params.max_workers.saturating_sub(n_active),
+        )
+    }
+
+    pub fn active_allocation_ids(&self) -> Vec<String> {
+        self.allocations
+            .iter()
+            .filter(|a| a.is_active())
+            .map(|a| a.id.clone())
+            .collect()
+    }
+
+    pub fn add_allocation(&mut self, id: String, working_dir: PathBuf, now: DateTime<Utc>) {
+        self.allocations.push(Allocation {
+            id,
+            status: AllocationStatus::Queued,
+            working_dir,
+            queued_at: now,
+            started_at: None,
+            ended_at: None,
+        });
+    }
+
+    pub fn add_submission_failure(&mut self) {
+        self.consecutive_failures += 1;
+    }
+
+    pub fn update_allocation_status(
+        &mut self,
+        id: &str,
+        status: AllocationStatus,
+        now: DateTime<Utc>,
+    ) {
+        let allocation = match self.allocations.iter_mut().find(|a| a.id == id) {
+            Some(allocation) if allocation.is_active() => allocation,
+            _ => return,
+        };
+        if allocation.status == status {
+            return;
+        }
+        log::debug!(
+            "Allocation {} of queue {} changed status {:?} -> {:?}",
+            id,
+            self.id,
+            allocation.status,
+            status
+        );
+        if status != AllocationStatus::Queued && allocation.started_at.is_none() {
+            allocation.started_at = Some(now);
+        }
+        match status {
+            AllocationStatus::Queued => {}
+            AllocationStatus::Running => self.consecutive_failures = 0,
+            AllocationStatus::Finished => allocation.ended_at = Some(now),
+            AllocationStatus::Failed => {
+                allocation.ended_at = Some(now);
+                self.consecutive_failures += 1;
+            }
+        }
+        allocation.status = status;
+    }
+
+    pub fn make_info(&self) -> AllocationQueueInfo {
+        AllocationQueueInfo {
+            id: self.id,
+            manager: self.params.manager,
+            queue: self.params.queue.clone(),
+            time_limit: self.params.time_limit,
+            max_workers: self.params.max_workers,
+            backlog: self.params.backlog,
+            additional_args: self.params.additional_args.clone(),
+            stopped: self.is_stopped(),
+            n_queued: self.n_queued(),
+            n_running: self.n_running(),
+        }
+    }
+
+    pub fn make_allocation_infos(&self) -> Vec<AllocationInfo> {
+        self.allocations
+            .iter()
+            .map(|a| AllocationInfo {
+                id: a.id.clone(),
+                status: a.status,
+                working_dir: a.working_dir.clone(),
+                queued_at: a.queued_at,
+                started_at: a.started_at,
+                ended_at: a.ended_at,
+            })
+            .collect()
+    }
+}
+
+#[derive(Default)]
+pub struct AutoAllocState {
+    queues: Map<QueueId, AllocationQueue>,
+    queue_id_counter: QueueId,
+}
+
+impl AutoAllocState {
+    pub fn add_queue(&mut self, params: AddQueueRequest) -> QueueId {
+        self.queue_id_counter += 1;
+        let id = self.queue_id_counter;
+        self.queues.insert(id, AllocationQueue::new(id, params));
+        id
+    }
+
+    pub fn remove_queue(&mut self, id: QueueId) -> Option<AllocationQueue> {
+        self.queues.remove(&id)
+    }
+
+    pub fn get_queue(&self, id: QueueId) -> Option<&AllocationQueue> {
+        self.queues.get(&id)
+    }
+
+    pub fn get_queue_mut(&mut self, id: QueueId) -> Option<&mut AllocationQueue> {
+        self.queues.get_mut(&id)
+    }
+
+    pub fn queue_ids(&self) -> Vec<QueueId> {
+        let mut ids: Vec<QueueId> = self.queues.keys().copied().collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    pub fn make_queue_infos(&self) -> Vec<AllocationQueueInfo> {
+        self.queue_ids()
+            .into_iter()
+            .map(|id| self.queues[&id].make_info())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    use chrono::Utc;
+
+    use crate::server::autoalloc::state::AutoAllocState;
+    use crate::transfer::messages::{AddQueueRequest, AllocationStatus, ManagerType};
+
+    fn make_request(max_workers: u32, backlog: u32) -> AddQueueRequest {
+        AddQueueRequest {
+            manager: ManagerType::Slurm,
+            queue: "cpu".to_string(),
+            time_limit: Duration::from_secs(3600),
+            max_workers,
+            backlog,
+            additional_args: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_allocations_to_submit() {
+        let mut state = AutoAllocState::default();
+        let id = state.add_queue(make_request(3, 2));
+        let queue = state.get_queue_mut(id).unwrap();
+        assert_eq!(queue.n_allocations_to_submit(), 2);
+
+        let now = Utc::now();
+        queue.add_allocation("1".to_string(), PathBuf::new(), now);
+        queue.add_allocation("2".to_string(), PathBuf::new(), now);
+        assert_eq!(queue.n_allocations_to_submit(), 0);
+
+        queue.update_allocation_status("1", AllocationStatus::Running, now);
+        assert_eq!(queue.n_allocations_to_submit(), 1);
+        queue.add_allocation("3".to_string(), PathBuf::new(), now);
+        // max_workers is reached
+        queue.update_allocation_status("2", AllocationStatus::Running, now);
+        assert_eq!(queue.n_allocations_to_submit(), 0);
+
+        queue.update_allocation_status("1", AllocationStatus::Finished, now);
+        assert_eq!(queue.n_allocations_to_submit(), 1);
+        assert_eq!(queue.active_allocation_ids(), vec!["2", "3"]);
+    }
+
+    #[test]
+    fn test_stop_after_failures() {
+        let mut state = AutoAllocState::default();
+        let id = state.add_queue(make_request(10, 1));
+        let queue = state.get_queue_mut(id).unwrap();
+        let now = Utc::now();
+
+        queue.add_submission_failure();
+        queue.add_allocation("1".to_string(), PathBuf::new(), now);
+        queue.update_allocation_status("1", AllocationStatus::Failed, now);
+        assert!(!queue.is_stopped());
+        // A running allocation resets the counter
+        queue.add_allocation("2".to_string(), PathBuf::new(), now);
+        queue.update_allocation_status("2", AllocationStatus::Running, now);
+        queue.add_submission_failure();
+        queue.add_submission_failure();
+        assert!(!queue.is_stopped());
+        queue.add_submission_failure();
+        assert!(queue.is_stopped());
+        assert_eq!(queue.n_allocations_to_submit(), 0);
+        assert!(queue.make_info().stopped);
+    }
+}
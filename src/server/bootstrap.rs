@@ -4,14 +4,18 @@ use std::rc::Rc;
 use std::sync::Arc;
 
 use anyhow::Context;
-use cli_table::{print_stdout, Cell, Style, Table};
+use cli_table::{print_stdout, Cell, CellStruct, Style, Table};
 use tokio::net::TcpListener;
 use tokio::sync::Notify;
 use tokio::task::LocalSet;
 
 use crate::client::globalsettings::GlobalSettings;
-use crate::common::serverdir::{AccessRecord, ServerDir, SYMLINK_PATH};
+use crate::common::serverdir::{find_last_server_dir, AccessRecord, ServerDir, SYMLINK_PATH};
 use crate::common::setup::setup_interrupt;
+use crate::server::autoalloc::{autoalloc_process, AutoAllocContext};
+use crate::server::journal::{
+    flush_journal_process, read_journal, restore_state, Journal, JournalRecord,
+};
 use crate::server::rpc::Backend;
 use crate::server::state::StateRef;
 use crate::transfer::auth::generate_key;
@@ -26,6 +30,10 @@ enum ServerStatus {
 pub struct ServerConfig {
     pub host: String,
     pub idle_timeout: Option<Duration>,
+    /// Restore jobs from the journal of the previous server instance
+    pub restore: bool,
+    /// How often are allocations of automatic worker allocation refreshed
+    pub autoalloc_interval: Duration,
 }
 
 /// This function initializes the HQ server.
@@ -92,6 +100,12 @@ async fn initialize_server(
         .with_context(|| "Cannot create HQ server socket".to_string())?;
     let server_port = client_listener.local_addr()?.port();
 
+    let journal_events = if server_cfg.restore {
+        Some(load_previous_journal(server_directory)?)
+    } else {
+        None
+    };
+
     let hq_secret_key = Arc::new(generate_key());
     let tako_secret_key = Arc::new(generate_key());
 
@@ -111,15 +125,42 @@ async fn initialize_server(
         tako_secret_key.clone(),
     );
 
-    ServerDir::create(server_directory, &record)?;
+    let server_dir = ServerDir::create(server_directory, &record)?;
     print_access_record(gsettings, server_directory, &record);
 
+    let restored = {
+        let mut state = state_ref.get_mut();
+        state.set_journal(
+            Journal::create(&server_dir.journal_filename())
+                .context("Cannot create server journal")?,
+        );
+        journal_events.map(|events| restore_state(&mut state, events))
+    };
+
+    let autoalloc_context = AutoAllocContext {
+        server_dir: server_directory.to_path_buf(),
+        work_dir: server_dir.directory().join("autoalloc"),
+        refresh_interval: server_cfg.autoalloc_interval,
+    };
+
     let stop_notify = Rc::new(Notify::new());
     let stop_cloned = stop_notify.clone();
 
     let key = hq_secret_key;
     let fut = async move {
-        tokio::select! {
+        if let Some(restored) = restored {
+            let backend = tako_server.clone();
+            tokio::task::spawn_local(async move {
+                log::info!("Restoring {} unfinished task(s)", restored.n_tasks());
+                if let Err(e) = restored.submit(&backend).await {
+                    log::error!("Restoring tasks failed: {}", e);
+                }
+            });
+        }
+        tokio::task::spawn_local(autoalloc_process(state_ref.clone(), autoalloc_context));
+        tokio::task::spawn_local(flush_journal_process(state_ref.clone()));
+        let journal_state_ref = state_ref.clone();
+        let result = tokio::select! {
             _ = end_flag.notified() => {
                 log::info!("Received SIGINT");
                 Ok(())
@@ -136,11 +177,28 @@ async fn initialize_server(
                 key
             ) => { Ok(()) }
             r = tako_future => { r.map_err(|e| e.into()) }
-        }
+        };
+        journal_state_ref.get_mut().flush_journal();
+        result
     };
     Ok(fut)
 }
 
+/// Loads journal events of the server that was previously running in the given server directory
+fn load_previous_journal(server_directory: &Path) -> anyhow::Result<Vec<JournalRecord>> {
+    let server_dir = find_last_server_dir(server_directory)
+        .context("Cannot restore server: no previous server directory found")?;
+    let path = server_dir.journal_filename();
+    let events = read_journal(&path)
+        .with_context(|| format!("Cannot read server journal from {:?}", path))?;
+    log::info!(
+        "Restoring {} journal event(s) from {:?}",
+        events.len(),
+        path
+    );
+    Ok(events)
+}
+
 async fn start_server(
     gsettings: &GlobalSettings,
     server_config: ServerConfig,
@@ -157,7 +215,14 @@ async fn start_server(
 }
 
 pub fn print_access_record(gsettings: &GlobalSettings, server_dir: &Path, record: &AccessRecord) {
-    let rows = vec![
+    let table = access_record_rows(server_dir, record)
+        .table()
+        .color_choice(gsettings.color_policy());
+    assert!(print_stdout(table).is_ok());
+}
+
+pub fn access_record_rows(server_dir: &Path, record: &AccessRecord) -> Vec<Vec<CellStruct>> {
+    vec![
         vec![
             "Server directory".cell().bold(true),
             server_dir.display().cell(),
@@ -174,19 +239,15 @@ pub fn print_access_record(gsettings: &GlobalSettings, server_dir: &Path, record
             record.start_date().format("%F %T %Z").cell(),
         ],
         vec!["Version".cell().bold(true), record.version().cell()],
-    ];
-    let table = rows.table().color_choice(gsettings.color_policy());
-    assert!(print_stdout(table).is_ok());
+    ]
 }
 
-pub async fn print_server_info(gsettings: &GlobalSettings) -> anyhow::Result<()> {
-    match get_server_status(gsettings.server_directory()).await {
+/// Returns the access record of the server running in the given server directory.
+pub async fn get_online_access_record(server_directory: &Path) -> anyhow::Result<AccessRecord> {
+    match get_server_status(server_directory).await {
         Err(_) | Ok(ServerStatus::Offline(_)) => anyhow::bail!("No online server found"),
-        Ok(ServerStatus::Online(record)) => {
-            print_access_record(gsettings, gsettings.server_directory(), &record)
-        }
+        Ok(ServerStatus::Online(record)) => Ok(record),
     }
-    Ok(())
 }
 
 #[cfg(test)]
@@ -208,6 +269,7 @@ mod tests {
     use cli_table::ColorChoice;
     use std::future::Future;
     use std::path::Path;
+    use std::time::Duration;
 
     pub async fn init_test_server(
         tmp_dir: &Path,
@@ -216,6 +278,8 @@ mod tests {
         let server_cfg = ServerConfig {
             host: "localhost".to_string(),
             idle_timeout: None,
+            restore: false,
+            autoalloc_interval: Duration::from_secs(60),
         };
         let notify = Arc::new(Notify::new());
         (
@@ -3,6 +3,7 @@ use std::sync::Arc;
 
 use futures::{Sink, SinkExt, Stream, StreamExt};
 use orion::kdf::SecretKey;
+use tako::common::resources::ResourceRequest;
 use tako::messages::common::ProgramDefinition;
 use tako::messages::gateway::{
     CancelTasks, FromGatewayMessage, NewTasksMessage, StopWorkerRequest, TaskDef, ToGatewayMessage,
@@ -10,20 +11,26 @@ use tako::messages::gateway::{
 use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::{oneshot, Notify};
 
-use crate::client::status::{job_status, task_status, Status};
+use crate::client::status::{is_terminated, job_status, task_status, Status};
 use crate::common::arraydef::ArrayDef;
+use crate::common::entries::parse_entry_fields;
 use crate::common::env::{HQ_ENTRY, HQ_JOB_ID, HQ_SUBMIT_DIR, HQ_TASK_ID};
-use crate::server::job::Job;
+use crate::server::dependency::validate_task_dependencies;
+use crate::server::job::{Job, JobTaskState};
+use crate::server::journal::JournalEvent;
+use crate::server::pools::{PoolAmount, ResourcePools, ResourcePoolsRef};
 use crate::server::rpc::Backend;
-use crate::server::state::StateRef;
+use crate::server::state::{submit_tasks_from_callback, State, StateRef};
 use crate::stream::server::control::StreamServerControlMessage;
 use crate::transfer::connection::ServerConnection;
 use crate::transfer::messages::{
-    CancelJobResponse, FromClientMessage, JobInfoResponse, JobSelector, JobType, ResubmitRequest,
-    StatsResponse, StopWorkerResponse, SubmitRequest, SubmitResponse, TaskBody, ToClientMessage,
-    WorkerListResponse, WorkerSelector,
+    AutoAllocRequest, AutoAllocResponse, CancelJobResponse, DrainWorkerResponse, FromClientMessage,
+    HoldJobResponse, JobDetail, JobInfoResponse, JobSelector, JobType, RequeueRequest,
+    RequeueResponse, ResourcePoolRequest, ResubmitOverrides, ResubmitRequest, StatsResponse,
+    StopWorkerResponse, SubmitRequest, SubmitResponse, TaskBody, TaskDependency, TaskGroup,
+    ToClientMessage, UpdateJobRequest, UpdateJobResponse, WorkerListResponse, WorkerSelector,
 };
-use crate::{JobId, JobTaskCount, JobTaskId, WorkerId};
+use crate::{JobId, JobTaskCount, JobTaskId, Map, Set, TakoTaskId, WorkerId};
 use bstr::BString;
 use std::path::Path;
 
@@ -80,7 +87,9 @@ pub async fn client_rpc_loop<
                     FromClientMessage::Submit(msg) => {
                         handle_submit(&state_ref, &tako_ref, msg).await
                     }
-                    FromClientMessage::JobInfo(msg) => compute_job_info(&state_ref, msg.selector),
+                    FromClientMessage::JobInfo(msg) => {
+                        compute_job_info(&state_ref, msg.selector, msg.exit_code)
+                    }
                     FromClientMessage::Resubmit(msg) => {
                         handle_resubmit(&state_ref, &tako_ref, msg).await
                     }
@@ -95,15 +104,41 @@ pub async fn client_rpc_loop<
                     FromClientMessage::StopWorker(msg) => {
                         handle_worker_stop(&state_ref, &tako_ref, msg.selector).await
                     }
+                    FromClientMessage::DrainWorker(msg) => {
+                        handle_worker_drain(&state_ref, msg.selector)
+                    }
+                    FromClientMessage::WaitForDrain(msg) => {
+                        handle_wait_for_drain(&state_ref, msg.worker_id).await
+                    }
                     FromClientMessage::Cancel(msg) => {
-                        handle_job_cancel(&state_ref, &tako_ref, msg.selector).await
+                        handle_job_cancel(&state_ref, &tako_ref, msg.selector, msg.tasks).await
+                    }
+                    FromClientMessage::Hold(msg) => {
+                        handle_job_hold(&state_ref, &tako_ref, msg.selector, msg.hold).await
+                    }
+                    FromClientMessage::UpdateJob(msg) => {
+                        handle_job_update(&state_ref, &tako_ref, msg).await
+                    }
+                    FromClientMessage::Requeue(msg) => {
+                        handle_task_requeue(&state_ref, &tako_ref, msg).await
                     }
                     FromClientMessage::JobDetail(msg) => {
                         compute_job_detail(&state_ref, msg.job_id, msg.include_tasks)
                     }
                     FromClientMessage::Stats => compose_server_stats(&state_ref, &tako_ref).await,
+                    FromClientMessage::ResourcePools => ToClientMessage::ResourcePoolsResponse(
+                        state_ref.get().resource_pools().get().make_info(),
+                    ),
+                    FromClientMessage::SetResourcePool(msg) => {
+                        handle_set_resource_pool(&state_ref, &tako_ref, msg)
+                    }
+                    FromClientMessage::AutoAlloc(msg) => handle_autoalloc(&state_ref, msg),
                 };
-                assert!(tx.send(response).await.is_ok());
+                // A worker waiting for a drain may be gone when the response is sent
+                if tx.send(response).await.is_err() {
+                    log::debug!("Cannot send response to client");
+                    return;
+                }
             }
             Err(e) => {
                 log::error!("Cannot parse client message: {}", e);
@@ -178,6 +213,44 @@ async fn handle_worker_stop(
     ToClientMessage::StopWorkerResponse(responses)
 }
 
+fn handle_worker_drain(state_ref: &StateRef, selector: WorkerSelector) -> ToClientMessage {
+    log::debug!("Client asked for worker drain {:?}", selector);
+    let mut state = state_ref.get_mut();
+    let worker_ids: Vec<WorkerId> = match selector {
+        WorkerSelector::Specific(ids) => ids,
+        WorkerSelector::All => state
+            .get_workers()
+            .values()
+            .filter(|worker| !worker.is_offline() && !worker.is_draining())
+            .map(|worker| worker.worker_id())
+            .collect(),
+    };
+
+    let mut responses: Vec<(WorkerId, DrainWorkerResponse)> = Vec::new();
+    for worker_id in worker_ids {
+        let response = match state.get_worker(worker_id) {
+            None => DrainWorkerResponse::InvalidWorker,
+            Some(worker) if worker.is_offline() => DrainWorkerResponse::AlreadyStopped,
+            Some(worker) if worker.is_draining() => DrainWorkerResponse::AlreadyDraining,
+            Some(_) => DrainWorkerResponse::Draining,
+        };
+        if let DrainWorkerResponse::Draining = response {
+            state.drain_worker(worker_id);
+        }
+        responses.push((worker_id, response));
+    }
+    ToClientMessage::DrainWorkerResponse(responses)
+}
+
+/// Responds when the worker is asked to drain, the request is sent by the worker itself
+async fn handle_wait_for_drain(state_ref: &StateRef, worker_id: WorkerId) -> ToClientMessage {
+    let receiver = state_ref.get_mut().wait_for_drain(worker_id);
+    match receiver.await {
+        Ok(()) => ToClientMessage::WorkerDraining,
+        Err(_) => ToClientMessage::Error(format!("Worker {} is not running", worker_id)),
+    }
+}
+
 fn compute_job_detail(state_ref: &StateRef, job_id: JobId, include_tasks: bool) -> ToClientMessage {
     let state = state_ref.get();
     ToClientMessage::JobDetailResponse(
@@ -196,22 +269,64 @@ async fn compose_server_stats(_state_ref: &StateRef, backend: &Backend) -> ToCli
     ToClientMessage::StatsResponse(StatsResponse { stream_stats })
 }
 
-fn compute_job_info(state_ref: &StateRef, selector: JobSelector) -> ToClientMessage {
-    let state = state_ref.get();
+/// Checks whether a text matches a pattern, `*` in the pattern matches any sequence of characters
+fn matches_pattern(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let prefix = parts.next().unwrap();
+    if !text.starts_with(prefix) {
+        return false;
+    }
+    let mut rest = &text[prefix.len()..];
+    let parts: Vec<&str> = parts.collect();
+    let (suffix, middle) = match parts.split_last() {
+        Some(split) => split,
+        None => return rest.is_empty(),
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(position) => rest = &rest[position + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(suffix)
+}
 
-    let jobs: Vec<_> = match selector {
-        JobSelector::All => state.jobs().map(|j| j.make_job_info()).collect(),
-        JobSelector::LastN(n) => state
-            .last_n_ids(n)
-            .filter_map(|id| state.get_job(id))
-            .map(|j| j.make_job_info())
+/// Returns sorted ids of jobs chosen by a selector.
+/// Specific ids are returned even when the jobs do not exist, only ranges are cut
+/// at the last job id.
+fn resolve_job_selector(state: &State, selector: JobSelector) -> Vec<JobId> {
+    let mut job_ids: Vec<JobId> = match selector {
+        JobSelector::All => state.jobs().map(|job| job.job_id).collect(),
+        JobSelector::LastN(n) => return state.last_n_ids(n).collect(),
+        JobSelector::Specific(ids) => ids.iter_up_to(state.last_job_id()).collect(),
+        JobSelector::Name(pattern) => state
+            .jobs()
+            .filter(|job| matches_pattern(&pattern, &job.name))
+            .map(|job| job.job_id)
             .collect(),
-        JobSelector::Specific(ids) => ids
-            .into_iter()
-            .filter_map(|id| state.get_job(id))
-            .map(|j| j.make_job_info())
+        JobSelector::Status(statuses) => state
+            .jobs()
+            .filter(|job| statuses.contains(&job_status(&job.make_job_info())))
+            .map(|job| job.job_id)
             .collect(),
     };
+    job_ids.sort_unstable();
+    job_ids
+}
+
+fn compute_job_info(
+    state_ref: &StateRef,
+    selector: JobSelector,
+    exit_code: Option<i32>,
+) -> ToClientMessage {
+    let state = state_ref.get();
+
+    let jobs = resolve_job_selector(&state, selector)
+        .into_iter()
+        .filter_map(|id| state.get_job(id))
+        .filter(|job| exit_code.map_or(true, |code| job.has_task_exit_code(code)))
+        .map(|job| job.make_job_info())
+        .collect();
     ToClientMessage::JobInfoResponse(JobInfoResponse { jobs })
 }
 
@@ -219,70 +334,339 @@ async fn handle_job_cancel(
     state_ref: &StateRef,
     tako_ref: &Backend,
     selector: JobSelector,
+    tasks: Option<ArrayDef>,
 ) -> ToClientMessage {
     let job_ids: Vec<JobId> = match selector {
         JobSelector::All => state_ref
             .get()
             .jobs()
             .map(|job| job.make_job_info())
-            .filter(|job_info| matches!(job_status(job_info), Status::Waiting | Status::Running))
+            .filter(|job_info| {
+                matches!(
+                    job_status(job_info),
+                    Status::Waiting | Status::Running | Status::Held
+                )
+            })
             .map(|job_info| job_info.id)
             .collect(),
-        JobSelector::LastN(n) => state_ref.get().last_n_ids(n).collect(),
-        JobSelector::Specific(ids) => ids,
+        selector => resolve_job_selector(&state_ref.get(), selector),
     };
+    let requested_tasks: Option<Set<JobTaskId>> = tasks.map(|tasks| tasks.iter().collect());
 
     let mut responses: Vec<(JobId, CancelJobResponse)> = Vec::new();
     for job_id in job_ids {
-        let tako_task_ids;
-        {
-            let n_tasks = match state_ref.get().get_job(job_id) {
-                None => {
-                    responses.push((job_id, CancelJobResponse::InvalidJob));
-                    continue;
-                }
-                Some(job) => {
-                    tako_task_ids = job.non_finished_task_ids();
-                    job.n_tasks()
-                }
-            };
-            if tako_task_ids.is_empty() {
-                responses.push((job_id, CancelJobResponse::Canceled(Vec::new(), n_tasks)));
+        // `selected` is None when all tasks of the job are canceled
+        let (selected, invalid, tako_task_ids) = match state_ref.get().get_job(job_id) {
+            None => {
+                responses.push((job_id, CancelJobResponse::InvalidJob));
                 continue;
             }
-        }
+            Some(job) => match &requested_tasks {
+                None => (None, Vec::new(), job.non_finished_task_ids()),
+                Some(task_ids) => {
+                    let (selected, invalid) = job.find_tako_task_ids(task_ids);
+                    let submitted: Set<TakoTaskId> =
+                        job.non_finished_task_ids().into_iter().collect();
+                    let tako_task_ids = selected
+                        .iter()
+                        .map(|(tako_id, _)| *tako_id)
+                        .filter(|tako_id| submitted.contains(tako_id))
+                        .collect();
+                    (Some(selected), invalid, tako_task_ids)
+                }
+            },
+        };
 
-        let canceled_tasks = match tako_ref
-            .send_tako_message(FromGatewayMessage::CancelTasks(CancelTasks {
-                tasks: tako_task_ids,
-            }))
-            .await
-            .unwrap()
-        {
-            ToGatewayMessage::CancelTasksResponse(msg) => msg.cancelled_tasks,
-            ToGatewayMessage::Error(msg) => {
-                responses.push((job_id, CancelJobResponse::Failed(msg.message)));
-                continue;
+        let canceled_tasks = if tako_task_ids.is_empty() {
+            Vec::new()
+        } else {
+            match take_tasks_from_tako(tako_ref, tako_task_ids).await {
+                Ok(tasks) => tasks,
+                Err(e) => {
+                    responses.push((job_id, CancelJobResponse::Failed(e)));
+                    continue;
+                }
             }
-            _ => panic!("Invalid message"),
         };
 
         let mut state = state_ref.get_mut();
         let job = state.get_job_mut(job_id).unwrap();
-        let canceled_ids: Vec<_> = canceled_tasks
-            .iter()
-            .map(|tako_id| job.set_cancel_state(*tako_id, tako_ref))
+        let mut canceled: Vec<(TakoTaskId, JobTaskId)> = canceled_tasks
+            .into_iter()
+            .map(|tako_id| (tako_id, job.set_cancel_state(tako_id, tako_ref)))
             .collect();
-        let already_finished = job.n_tasks() - canceled_ids.len() as JobTaskCount;
+        let mut dependants: Vec<(TakoTaskId, JobTaskId)> = Vec::new();
+        let already_finished: Vec<JobTaskId> = match &selected {
+            None => {
+                canceled.extend(job.cancel_unsubmitted_tasks());
+                let canceled_ids: Set<JobTaskId> =
+                    canceled.iter().map(|(_, task_id)| *task_id).collect();
+                job.iter_task_states()
+                    .map(|(_, task_id, _)| task_id)
+                    .filter(|task_id| !canceled_ids.contains(task_id))
+                    .collect()
+            }
+            Some(selected) => {
+                for (tako_id, _) in selected {
+                    if let Some(task_id) = job.cancel_unsubmitted_task(*tako_id) {
+                        canceled.push((*tako_id, task_id));
+                    }
+                }
+                for (tako_id, _) in &canceled {
+                    dependants.extend(job.cancel_dependants(*tako_id));
+                }
+                let canceled_ids: Set<JobTaskId> =
+                    canceled.iter().map(|(_, task_id)| *task_id).collect();
+                selected
+                    .iter()
+                    .map(|(_, task_id)| *task_id)
+                    .filter(|task_id| !canceled_ids.contains(task_id))
+                    .collect()
+            }
+        };
+        job.unregister_stream_if_terminated(tako_ref);
+        submit_tasks_from_callback(tako_ref, job.take_throttled_tasks());
+        for (task_id, _) in canceled.iter().chain(dependants.iter()) {
+            state.write_journal(JournalEvent::TaskCanceled { task_id: *task_id });
+        }
+        submit_tasks_from_callback(tako_ref, state.take_pool_tasks());
+        state.resolve_held_jobs_if_terminated(job_id, tako_ref);
         responses.push((
             job_id,
-            CancelJobResponse::Canceled(canceled_ids, already_finished),
+            CancelJobResponse::Canceled {
+                canceled: canceled.into_iter().map(|(_, task_id)| task_id).collect(),
+                dependants: dependants.into_iter().map(|(_, task_id)| task_id).collect(),
+                already_finished,
+                invalid,
+            },
         ));
     }
 
     ToClientMessage::CancelJobResponse(responses)
 }
 
+async fn handle_task_requeue(
+    state_ref: &StateRef,
+    tako_ref: &Backend,
+    request: RequeueRequest,
+) -> ToClientMessage {
+    let job_id = request.job_id;
+    let (running, mut not_running, invalid) = match state_ref.get().get_job(job_id) {
+        None => return ToClientMessage::RequeueResponse(RequeueResponse::InvalidJob),
+        Some(job) => {
+            let task_ids: Set<JobTaskId> = request.tasks.iter().collect();
+            let (selected, invalid) = job.find_tako_task_ids(&task_ids);
+            let running_ids: Set<TakoTaskId> = job
+                .iter_task_states()
+                .filter(|(_, _, state)| matches!(state, JobTaskState::Running { .. }))
+                .map(|(tako_id, _, _)| tako_id)
+                .collect();
+            let (running, not_running): (Vec<_>, Vec<_>) = selected
+                .into_iter()
+                .partition(|(tako_id, _)| running_ids.contains(tako_id));
+            let not_running: Vec<JobTaskId> = not_running
+                .into_iter()
+                .map(|(_, task_id)| task_id)
+                .collect();
+            (running, not_running, invalid)
+        }
+    };
+
+    let canceled_tasks = if running.is_empty() {
+        Vec::new()
+    } else {
+        let tako_task_ids = running.iter().map(|(tako_id, _)| *tako_id).collect();
+        match take_tasks_from_tako(tako_ref, tako_task_ids).await {
+            Ok(tasks) => tasks,
+            Err(e) => return ToClientMessage::RequeueResponse(RequeueResponse::Failed(e)),
+        }
+    };
+    let task_defs = state_ref
+        .get_mut()
+        .get_job_mut(job_id)
+        .unwrap()
+        .resubmit_canceled_tasks(&canceled_tasks);
+    submit_tasks_from_callback(tako_ref, task_defs);
+
+    // Tasks that were not canceled by tako have finished in the meantime
+    let canceled_tasks: Set<TakoTaskId> = canceled_tasks.into_iter().collect();
+    let (requeued, finished): (Vec<_>, Vec<_>) = running
+        .into_iter()
+        .partition(|(tako_id, _)| canceled_tasks.contains(tako_id));
+    not_running.extend(finished.into_iter().map(|(_, task_id)| task_id));
+    ToClientMessage::RequeueResponse(RequeueResponse::Requeued {
+        requeued: requeued.into_iter().map(|(_, task_id)| task_id).collect(),
+        not_running,
+        invalid,
+    })
+}
+
+async fn handle_job_hold(
+    state_ref: &StateRef,
+    tako_ref: &Backend,
+    selector: JobSelector,
+    hold: bool,
+) -> ToClientMessage {
+    let job_ids: Vec<JobId> = match selector {
+        JobSelector::All => state_ref
+            .get()
+            .jobs()
+            .filter(|job| !job.is_terminated() && job.is_held_by_user() != hold)
+            .map(|job| job.job_id)
+            .collect(),
+        selector => resolve_job_selector(&state_ref.get(), selector),
+    };
+
+    let mut responses: Vec<(JobId, HoldJobResponse)> = Vec::new();
+    for job_id in job_ids {
+        let response = if hold {
+            hold_job(state_ref, tako_ref, job_id).await
+        } else {
+            release_job(state_ref, tako_ref, job_id)
+        };
+        responses.push((job_id, response));
+    }
+    ToClientMessage::HoldJobResponse(responses)
+}
+
+/// Holds a job, its waiting tasks are taken back from tako, running tasks are not affected
+async fn hold_job(state_ref: &StateRef, tako_ref: &Backend, job_id: JobId) -> HoldJobResponse {
+    let tako_task_ids = {
+        let mut state = state_ref.get_mut();
+        let job = match state.get_job_mut(job_id) {
+            Some(job) => job,
+            None => return HoldJobResponse::InvalidJob,
+        };
+        if job.is_terminated() {
+            return HoldJobResponse::Terminated;
+        }
+        let tako_task_ids = job.hold();
+        state.write_journal(JournalEvent::JobHeld { job_id });
+        tako_task_ids
+    };
+
+    if !tako_task_ids.is_empty() {
+        let canceled_tasks = match take_tasks_from_tako(tako_ref, tako_task_ids).await {
+            Ok(tasks) => tasks,
+            Err(e) => return HoldJobResponse::Failed(e),
+        };
+        // The job may be released before tako has answered
+        let mut state = state_ref.get_mut();
+        let released = state
+            .get_job_mut(job_id)
+            .unwrap()
+            .hold_canceled_tasks(&canceled_tasks);
+        submit_tasks_from_callback(tako_ref, released);
+        // Units of resource pools taken by held tasks may be used by other jobs
+        submit_tasks_from_callback(tako_ref, state.take_pool_tasks());
+    }
+
+    let state = state_ref.get();
+    HoldJobResponse::Held(state.get_job(job_id).unwrap().counters.n_held_tasks)
+}
+
+/// Cancels waiting tasks in tako, so they can be held or submitted again,
+/// returns ids of tasks that were canceled.
+///
+/// Ids have to be collected from the state right before this call without awaiting
+/// in between, so only tasks that the server sees as waiting are canceled. Tasks that tako
+/// starts before it receives the request are reported as running before the response arrives,
+/// callers check the state of canceled tasks against the response.
+async fn take_tasks_from_tako(
+    tako_ref: &Backend,
+    tako_task_ids: Vec<TakoTaskId>,
+) -> Result<Vec<TakoTaskId>, String> {
+    match tako_ref
+        .send_tako_message(FromGatewayMessage::CancelTasks(CancelTasks {
+            tasks: tako_task_ids,
+        }))
+        .await
+        .unwrap()
+    {
+        ToGatewayMessage::CancelTasksResponse(msg) => Ok(msg.cancelled_tasks),
+        ToGatewayMessage::Error(msg) => Err(msg.message),
+        _ => panic!("Invalid message"),
+    }
+}
+
+fn release_job(state_ref: &StateRef, tako_ref: &Backend, job_id: JobId) -> HoldJobResponse {
+    let mut state = state_ref.get_mut();
+    let job = match state.get_job_mut(job_id) {
+        Some(job) => job,
+        None => return HoldJobResponse::InvalidJob,
+    };
+    if !job.is_held_by_user() {
+        return HoldJobResponse::NotHeld;
+    }
+    // Some released tasks may be kept by the server because of `max_parallel`
+    let n_held_tasks = job.counters.n_held_tasks;
+    let task_defs = job.release();
+    let count = n_held_tasks - job.counters.n_held_tasks;
+    state.write_journal(JournalEvent::JobReleased { job_id });
+    submit_tasks_from_callback(tako_ref, task_defs);
+    HoldJobResponse::Released(count)
+}
+
+async fn handle_job_update(
+    state_ref: &StateRef,
+    tako_ref: &Backend,
+    request: UpdateJobRequest,
+) -> ToClientMessage {
+    let job_id = request.job_id;
+    let (tako_task_ids, max_fails_exceeded) = {
+        let mut state = state_ref.get_mut();
+        let job = match state.get_job_mut(job_id) {
+            Some(job) => job,
+            None => return ToClientMessage::UpdateJobResponse(UpdateJobResponse::InvalidJob),
+        };
+        let tako_task_ids = match request.priority {
+            Some(priority) => job.set_priority(priority),
+            None => Vec::new(),
+        };
+        if request.max_fails.is_some() {
+            job.max_fails = request.max_fails;
+        }
+        let max_fails_exceeded = !job.is_terminated()
+            && job
+                .max_fails
+                .map_or(false, |max_fails| job.counters.n_failed_tasks > max_fails);
+        state.write_journal(JournalEvent::JobUpdated {
+            job_id,
+            priority: request.priority,
+            max_fails: request.max_fails,
+        });
+        (tako_task_ids, max_fails_exceeded)
+    };
+
+    if max_fails_exceeded {
+        // The job has already more failed tasks than the new limit allows
+        handle_job_cancel(
+            state_ref,
+            tako_ref,
+            JobSelector::Specific(ArrayDef::simple_range(job_id, 1)),
+            None,
+        )
+        .await;
+        return ToClientMessage::UpdateJobResponse(UpdateJobResponse::Updated(0));
+    }
+
+    let mut count = 0;
+    if !tako_task_ids.is_empty() {
+        let canceled_tasks = match take_tasks_from_tako(tako_ref, tako_task_ids).await {
+            Ok(tasks) => tasks,
+            Err(e) => return ToClientMessage::UpdateJobResponse(UpdateJobResponse::Failed(e)),
+        };
+        let task_defs = state_ref
+            .get_mut()
+            .get_job_mut(job_id)
+            .unwrap()
+            .resubmit_canceled_tasks(&canceled_tasks);
+        count = task_defs.len() as JobTaskCount;
+        submit_tasks_from_callback(tako_ref, task_defs);
+    }
+    ToClientMessage::UpdateJobResponse(UpdateJobResponse::Updated(count))
+}
+
 fn make_program_def_for_task(
     program_def: &ProgramDefinition,
     job_id: JobId,
@@ -300,23 +684,47 @@ fn make_program_def_for_task(
     def
 }
 
-async fn handle_submit(
-    state_ref: &StateRef,
-    tako_ref: &Backend,
+/// Creates a job and definitions of its tasks from a submit request
+pub(crate) fn prepare_job(
+    job_id: JobId,
+    tako_base_id: TakoTaskId,
     message: SubmitRequest,
-) -> ToClientMessage {
-    if message.resources.validate().is_err() {
-        return ToClientMessage::Error("Invalid resource request".to_string());
-    }
+    pools: &ResourcePoolsRef,
+) -> (Job, Vec<TaskDef>) {
     let resources = message.resources;
     let spec = message.spec;
     let pin = message.pin;
     let submit_dir = message.submit_dir;
     let priority = message.priority;
+    let task_deps = message.task_deps;
+    let after = message.after;
+    let max_retries = message.max_retries;
+    let retry_delay = message.retry_delay;
+    let time_limit = message.time_limit;
+    let task_groups = message.task_groups;
+    let entry_format = message.entry_format;
+    let generic_resources = message.generic_resources;
+    let required_labels = message.required_labels;
+    let time_request = message.time_request;
+    let groups: Map<JobTaskId, &TaskGroup> = task_groups
+        .iter()
+        .flat_map(|group| group.tasks.iter().map(move |task_id| (task_id, group)))
+        .collect();
 
     let make_task = |job_id, task_id, tako_id, entry: Option<BString>| {
-        let mut program = make_program_def_for_task(&spec, job_id, task_id, &submit_dir);
+        let (spec, resources, pin, priority) = match groups.get(&task_id) {
+            Some(group) => (&group.spec, &group.resources, group.pin, group.priority),
+            None => (&spec, &resources, pin, priority),
+        };
+        let mut program = make_program_def_for_task(spec, job_id, task_id, &submit_dir);
         if let Some(e) = entry {
+            // Entries are validated by the client, invalid fields are ignored
+            for (key, value) in parse_entry_fields(&entry_format, &e).unwrap_or_default() {
+                program.env.insert(
+                    format!("{}{}", entry_format.field_prefix(), key).into(),
+                    value,
+                );
+            }
             program.env.insert(HQ_ENTRY.into(), e);
         }
         let body_msg = TaskBody {
@@ -324,6 +732,10 @@ async fn handle_submit(
             pin,
             job_id,
             task_id,
+            time_limit,
+            resources: generic_resources.clone(),
+            required_labels: required_labels.clone(),
+            time_request,
         };
         let body = tako::transfer::auth::serialize(&body_msg).unwrap();
         TaskDef {
@@ -337,6 +749,124 @@ async fn handle_submit(
             resources: resources.clone(),
         }
     };
+    let task_defs = match (&message.job_type, message.entries.clone()) {
+        (JobType::Simple, _) => vec![make_task(job_id, 0, tako_base_id, None)],
+        (JobType::Array(a), None) => a
+            .iter()
+            .zip(tako_base_id..)
+            .map(|(task_id, tako_id)| make_task(job_id, task_id, tako_id, None))
+            .collect(),
+        (JobType::Array(a), Some(entries)) => a
+            .iter()
+            .zip(tako_base_id..)
+            .zip(entries.into_iter())
+            .map(|((task_id, tako_id), entry)| make_task(job_id, task_id, tako_id, Some(entry)))
+            .collect(),
+    };
+    let mut job = Job::new(
+        message.job_type,
+        job_id,
+        tako_base_id,
+        message.name,
+        spec,
+        resources,
+        pin,
+        message.max_fails,
+        message.entries,
+        message.priority,
+        message.log,
+    );
+    job.set_task_defs(&task_defs);
+    job.set_retries(max_retries, retry_delay);
+    job.time_limit = time_limit;
+    job.task_groups = task_groups;
+    job.entry_format = entry_format;
+    job.max_parallel = message.max_parallel;
+    job.set_pool_usage(message.pool_usage, pools.clone());
+    job.generic_resources = generic_resources;
+    job.required_labels = required_labels;
+    job.time_request = time_request;
+    let task_defs = if task_deps.is_empty() {
+        task_defs
+    } else {
+        block_dependent_tasks(&mut job, task_defs, task_deps)
+    };
+    match after {
+        Some(after) => {
+            job.hold_tasks(after, task_defs);
+            (job, Vec::new())
+        }
+        None => {
+            let task_defs = job.submit_or_hold(task_defs);
+            (job, task_defs)
+        }
+    }
+}
+
+/// Blocks tasks that depend on other tasks of the job, returns tasks that are ready
+fn block_dependent_tasks(
+    job: &mut Job,
+    task_defs: Vec<TaskDef>,
+    task_deps: Vec<TaskDependency>,
+) -> Vec<TaskDef> {
+    let tako_ids: Map<JobTaskId, TakoTaskId> = job
+        .iter_task_states()
+        .map(|(tako_id, task_id, _)| (task_id, tako_id))
+        .collect();
+    let mut deps: Map<TakoTaskId, Set<TakoTaskId>> = Map::new();
+    for dep in task_deps {
+        deps.entry(tako_ids[&dep.task_id])
+            .or_default()
+            .extend(dep.depends_on.iter().map(|task_id| tako_ids[task_id]));
+    }
+    let mut ready_defs = Vec::new();
+    for task_def in task_defs {
+        match deps.remove(&task_def.id) {
+            Some(task_deps) if !task_deps.is_empty() => job.add_blocked_task(task_def, task_deps),
+            _ => ready_defs.push(task_def),
+        }
+    }
+    ready_defs
+}
+
+async fn handle_submit(
+    state_ref: &StateRef,
+    tako_ref: &Backend,
+    message: SubmitRequest,
+) -> ToClientMessage {
+    if message.resources.validate().is_err()
+        || message
+            .task_groups
+            .iter()
+            .any(|group| group.resources.validate().is_err())
+    {
+        return ToClientMessage::Error("Invalid resource request".to_string());
+    }
+    if !message.task_deps.is_empty() {
+        let task_ids: Set<JobTaskId> = match &message.job_type {
+            JobType::Simple => {
+                return ToClientMessage::Error(
+                    "Task dependencies can be used only in task arrays".to_string(),
+                )
+            }
+            JobType::Array(a) => a.iter().collect(),
+        };
+        if let Err(e) = validate_task_dependencies(&task_ids, &message.task_deps) {
+            return ToClientMessage::Error(e);
+        }
+    }
+    if let Some(after) = &message.after {
+        let state = state_ref.get();
+        if let Some(job_id) = after.jobs.iter().find(|id| state.get_job(**id).is_none()) {
+            return ToClientMessage::Error(format!("Job {} does not exist", job_id));
+        }
+    }
+    if let Err(e) =
+        validate_pool_usage(&state_ref.get().resource_pools().get(), &message.pool_usage)
+    {
+        return ToClientMessage::Error(e);
+    }
+    let log_path = message.log.as_ref().map(|log| message.submit_dir.join(log));
     let (task_defs, job_detail, job_id) = {
         let mut state = state_ref.get_mut();
         let job_id = state.new_job_id();
@@ -345,65 +875,88 @@ async fn handle_submit(
             JobType::Array(a) => a.task_count(),
         };
         let tako_base_id = state.new_task_id(task_count);
-        let task_defs = match (&message.job_type, message.entries.clone()) {
-            (JobType::Simple, _) => vec![make_task(job_id, 0, tako_base_id, None)],
-            (JobType::Array(a), None) => a
-                .iter()
-                .zip(tako_base_id..)
-                .map(|(task_id, tako_id)| make_task(job_id, task_id, tako_id, None))
-                .collect(),
-            (JobType::Array(a), Some(entries)) => a
-                .iter()
-                .zip(tako_base_id..)
-                .zip(entries.into_iter())
-                .map(|((task_id, tako_id), entry)| make_task(job_id, task_id, tako_id, Some(entry)))
-                .collect(),
-        };
-        let job = Job::new(
-            message.job_type,
+        state.write_journal(JournalEvent::JobSubmitted {
             job_id,
-            tako_base_id,
-            message.name.clone(),
-            spec,
-            resources,
-            pin,
-            message.max_fails,
-            message.entries.clone(),
-            message.priority,
-            message.log.clone(),
-        );
-        let job_detail = job.make_job_detail(false);
+            base_task_id: tako_base_id,
+            request: message.clone(),
+        });
+        let pools = state.resource_pools().clone();
+        let (job, mut task_defs) = prepare_job(job_id, tako_base_id, message, &pools);
         state.add_job(job);
 
+        // Job dependencies may be already resolved
+        let (ready, _) = state.resolve_job_dependencies();
+        task_defs.extend(ready);
+        let job_detail = state.get_job(job_id).unwrap().make_job_detail(false);
+
         (task_defs, job_detail, job_id)
     };
 
-    if let Some(log) = message.log {
+    // Job may be canceled immediately because of its job dependencies
+    let log_path = log_path.filter(|_| !is_terminated(&job_detail.info));
+    if let Some(path) = log_path {
         let (sender, receiver) = oneshot::channel();
         tako_ref.send_stream_control(StreamServerControlMessage::RegisterStream {
             job_id,
-            path: submit_dir.join(log),
+            path,
+            append: false,
             response: sender,
         });
         assert!(receiver.await.is_ok());
     }
 
-    match tako_ref
-        .send_tako_message(FromGatewayMessage::NewTasks(NewTasksMessage {
-            tasks: task_defs,
-        }))
-        .await
-        .unwrap()
-    {
-        ToGatewayMessage::NewTasksResponse(_) => { /* Ok */ }
-        _ => {
-            panic!("Invalid response");
-        }
-    };
+    if !task_defs.is_empty() {
+        match tako_ref
+            .send_tako_message(FromGatewayMessage::NewTasks(NewTasksMessage {
+                tasks: task_defs,
+            }))
+            .await
+            .unwrap()
+        {
+            ToGatewayMessage::NewTasksResponse(_) => { /* Ok */ }
+            _ => {
+                panic!("Invalid response");
+            }
+        };
+    }
 
     ToClientMessage::SubmitResponse(SubmitResponse { job: job_detail })
 }
 
+/// Applies overrides of a resubmitted job on the configuration of its tasks
+fn apply_overrides(
+    overrides: &ResubmitOverrides,
+    spec: &ProgramDefinition,
+    resources: &ResourceRequest,
+    pin: bool,
+    priority: tako::Priority,
+) -> (ProgramDefinition, ResourceRequest, bool, tako::Priority) {
+    let mut spec = spec.clone();
+    if let Some(args) = &overrides.args {
+        spec.args = args.clone();
+    }
+    spec.env.extend(overrides.env.clone());
+    if let Some(cwd) = &overrides.cwd {
+        spec.cwd = Some(cwd.clone());
+    }
+    if let Some(stdout) = &overrides.stdout {
+        spec.stdout = stdout.clone();
+    }
+    if let Some(stderr) = &overrides.stderr {
+        spec.stderr = stderr.clone();
+    }
+    let resources = match &overrides.cpus {
+        Some(cpus) => ResourceRequest::new(cpus.clone()),
+        None => resources.clone(),
+    };
+    (
+        spec,
+        resources,
+        overrides.pin.unwrap_or(pin),
+        overrides.priority.unwrap_or(priority),
+    )
+}
+
 async fn handle_resubmit(
     state_ref: &StateRef,
     tako_ref: &Backend,
@@ -430,29 +983,80 @@ async fn handle_resubmit(
         ids.sort_unstable();
 
         if !ids.is_empty() {
+            let resubmitted: Set<JobTaskId> = ids.iter().copied().collect();
             let job_type = match &job.job_type {
                 JobType::Simple => job.job_type.clone(),
                 JobType::Array(_) => JobType::Array(ArrayDef::new_tasks(ids)),
             };
-            let spec = job.program_def.clone();
-            let name = job.info.name.clone();
-            let resources = job.resources.clone();
-            let pin = job.pin;
-            let entries = job.entries.clone();
-            let max_fails = job.max_fails;
-            let priority = job.priority;
+            let overrides = message.overrides;
+            let JobDetail {
+                program_def: spec,
+                resources,
+                pin,
+                priority,
+                ..
+            } = &job;
+            let (spec, resources, pin, priority) =
+                apply_overrides(&overrides, spec, resources, *pin, *priority);
+            let task_groups = job
+                .task_groups
+                .iter()
+                .filter_map(|group| {
+                    let tasks: Vec<JobTaskId> = group
+                        .tasks
+                        .iter()
+                        .filter(|task_id| resubmitted.contains(task_id))
+                        .collect();
+                    if tasks.is_empty() {
+                        return None;
+                    }
+                    let (spec, resources, pin, priority) = apply_overrides(
+                        &overrides,
+                        &group.spec,
+                        &group.resources,
+                        group.pin,
+                        group.priority,
+                    );
+                    Some(TaskGroup {
+                        tasks: ArrayDef::new_tasks(tasks),
+                        spec,
+                        resources,
+                        pin,
+                        priority,
+                    })
+                })
+                .collect();
 
             let msg_submit = SubmitRequest {
                 job_type,
-                name,
-                max_fails,
+                name: overrides.name.unwrap_or_else(|| job.info.name.clone()),
+                max_fails: overrides.max_fails.or(job.max_fails),
                 spec,
                 resources,
                 pin,
-                entries,
+                entries: job.entries.clone(),
+                entry_format: job.entry_format.clone(),
                 submit_dir: std::env::current_dir().unwrap().to_str().unwrap().into(),
                 priority,
                 log: None, // TODO: Reuse log configuration
+                // Resubmitted tasks are independent, their dependencies may not be resubmitted
+                task_deps: Vec::new(),
+                after: None,
+                max_retries: overrides.max_retries.unwrap_or(job.max_retries),
+                retry_delay: overrides.retry_delay.unwrap_or(job.retry_delay),
+                time_limit: overrides.time_limit.or(job.time_limit),
+                task_groups,
+                max_parallel: overrides.max_parallel.or(job.max_parallel),
+                pool_usage: overrides
+                    .pool_usage
+                    .unwrap_or_else(|| job.pool_usage.clone()),
+                generic_resources: overrides
+                    .generic_resources
+                    .unwrap_or_else(|| job.generic_resources.clone()),
+                required_labels: overrides
+                    .required_labels
+                    .unwrap_or_else(|| job.required_labels.clone()),
+                time_request: overrides.time_request.or(job.time_request),
             };
             handle_submit(&state_ref.clone(), &tako_ref.clone(), msg_submit).await
         } else {
@@ -463,6 +1067,70 @@ async fn handle_resubmit(
     }
 }
 
+fn handle_set_resource_pool(
+    state_ref: &StateRef,
+    tako_ref: &Backend,
+    request: ResourcePoolRequest,
+) -> ToClientMessage {
+    let mut state = state_ref.get_mut();
+    state
+        .resource_pools()
+        .get_mut()
+        .set_size(&request.name, request.size);
+    state.write_journal(JournalEvent::ResourcePoolChanged {
+        name: request.name,
+        size: request.size,
+    });
+    // Tasks may wait for units of a pool that was enlarged
+    submit_tasks_from_callback(tako_ref, state.take_pool_tasks());
+    ToClientMessage::ResourcePoolsResponse(state.resource_pools().get().make_info())
+}
+
+fn handle_autoalloc(state_ref: &StateRef, request: AutoAllocRequest) -> ToClientMessage {
+    let mut state = state_ref.get_mut();
+    let autoalloc = state.autoalloc_mut();
+    let response = match request {
+        AutoAllocRequest::List => AutoAllocResponse::Queues(autoalloc.make_queue_infos()),
+        AutoAllocRequest::Info { queue_id } => match autoalloc.get_queue(queue_id) {
+            Some(queue) => AutoAllocResponse::Allocations(queue.make_allocation_infos()),
+            None => AutoAllocResponse::InvalidQueue(queue_id),
+        },
+        AutoAllocRequest::AddQueue(params) => {
+            let queue_id = autoalloc.add_queue(params);
+            log::info!("Allocation queue {} was created", queue_id);
+            AutoAllocResponse::QueueCreated(queue_id)
+        }
+        AutoAllocRequest::RemoveQueue { queue_id } => match autoalloc.remove_queue(queue_id) {
+            Some(_) => {
+                log::info!("Allocation queue {} was removed", queue_id);
+                AutoAllocResponse::QueueRemoved(queue_id)
+            }
+            None => AutoAllocResponse::InvalidQueue(queue_id),
+        },
+    };
+    ToClientMessage::AutoAllocResponse(response)
+}
+
+/// Checks that all pools used by a job exist and that they are large enough for a single task
+fn validate_pool_usage(
+    pools: &ResourcePools,
+    pool_usage: &[(String, PoolAmount)],
+) -> Result<(), String> {
+    for (name, amount) in pool_usage {
+        match pools.size(name) {
+            None => return Err(format!("Resource pool {} does not exist", name)),
+            Some(size) if size < *amount => {
+                return Err(format!(
+                "Each task requires {} units of resource pool {}, but the pool has only {} units",
+                amount, name, size
+            ))
+            }
+            Some(_) => {}
+        }
+    }
+    Ok(())
+}
+
 async fn handle_worker_list(state_ref: &StateRef) -> ToClientMessage {
     let state = state_ref.get();
 
@@ -480,3 +1148,22 @@ async fn handle_worker_info(state_ref: &StateRef, worker_id: WorkerId) -> ToClie
 
     ToClientMessage::WorkerInfoResponse(state.get_worker(worker_id).map(|w| w.make_info()))
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::server::client::matches_pattern;
+
+    #[test]
+    fn test_matches_pattern() {
+        assert!(matches_pattern("abc", "abc"));
+        assert!(!matches_pattern("abc", "abcd"));
+        assert!(matches_pattern("pre*", "preprocess"));
+        assert!(matches_pattern("pre*", "pre"));
+        assert!(!matches_pattern("pre*", "xpre"));
+        assert!(matches_pattern("*.sh", "run.sh"));
+        assert!(matches_pattern("a*b*c", "a12b34c"));
+        assert!(!matches_pattern("a*b*c", "a12c34b"));
+        assert!(matches_pattern("*", ""));
+        assert!(!matches_pattern("ab*ba", "aba"));
+    }
+}
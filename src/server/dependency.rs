@@ -0,0 +1,106 @@
+use crate::transfer::messages::TaskDependency;
+use crate::{JobTaskId, Map, Set};
+
+/// Checks that dependencies refer only to tasks of the job and that they do not form a cycle
+pub fn validate_task_dependencies(
+    task_ids: &Set<JobTaskId>,
+    deps: &[TaskDependency],
+) -> Result<(), String> {
+    let mut graph: Map<JobTaskId, Vec<JobTaskId>> = Map::new();
+    for dep in deps {
+        if !task_ids.contains(&dep.task_id) {
+            return Err(format!("Task {} is not part of the job", dep.task_id));
+        }
+        for parent in &dep.depends_on {
+            if !task_ids.contains(parent) {
+                return Err(format!(
+                    "Task {} depends on task {} that is not part of the job",
+                    dep.task_id, parent
+                ));
+            }
+            if *parent == dep.task_id {
+                return Err(format!("Task {} depends on itself", dep.task_id));
+            }
+        }
+        graph
+            .entry(dep.task_id)
+            .or_default()
+            .extend(dep.depends_on.iter().copied());
+    }
+
+    // Kahn's algorithm, the graph contains a cycle iff some tasks cannot be ordered
+    let mut n_parents: Map<JobTaskId, usize> = graph
+        .iter()
+        .map(|(task_id, parents)| (*task_id, parents.len()))
+        .collect();
+    let mut children: Map<JobTaskId, Vec<JobTaskId>> = Map::new();
+    for (task_id, parents) in &graph {
+        for parent in parents {
+            children.entry(*parent).or_default().push(*task_id);
+        }
+    }
+    let mut stack: Vec<JobTaskId> = task_ids
+        .iter()
+        .filter(|id| n_parents.get(id).copied().unwrap_or(0) == 0)
+        .copied()
+        .collect();
+    let mut n_ordered = 0;
+    while let Some(task_id) = stack.pop() {
+        n_ordered += 1;
+        for child in children.get(&task_id).into_iter().flatten() {
+            let count = n_parents.get_mut(child).unwrap();
+            *count -= 1;
+            if *count == 0 {
+                stack.push(*child);
+            }
+        }
+    }
+    if n_ordered != task_ids.len() {
+        return Err("Task dependencies contain a cycle".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::server::dependency::validate_task_dependencies;
+    use crate::transfer::messages::TaskDependency;
+    use crate::{JobTaskId, Set};
+
+    fn dep(task_id: JobTaskId, depends_on: Vec<JobTaskId>) -> TaskDependency {
+        TaskDependency {
+            task_id,
+            depends_on,
+        }
+    }
+
+    fn ids(count: JobTaskId) -> Set<JobTaskId> {
+        (0..count).collect()
+    }
+
+    #[test]
+    fn test_valid_dependencies() {
+        assert!(validate_task_dependencies(&ids(4), &[]).is_ok());
+        assert!(validate_task_dependencies(
+            &ids(4),
+            &[dep(1, vec![0]), dep(2, vec![0]), dep(3, vec![1, 2])]
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn test_unknown_task() {
+        assert!(validate_task_dependencies(&ids(2), &[dep(5, vec![0])]).is_err());
+        assert!(validate_task_dependencies(&ids(2), &[dep(1, vec![7])]).is_err());
+    }
+
+    #[test]
+    fn test_cycle() {
+        assert!(validate_task_dependencies(&ids(2), &[dep(1, vec![1])]).is_err());
+        assert!(validate_task_dependencies(
+            &ids(4),
+            &[dep(1, vec![0, 3]), dep(2, vec![1]), dep(3, vec![2])]
+        )
+        .is_err());
+    }
+}
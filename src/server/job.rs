@@ -1,20 +1,43 @@
 use serde::{Deserialize, Serialize};
 use tako::messages::common::ProgramDefinition;
 
+use crate::client::resources::GenericResourceRequest;
+use crate::common::entries::EntryFormat;
+use crate::common::error::error;
+use crate::server::pools::{PoolUsage, ResourcePoolsRef};
 use crate::server::rpc::Backend;
+use crate::server::worker::Worker;
 use crate::stream::server::control::StreamServerControlMessage;
-use crate::transfer::messages::{JobDetail, JobInfo, JobType};
-use crate::{JobId, JobTaskCount, JobTaskId, Map, TakoTaskId, WorkerId};
+use crate::transfer::messages::{
+    JobDependency, JobDetail, JobInfo, JobType, TaskFailure, TaskGroup,
+};
+use crate::{JobId, JobTaskCount, JobTaskId, Map, Set, TakoTaskId, WorkerId};
 use bstr::BString;
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
 use std::path::PathBuf;
+use std::time::Duration;
 use tako::common::resources::ResourceRequest;
+use tako::messages::gateway::TaskDef;
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum JobTaskState {
     Waiting,
-    Running { worker: WorkerId },
-    Finished { worker: WorkerId },
-    Failed { worker: WorkerId, error: String },
+    /// Task waits for its dependencies, it was not submitted to tako yet
+    Blocked,
+    /// Task is held by the server, because its job is held by a user or it waits for other jobs
+    Held,
+    Running {
+        worker: WorkerId,
+    },
+    Finished {
+        worker: WorkerId,
+    },
+    Failed {
+        worker: WorkerId,
+        error: String,
+        failure: TaskFailure,
+    },
     Canceled,
 }
 
@@ -33,10 +56,26 @@ impl JobTaskState {
 pub struct JobTaskInfo {
     pub state: JobTaskState,
     pub task_id: JobTaskId,
+    /// Number of the current execution attempt, starting from 1
+    pub attempt: u32,
+    pub started_at: Option<DateTime<Utc>>,
+    pub ended_at: Option<DateTime<Utc>>,
+}
+
+impl JobTaskInfo {
+    fn new(task_id: JobTaskId) -> Self {
+        JobTaskInfo {
+            state: JobTaskState::Waiting,
+            task_id,
+            attempt: 1,
+            started_at: None,
+            ended_at: None,
+        }
+    }
 }
 
 pub enum JobState {
-    SingleTask(JobTaskState),
+    SingleTask(JobTaskInfo),
     ManyTasks(Map<TakoTaskId, JobTaskInfo>),
 }
 
@@ -46,6 +85,7 @@ pub struct JobTaskCounters {
     pub n_finished_tasks: JobTaskCount,
     pub n_failed_tasks: JobTaskCount,
     pub n_canceled_tasks: JobTaskCount,
+    pub n_held_tasks: JobTaskCount,
 }
 
 impl std::ops::Add<JobTaskCounters> for JobTaskCounters {
@@ -57,6 +97,7 @@ impl std::ops::Add<JobTaskCounters> for JobTaskCounters {
             n_finished_tasks: self.n_finished_tasks + rhs.n_finished_tasks,
             n_failed_tasks: self.n_failed_tasks + rhs.n_failed_tasks,
             n_canceled_tasks: self.n_canceled_tasks + rhs.n_canceled_tasks,
+            n_held_tasks: self.n_held_tasks + rhs.n_held_tasks,
         }
     }
 }
@@ -68,9 +109,16 @@ impl JobTaskCounters {
             - self.n_finished_tasks
             - self.n_failed_tasks
             - self.n_canceled_tasks
+            - self.n_held_tasks
     }
 }
 
+/// Task that is held by the server until all its dependencies are finished
+pub struct BlockedTask {
+    def: TaskDef,
+    waiting_for: Set<TakoTaskId>,
+}
+
 pub struct Job {
     pub job_id: JobId,
     pub base_task_id: TakoTaskId,
@@ -89,12 +137,58 @@ pub struct Job {
     pub pin: bool,
 
     pub entries: Option<Vec<BString>>,
+    pub entry_format: EntryFormat,
     pub priority: tako::Priority,
+
+    blocked_tasks: Map<TakoTaskId, BlockedTask>,
+    dependants: Map<TakoTaskId, Vec<TakoTaskId>>,
+
+    after: Option<JobDependency>,
+    /// Tasks that are held by the server, they were not submitted to tako yet
+    held_tasks: Map<TakoTaskId, TaskDef>,
+    /// Job waits until its job dependencies are resolved
+    waits_for_jobs: bool,
+    /// Job was held by a user, its tasks are not submitted until it is released
+    held_by_user: bool,
+
+    pub max_retries: u32,
+    pub retry_delay: Duration,
+    pub time_limit: Option<Duration>,
+    pub task_groups: Vec<TaskGroup>,
+    /// Definitions of unfinished tasks, they are needed when a task is submitted again
+    /// (e.g. when it is retried or when its job is released)
+    task_defs: Map<TakoTaskId, TaskDef>,
+    /// Failed tasks that wait for `retry_delay` before they are submitted again
+    delayed_tasks: Map<TakoTaskId, TaskDef>,
+    /// Rejected tasks that no connected worker can run,
+    /// they are submitted again when a suitable worker connects
+    parked_tasks: BTreeMap<TakoTaskId, TaskDef>,
+    /// Maximal number of tasks that are submitted to tako at the same time
+    pub max_parallel: Option<JobTaskCount>,
+    /// Waiting tasks that are kept by the server because of `max_parallel`
+    /// or because there are not enough free units of resource pools,
+    /// tasks with lower ids are submitted first
+    throttled_tasks: BTreeMap<TakoTaskId, TaskDef>,
+    /// Units of resource pools that are used by each task
+    pub pool_usage: PoolUsage,
+    pools: Option<ResourcePoolsRef>,
+    /// Submitted tasks that hold units of resource pools
+    pool_tasks: Set<TakoTaskId>,
+    /// Generic resources of workers that are used by each task
+    pub generic_resources: Vec<GenericResourceRequest>,
+    /// Labels that a worker needs to have to run tasks of the job
+    pub required_labels: Vec<String>,
+    /// Minimal remaining lifetime of a worker that starts a task of the job
+    pub time_request: Option<Duration>,
+
+    pub submitted_at: DateTime<Utc>,
+    started_at: Option<DateTime<Utc>>,
+    last_task_ended_at: Option<DateTime<Utc>>,
 }
 
 impl Job {
-    // Probably we need some structure for the future, but as it is called in exactly one place,
-    // I am disabling it now
+    // Jobs are created by `prepare_job` (and in tests), other properties of a job
+    // are set by `prepare_job` after the job is created
     #[allow(clippy::too_many_arguments)]
     pub fn new(
         job_type: JobType,
@@ -110,20 +204,12 @@ impl Job {
         job_log: Option<PathBuf>,
     ) -> Self {
         let state = match &job_type {
-            JobType::Simple => JobState::SingleTask(JobTaskState::Waiting),
-            JobType::Array(m) if m.task_count() == 1 => JobState::SingleTask(JobTaskState::Waiting),
+            JobType::Simple => JobState::SingleTask(JobTaskInfo::new(0)),
+            JobType::Array(m) if m.task_count() == 1 => JobState::SingleTask(JobTaskInfo::new(0)),
             JobType::Array(m) => JobState::ManyTasks(
                 m.iter()
                     .enumerate()
-                    .map(|(i, task_id)| {
-                        (
-                            base_task_id + i as TakoTaskId,
-                            JobTaskInfo {
-                                state: JobTaskState::Waiting,
-                                task_id,
-                            },
-                        )
-                    })
+                    .map(|(i, task_id)| (base_task_id + i as TakoTaskId, JobTaskInfo::new(task_id)))
                     .collect(),
             ),
         };
@@ -140,8 +226,33 @@ impl Job {
             pin,
             max_fails,
             entries,
+            entry_format: EntryFormat::Line,
             priority,
             log: job_log,
+            blocked_tasks: Default::default(),
+            dependants: Default::default(),
+            after: None,
+            held_tasks: Default::default(),
+            waits_for_jobs: false,
+            held_by_user: false,
+            max_retries: 0,
+            retry_delay: Duration::default(),
+            time_limit: None,
+            task_groups: Vec::new(),
+            task_defs: Default::default(),
+            delayed_tasks: Default::default(),
+            parked_tasks: Default::default(),
+            max_parallel: None,
+            throttled_tasks: Default::default(),
+            pool_usage: Vec::new(),
+            pools: None,
+            pool_tasks: Default::default(),
+            generic_resources: Vec::new(),
+            required_labels: Vec::new(),
+            time_request: None,
+            submitted_at: Utc::now(),
+            started_at: None,
+            last_task_ended_at: None,
         }
     }
 
@@ -153,12 +264,7 @@ impl Job {
             resources: self.resources.clone(),
             tasks: if include_tasks {
                 match &self.state {
-                    JobState::SingleTask(s) => {
-                        vec![JobTaskInfo {
-                            task_id: 0,
-                            state: s.clone(),
-                        }]
-                    }
+                    JobState::SingleTask(s) => vec![s.clone()],
                     JobState::ManyTasks(m) => m.values().cloned().collect(),
                 }
             } else {
@@ -166,8 +272,19 @@ impl Job {
             },
             pin: self.pin,
             entries: self.entries.clone(),
+            entry_format: self.entry_format.clone(),
             max_fails: self.max_fails,
             priority: self.priority,
+            after: self.after.clone(),
+            max_retries: self.max_retries,
+            retry_delay: self.retry_delay,
+            time_limit: self.time_limit,
+            task_groups: self.task_groups.clone(),
+            max_parallel: self.max_parallel,
+            pool_usage: self.pool_usage.clone(),
+            generic_resources: self.generic_resources.clone(),
+            required_labels: self.required_labels.clone(),
+            time_request: self.time_request,
         }
     }
 
@@ -186,6 +303,13 @@ impl Job {
             n_tasks: self.n_tasks(),
             counters: self.counters,
             resources: self.resources.clone(),
+            submitted_at: self.submitted_at,
+            started_at: self.started_at,
+            ended_at: if self.is_terminated() {
+                self.last_task_ended_at
+            } else {
+                None
+            },
         }
     }
 
@@ -198,42 +322,67 @@ impl Job {
     }
 
     pub fn is_terminated(&self) -> bool {
-        self.counters.n_running_tasks == 0 && self.counters.n_waiting_tasks(self.n_tasks()) == 0
+        self.counters.n_running_tasks == 0
+            && self.counters.n_held_tasks == 0
+            && self.counters.n_waiting_tasks(self.n_tasks()) == 0
     }
 
-    pub fn get_task_state_mut(
-        &mut self,
-        tako_task_id: TakoTaskId,
-    ) -> (JobTaskId, &mut JobTaskState) {
+    pub fn get_task_info_mut(&mut self, tako_task_id: TakoTaskId) -> &mut JobTaskInfo {
         match &mut self.state {
             JobState::SingleTask(ref mut s) => {
                 debug_assert_eq!(tako_task_id, self.base_task_id);
-                (0, s)
-            }
-            JobState::ManyTasks(m) => {
-                let state = m.get_mut(&tako_task_id).unwrap();
-                (state.task_id, &mut state.state)
+                s
             }
+            JobState::ManyTasks(m) => m.get_mut(&tako_task_id).unwrap(),
         }
     }
 
+    pub fn get_task_state_mut(
+        &mut self,
+        tako_task_id: TakoTaskId,
+    ) -> (JobTaskId, &mut JobTaskState) {
+        let info = self.get_task_info_mut(tako_task_id);
+        (info.task_id, &mut info.state)
+    }
+
     pub fn iter_task_states<'a>(
         &'a self,
     ) -> Box<dyn Iterator<Item = (TakoTaskId, JobTaskId, &'a JobTaskState)> + 'a> {
         match self.state {
-            JobState::SingleTask(ref s) => Box::new(Some((self.base_task_id, 0, s)).into_iter()),
+            JobState::SingleTask(ref s) => {
+                Box::new(Some((self.base_task_id, s.task_id, &s.state)).into_iter())
+            }
             JobState::ManyTasks(ref m) => {
                 Box::new(m.iter().map(|(k, v)| (*k, v.task_id, &v.state)))
             }
         }
     }
 
+    /// Returns true if some task of the job failed with the given exit code
+    pub fn has_task_exit_code(&self, exit_code: i32) -> bool {
+        self.iter_task_states().any(|(_, _, state)| match state {
+            JobTaskState::Failed { failure, .. } => failure.exit_code() == Some(exit_code),
+            _ => false,
+        })
+    }
+
     pub fn non_finished_task_ids(&self) -> Vec<TakoTaskId> {
         let mut result = Vec::new();
         for (tako_id, _task_id, state) in self.iter_task_states() {
             match state {
-                JobTaskState::Waiting | JobTaskState::Running { .. } => result.push(tako_id),
-                JobTaskState::Finished { .. }
+                JobTaskState::Waiting => {
+                    // Delayed, parked and throttled tasks are not in tako now
+                    if !self.delayed_tasks.contains_key(&tako_id)
+                        && !self.parked_tasks.contains_key(&tako_id)
+                        && !self.throttled_tasks.contains_key(&tako_id)
+                    {
+                        result.push(tako_id)
+                    }
+                }
+                JobTaskState::Running { .. } => result.push(tako_id),
+                JobTaskState::Blocked
+                | JobTaskState::Held
+                | JobTaskState::Finished { .. }
                 | JobTaskState::Failed { .. }
                 | JobTaskState::Canceled => { /* Do nothing */ }
             }
@@ -242,27 +391,42 @@ impl Job {
     }
 
     pub fn set_running_state(&mut self, tako_task_id: TakoTaskId, worker: WorkerId) {
-        let (_, state) = self.get_task_state_mut(tako_task_id);
+        let now = Utc::now();
+        let info = self.get_task_info_mut(tako_task_id);
 
-        if matches!(state, JobTaskState::Waiting) {
-            *state = JobTaskState::Running { worker };
+        if matches!(info.state, JobTaskState::Waiting) {
+            info.state = JobTaskState::Running { worker };
+            info.started_at = Some(now);
             self.counters.n_running_tasks += 1;
+            self.started_at.get_or_insert(now);
         }
     }
 
-    pub fn set_finished_state(&mut self, tako_task_id: TakoTaskId, backend: &Backend) {
+    /// Records the end time of a task that reached a final state
+    fn set_task_ended(&mut self, tako_task_id: TakoTaskId, time: DateTime<Utc>) {
+        self.get_task_info_mut(tako_task_id).ended_at = Some(time);
+        self.last_task_ended_at = Some(time);
+        self.task_defs.remove(&tako_task_id);
+        self.release_pool_units(tako_task_id);
+    }
+
+    pub fn set_finished_state(&mut self, tako_task_id: TakoTaskId, backend: &Backend) -> WorkerId {
         let (_, state) = self.get_task_state_mut(tako_task_id);
-        match state {
+        let worker = match state {
             JobTaskState::Running { worker } => {
-                *state = JobTaskState::Finished { worker: *worker };
+                let worker = *worker;
+                *state = JobTaskState::Finished { worker };
                 self.counters.n_running_tasks -= 1;
                 self.counters.n_finished_tasks += 1;
+                worker
             }
             _ => panic!("Invalid worker state, expected Running, got {:?}", state),
-        }
+        };
+        self.set_task_ended(tako_task_id, Utc::now());
         if self.log.is_some() && self.is_terminated() {
             backend.send_stream_control(StreamServerControlMessage::UnregisterStream(self.job_id));
         }
+        worker
     }
 
     pub fn set_waiting_state(&mut self, tako_task_id: TakoTaskId) {
@@ -272,21 +436,542 @@ impl Job {
         self.counters.n_running_tasks -= 1;
     }
 
-    pub fn set_failed_state(&mut self, tako_task_id: TakoTaskId, error: String, backend: &Backend) {
+    pub fn set_failed_state(
+        &mut self,
+        tako_task_id: TakoTaskId,
+        error: String,
+        failure: TaskFailure,
+        backend: &Backend,
+    ) -> WorkerId {
         let (_, state) = self.get_task_state_mut(tako_task_id);
 
-        match state {
+        let worker = match state {
             JobTaskState::Running { worker } => {
+                let worker = *worker;
                 *state = JobTaskState::Failed {
                     error,
-                    worker: *worker,
+                    failure,
+                    worker,
                 };
                 self.counters.n_running_tasks -= 1;
                 self.counters.n_failed_tasks += 1;
+                worker
             }
             _ => panic!("Invalid worker state, expected Running, got {:?}", state),
+        };
+        self.set_task_ended(tako_task_id, Utc::now());
+
+        if self.log.is_some() && self.is_terminated() {
+            backend.send_stream_control(StreamServerControlMessage::UnregisterStream(self.job_id));
+        }
+        worker
+    }
+
+    /// Sets a final state of a waiting task when the job is restored from a journal.
+    ///
+    /// Fails when the journal record does not match the state of the task,
+    /// the task is left untouched in that case.
+    pub fn restore_task_state(
+        &mut self,
+        tako_task_id: TakoTaskId,
+        new_state: JobTaskState,
+        time: DateTime<Utc>,
+    ) -> crate::Result<()> {
+        match new_state {
+            JobTaskState::Finished { .. }
+            | JobTaskState::Failed { .. }
+            | JobTaskState::Canceled => {}
+            _ => return error(format!("Invalid restored state {:?}", new_state)),
+        }
+        let (task_id, state) = self.get_task_state_mut(tako_task_id);
+        let was_held = match state {
+            JobTaskState::Waiting | JobTaskState::Blocked => false,
+            JobTaskState::Held => true,
+            old_state => {
+                return error(format!(
+                    "Task {} cannot be restored as {:?}, it is already {:?}",
+                    task_id, new_state, old_state
+                ))
+            }
+        };
+        *state = new_state.clone();
+        if was_held {
+            self.counters.n_held_tasks -= 1;
+        }
+        match new_state {
+            JobTaskState::Finished { .. } => self.counters.n_finished_tasks += 1,
+            JobTaskState::Failed { .. } => self.counters.n_failed_tasks += 1,
+            _ => self.counters.n_canceled_tasks += 1,
+        }
+        self.blocked_tasks.remove(&tako_task_id);
+        self.held_tasks.remove(&tako_task_id);
+        self.parked_tasks.remove(&tako_task_id);
+        self.throttled_tasks.remove(&tako_task_id);
+        self.set_task_ended(tako_task_id, time);
+        Ok(())
+    }
+
+    /// Counts a failed attempt of a task when the job is restored from a journal
+    pub fn restore_task_retry(&mut self, tako_task_id: TakoTaskId) -> crate::Result<()> {
+        let info = self.get_task_info_mut(tako_task_id);
+        if !matches!(info.state, JobTaskState::Waiting) {
+            return error(format!(
+                "Task {} cannot be retried, it is {:?}",
+                info.task_id, info.state
+            ));
+        }
+        info.attempt += 1;
+        Ok(())
+    }
+
+    /// Keeps definitions of tasks, so they can be submitted again
+    pub fn set_task_defs(&mut self, defs: &[TaskDef]) {
+        self.task_defs = defs.iter().map(|def| (def.id, def.clone())).collect();
+    }
+
+    pub fn set_retries(&mut self, max_retries: u32, retry_delay: Duration) {
+        self.max_retries = max_retries;
+        self.retry_delay = retry_delay;
+    }
+
+    /// Moves a failed task back into the waiting state when it has some retries left.
+    /// Returns a definition of the task that should be submitted again.
+    pub fn retry_task(&mut self, tako_task_id: TakoTaskId) -> Option<TaskDef> {
+        let max_retries = self.max_retries;
+        let info = self.get_task_info_mut(tako_task_id);
+        if info.attempt > max_retries {
+            return None;
+        }
+        assert!(matches!(info.state, JobTaskState::Running { .. }));
+        info.state = JobTaskState::Waiting;
+        info.attempt += 1;
+        self.counters.n_running_tasks -= 1;
+        self.release_pool_units(tako_task_id);
+        Some(self.task_defs[&tako_task_id].clone())
+    }
+
+    /// Returns a task that was rejected by its worker into the waiting state,
+    /// unlike `retry_task`, a rejected task does not count as a new attempt
+    /// and it does not count as a start of the job
+    pub fn reject_task(&mut self, tako_task_id: TakoTaskId) -> TaskDef {
+        let info = self.get_task_info_mut(tako_task_id);
+        assert!(matches!(info.state, JobTaskState::Running { .. }));
+        info.state = JobTaskState::Waiting;
+        let started_at = info.started_at.take();
+        self.counters.n_running_tasks -= 1;
+        if started_at.is_some() && self.started_at == started_at {
+            self.started_at = self.first_task_start();
+        }
+        self.release_pool_units(tako_task_id);
+        self.task_defs[&tako_task_id].clone()
+    }
+
+    fn first_task_start(&self) -> Option<DateTime<Utc>> {
+        match &self.state {
+            JobState::SingleTask(s) => s.started_at,
+            JobState::ManyTasks(m) => m.values().filter_map(|info| info.started_at).min(),
+        }
+    }
+
+    /// Keeps a rejected task in the server until `take_parked_tasks` is called
+    /// with a worker that can run it
+    pub fn park_task(&mut self, def: TaskDef) {
+        self.parked_tasks.insert(def.id, def);
+    }
+
+    /// Returns parked tasks that should be submitted to tako, because the given worker
+    /// can run them
+    pub fn take_parked_tasks(&mut self, worker: &Worker, now: DateTime<Utc>) -> Vec<TaskDef> {
+        if self.parked_tasks.is_empty() || !worker.can_run_tasks_of(self, now) {
+            return Vec::new();
+        }
+        let defs = std::mem::take(&mut self.parked_tasks)
+            .into_iter()
+            .map(|(_, def)| def)
+            .collect();
+        self.submit_or_hold(defs)
+    }
+
+    /// Holds a retried task until it is taken by `take_delayed_task`
+    pub fn delay_task(&mut self, def: TaskDef) {
+        self.delayed_tasks.insert(def.id, def);
+    }
+
+    /// Returns a delayed task if it was not canceled in the meantime
+    pub fn take_delayed_task(&mut self, tako_task_id: TakoTaskId) -> Option<TaskDef> {
+        self.delayed_tasks.remove(&tako_task_id)
+    }
+
+    /// Holds a task until all tasks in `deps` are finished
+    pub fn add_blocked_task(&mut self, def: TaskDef, deps: Set<TakoTaskId>) {
+        let tako_task_id = def.id;
+        let (_, state) = self.get_task_state_mut(tako_task_id);
+        assert!(matches!(state, JobTaskState::Waiting));
+        *state = JobTaskState::Blocked;
+        for dep in &deps {
+            self.dependants.entry(*dep).or_default().push(tako_task_id);
+        }
+        self.blocked_tasks.insert(
+            tako_task_id,
+            BlockedTask {
+                def,
+                waiting_for: deps,
+            },
+        );
+    }
+
+    /// Removes a finished task from dependencies of blocked tasks.
+    /// Returns tasks that are no longer blocked and should be submitted to tako.
+    pub fn resolve_dependants(&mut self, tako_task_id: TakoTaskId) -> Vec<TaskDef> {
+        let mut ready = Vec::new();
+        for task_id in self.dependants.remove(&tako_task_id).unwrap_or_default() {
+            let is_ready = match self.blocked_tasks.get_mut(&task_id) {
+                Some(blocked) => {
+                    blocked.waiting_for.remove(&tako_task_id);
+                    blocked.waiting_for.is_empty()
+                }
+                None => false,
+            };
+            if is_ready {
+                let blocked = self.blocked_tasks.remove(&task_id).unwrap();
+                let (_, state) = self.get_task_state_mut(task_id);
+                *state = JobTaskState::Waiting;
+                ready.push(blocked.def);
+            }
+        }
+        ready
+    }
+
+    /// Cancels all blocked tasks that (transitively) depend on a given task.
+    /// Returns ids of the canceled tasks.
+    pub fn cancel_dependants(&mut self, tako_task_id: TakoTaskId) -> Vec<(TakoTaskId, JobTaskId)> {
+        let mut canceled = Vec::new();
+        let mut stack = vec![tako_task_id];
+        while let Some(id) = stack.pop() {
+            for tako_id in self.dependants.remove(&id).unwrap_or_default() {
+                if self.blocked_tasks.remove(&tako_id).is_some() {
+                    let (task_id, state) = self.get_task_state_mut(tako_id);
+                    *state = JobTaskState::Canceled;
+                    self.counters.n_canceled_tasks += 1;
+                    self.set_task_ended(tako_id, Utc::now());
+                    canceled.push((tako_id, task_id));
+                    stack.push(tako_id);
+                }
+            }
+        }
+        canceled
+    }
+
+    fn add_held_task(&mut self, def: TaskDef) {
+        let (_, state) = self.get_task_state_mut(def.id);
+        assert!(matches!(state, JobTaskState::Waiting));
+        *state = JobTaskState::Held;
+        self.counters.n_held_tasks += 1;
+        self.held_tasks.insert(def.id, def);
+    }
+
+    /// Moves all held tasks into the waiting state, returns tasks that should be submitted to tako
+    fn take_held_tasks(&mut self) -> Vec<TaskDef> {
+        let defs: Vec<TaskDef> = self.held_tasks.drain().map(|(_, def)| def).collect();
+        for def in &defs {
+            let (_, state) = self.get_task_state_mut(def.id);
+            *state = JobTaskState::Waiting;
+        }
+        self.counters.n_held_tasks -= defs.len() as JobTaskCount;
+        defs
+    }
+
+    /// Holds tasks until job dependencies are resolved
+    pub fn hold_tasks(&mut self, after: JobDependency, defs: Vec<TaskDef>) {
+        self.after = Some(after);
+        self.waits_for_jobs = true;
+        for def in defs {
+            self.add_held_task(def);
+        }
+    }
+
+    pub fn job_dependency(&self) -> Option<&JobDependency> {
+        self.after.as_ref()
+    }
+
+    #[inline]
+    pub fn waits_for_jobs(&self) -> bool {
+        self.waits_for_jobs
+    }
+
+    /// Marks job dependencies as resolved, returns held tasks that should be submitted to tako
+    pub fn release_held_tasks(&mut self) -> Vec<TaskDef> {
+        self.waits_for_jobs = false;
+        if self.held_by_user {
+            Vec::new()
+        } else {
+            let defs = self.take_held_tasks();
+            self.submit_or_hold(defs)
+        }
+    }
+
+    #[inline]
+    pub fn is_held_by_user(&self) -> bool {
+        self.held_by_user
+    }
+
+    /// Returns ids of waiting tasks that were submitted to tako
+    fn submitted_waiting_task_ids(&self) -> Vec<TakoTaskId> {
+        self.iter_task_states()
+            .filter(|(tako_id, _, state)| {
+                matches!(state, JobTaskState::Waiting)
+                    && !self.delayed_tasks.contains_key(tako_id)
+                    && !self.parked_tasks.contains_key(tako_id)
+                    && !self.throttled_tasks.contains_key(tako_id)
+            })
+            .map(|(tako_id, _, _)| tako_id)
+            .collect()
+    }
+
+    /// Moves tasks that were taken back from tako into the waiting state,
+    /// returns their definitions
+    fn take_canceled_tasks(&mut self, tako_task_ids: &[TakoTaskId]) -> Vec<TaskDef> {
+        let mut defs = Vec::new();
+        for tako_task_id in tako_task_ids {
+            let info = self.get_task_info_mut(*tako_task_id);
+            match info.state {
+                JobTaskState::Waiting => {}
+                JobTaskState::Running { .. } => {
+                    // The task was started before tako received the cancel request
+                    // (tako cancels also running tasks), or it was requeued by a user.
+                    // Its run was killed, so it waits again without losing an attempt.
+                    log::debug!(
+                        "Task {} was running when it was canceled in tako",
+                        info.task_id
+                    );
+                    info.state = JobTaskState::Waiting;
+                    info.started_at = None;
+                    self.counters.n_running_tasks -= 1;
+                }
+                _ => continue,
+            }
+            self.release_pool_units(*tako_task_id);
+            defs.push(self.task_defs[tako_task_id].clone());
+        }
+        defs
+    }
+
+    /// Marks the job as held by a user.
+    /// Returns ids of waiting tasks that have to be canceled in tako and passed
+    /// to `hold_canceled_tasks` afterwards.
+    pub fn hold(&mut self) -> Vec<TakoTaskId> {
+        self.held_by_user = true;
+        for (_, def) in std::mem::take(&mut self.throttled_tasks) {
+            self.add_held_task(def);
+        }
+        self.submitted_waiting_task_ids()
+    }
+
+    /// Holds tasks that were canceled in tako because of `hold`.
+    /// Returns tasks that should be submitted back when the job was released in the meantime.
+    pub fn hold_canceled_tasks(&mut self, tako_task_ids: &[TakoTaskId]) -> Vec<TaskDef> {
+        let defs = self.take_canceled_tasks(tako_task_ids);
+        self.submit_or_hold(defs)
+    }
+
+    /// Changes the priority of the job and of all its unfinished tasks.
+    /// Priorities of tasks are shifted by the same amount as the priority of the job,
+    /// so task groups of a job file keep their relative priorities.
+    /// Returns ids of waiting tasks that have to be canceled in tako and passed
+    /// to `resubmit_canceled_tasks` afterwards, because tako cannot change
+    /// priorities of submitted tasks.
+    pub fn set_priority(&mut self, priority: tako::Priority) -> Vec<TakoTaskId> {
+        if priority == self.priority {
+            return Vec::new();
+        }
+        let old_priority = self.priority;
+        self.priority = priority;
+        let defs = self
+            .task_defs
+            .values_mut()
+            .chain(self.held_tasks.values_mut())
+            .chain(self.delayed_tasks.values_mut())
+            .chain(self.parked_tasks.values_mut())
+            .chain(self.throttled_tasks.values_mut())
+            .chain(
+                self.blocked_tasks
+                    .values_mut()
+                    .map(|blocked| &mut blocked.def),
+            );
+        for def in defs {
+            def.priority = priority.saturating_add(def.priority.saturating_sub(old_priority));
         }
+        self.submitted_waiting_task_ids()
+    }
+
+    /// Current priority of a task, it may differ from the priority of the job
+    /// when the task belongs to a task group
+    pub fn task_priority(&self, tako_task_id: TakoTaskId) -> tako::Priority {
+        self.task_defs
+            .get(&tako_task_id)
+            .map_or(self.priority, |def| def.priority)
+    }
+
+    /// Returns tasks that were canceled in tako (e.g. because of `set_priority`)
+    /// and that should be submitted again
+    pub fn resubmit_canceled_tasks(&mut self, tako_task_ids: &[TakoTaskId]) -> Vec<TaskDef> {
+        let defs = self.take_canceled_tasks(tako_task_ids);
+        self.submit_or_hold(defs)
+    }
+
+    /// Releases a job held by a user, returns held tasks that should be submitted to tako
+    pub fn release(&mut self) -> Vec<TaskDef> {
+        self.held_by_user = false;
+        if self.waits_for_jobs {
+            Vec::new()
+        } else {
+            let defs = self.take_held_tasks();
+            self.submit_or_hold(defs)
+        }
+    }
+
+    /// Returns tasks that can be submitted to tako. Tasks of a job held by a user
+    /// are kept by the server until the job is released and tasks over `max_parallel`
+    /// are kept until some submitted tasks are finished.
+    pub fn submit_or_hold(&mut self, defs: Vec<TaskDef>) -> Vec<TaskDef> {
+        if self.held_by_user {
+            for def in defs {
+                self.add_held_task(def);
+            }
+            Vec::new()
+        } else if self.max_parallel.is_some() || self.pools.is_some() {
+            for def in defs {
+                // Units may be taken when the task was already submitted (e.g. in a journal replay)
+                self.release_pool_units(def.id);
+                self.throttled_tasks.insert(def.id, def);
+            }
+            self.take_throttled_tasks()
+        } else {
+            defs
+        }
+    }
+
+    /// Number of tasks that are waiting or running in tako
+    fn n_submitted_tasks(&self) -> JobTaskCount {
+        // Blocked, delayed, parked and throttled tasks are counted as waiting tasks
+        self.counters.n_running_tasks + self.counters.n_waiting_tasks(self.n_tasks())
+            - self.blocked_tasks.len() as JobTaskCount
+            - self.delayed_tasks.len() as JobTaskCount
+            - self.parked_tasks.len() as JobTaskCount
+            - self.throttled_tasks.len() as JobTaskCount
+    }
+
+    /// Returns throttled tasks that can be submitted to tako without exceeding `max_parallel`
+    /// and sizes of resource pools, it should be called whenever a submitted task leaves tako
+    pub fn take_throttled_tasks(&mut self) -> Vec<TaskDef> {
+        if self.held_by_user || self.throttled_tasks.is_empty() {
+            return Vec::new();
+        }
+        let free = match self.max_parallel {
+            Some(max_parallel) => max_parallel.saturating_sub(self.n_submitted_tasks()),
+            None => JobTaskCount::MAX,
+        };
+        let mut tako_task_ids: Vec<TakoTaskId> = Vec::new();
+        for tako_task_id in self.throttled_tasks.keys().take(free as usize) {
+            if let Some(pools) = &self.pools {
+                if !pools.get_mut().allocate(&self.pool_usage) {
+                    break;
+                }
+                self.pool_tasks.insert(*tako_task_id);
+            }
+            tako_task_ids.push(*tako_task_id);
+        }
+        tako_task_ids
+            .into_iter()
+            .map(|tako_task_id| self.throttled_tasks.remove(&tako_task_id).unwrap())
+            .collect()
+    }
+
+    /// Tasks of the job use units of resource pools
+    pub fn set_pool_usage(&mut self, pool_usage: PoolUsage, pools: ResourcePoolsRef) {
+        if !pool_usage.is_empty() {
+            self.pool_usage = pool_usage;
+            self.pools = Some(pools);
+        }
+    }
+
+    #[inline]
+    pub fn uses_pools(&self) -> bool {
+        self.pools.is_some()
+    }
+
+    /// Returns units of resource pools taken by a task that left tako
+    fn release_pool_units(&mut self, tako_task_id: TakoTaskId) {
+        if self.pool_tasks.remove(&tako_task_id) {
+            self.pools
+                .as_ref()
+                .unwrap()
+                .get_mut()
+                .release(&self.pool_usage);
+        }
+    }
+
+    /// Cancels all tasks that were not submitted to tako
+    /// (blocked, held, delayed, parked and throttled tasks), returns their ids
+    pub fn cancel_unsubmitted_tasks(&mut self) -> Vec<(TakoTaskId, JobTaskId)> {
+        let unsubmitted: Vec<TakoTaskId> = self
+            .blocked_tasks
+            .keys()
+            .chain(self.held_tasks.keys())
+            .chain(self.delayed_tasks.keys())
+            .chain(self.parked_tasks.keys())
+            .chain(self.throttled_tasks.keys())
+            .copied()
+            .collect();
+        let canceled = unsubmitted
+            .into_iter()
+            .filter_map(|tako_task_id| {
+                self.cancel_unsubmitted_task(tako_task_id)
+                    .map(|task_id| (tako_task_id, task_id))
+            })
+            .collect();
+        self.dependants.clear();
+        self.waits_for_jobs = false;
+        canceled
+    }
+
+    /// Cancels a task if it was not submitted to tako
+    /// (a blocked, held, delayed, parked or throttled task), returns its id
+    pub fn cancel_unsubmitted_task(&mut self, tako_task_id: TakoTaskId) -> Option<JobTaskId> {
+        if self.blocked_tasks.remove(&tako_task_id).is_none()
+            && self.held_tasks.remove(&tako_task_id).is_none()
+            && self.delayed_tasks.remove(&tako_task_id).is_none()
+            && self.parked_tasks.remove(&tako_task_id).is_none()
+            && self.throttled_tasks.remove(&tako_task_id).is_none()
+        {
+            return None;
+        }
+        let (task_id, state) = self.get_task_state_mut(tako_task_id);
+        if let JobTaskState::Held = std::mem::replace(state, JobTaskState::Canceled) {
+            self.counters.n_held_tasks -= 1;
+        }
+        self.counters.n_canceled_tasks += 1;
+        self.set_task_ended(tako_task_id, Utc::now());
+        Some(task_id)
+    }
+
+    /// Returns tako ids of the given tasks of the job and ids of tasks that are not part of the job
+    pub fn find_tako_task_ids(
+        &self,
+        task_ids: &Set<JobTaskId>,
+    ) -> (Vec<(TakoTaskId, JobTaskId)>, Vec<JobTaskId>) {
+        let found: Vec<(TakoTaskId, JobTaskId)> = self
+            .iter_task_states()
+            .filter(|(_, task_id, _)| task_ids.contains(task_id))
+            .map(|(tako_id, task_id, _)| (tako_id, task_id))
+            .collect();
+        let found_ids: Set<JobTaskId> = found.iter().map(|(_, task_id)| *task_id).collect();
+        let mut missing: Vec<JobTaskId> = task_ids.difference(&found_ids).copied().collect();
+        missing.sort_unstable();
+        (found, missing)
+    }
 
+    pub fn unregister_stream_if_terminated(&self, backend: &Backend) {
         if self.log.is_some() && self.is_terminated() {
             backend.send_stream_control(StreamServerControlMessage::UnregisterStream(self.job_id));
         }
@@ -303,6 +988,7 @@ impl Job {
             self.counters.n_running_tasks -= 1;
         }
         self.counters.n_canceled_tasks += 1;
+        self.set_task_ended(tako_task_id, Utc::now());
 
         if self.log.is_some() && self.is_terminated() {
             backend.send_stream_control(StreamServerControlMessage::UnregisterStream(self.job_id));
@@ -0,0 +1,558 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use byteorder::{ReadBytesExt, WriteBytesExt};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tako::messages::gateway::{FromGatewayMessage, NewTasksMessage, TaskDef, ToGatewayMessage};
+use tokio::sync::oneshot;
+
+use crate::common::error::error;
+use crate::server::client::prepare_job;
+use crate::server::job::JobTaskState;
+use crate::server::pools::PoolAmount;
+use crate::server::rpc::Backend;
+use crate::server::state::{State, StateRef};
+use crate::stream::server::control::StreamServerControlMessage;
+use crate::transfer::messages::{decode_task_failure, SubmitRequest};
+use crate::{JobId, JobTaskCount, Map, Set, TakoTaskId, WorkerId};
+
+pub const HQ_JOURNAL_HEADER: &[u8] = b"HQ:jrn";
+pub const HQ_JOURNAL_VERSION: u32 = 0;
+
+/// How often are buffered journal events written to the disk
+const JOURNAL_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Events that are needed to rebuild the server state after a restart.
+///
+/// Transient states (e.g. running tasks) are not recorded, unfinished tasks
+/// are simply submitted again when the state is restored.
+#[derive(Serialize, Deserialize, Debug)]
+pub enum JournalEvent {
+    JobSubmitted {
+        job_id: JobId,
+        base_task_id: TakoTaskId,
+        request: SubmitRequest,
+    },
+    TaskFinished {
+        task_id: TakoTaskId,
+        worker: WorkerId,
+    },
+    TaskFailed {
+        task_id: TakoTaskId,
+        worker: WorkerId,
+        /// Error message as it was received from tako
+        error: String,
+    },
+    TaskCanceled {
+        task_id: TakoTaskId,
+    },
+    /// Task failed, but it was submitted again because it has some retries left
+    TaskRetried {
+        task_id: TakoTaskId,
+    },
+    /// Job was held by a user
+    JobHeld {
+        job_id: JobId,
+    },
+    /// Job held by a user was released
+    JobReleased {
+        job_id: JobId,
+    },
+    /// Properties of a job were changed by a user
+    JobUpdated {
+        job_id: JobId,
+        priority: Option<tako::Priority>,
+        max_fails: Option<JobTaskCount>,
+    },
+    /// Resource pool was created or its size was changed
+    ResourcePoolChanged {
+        name: String,
+        size: PoolAmount,
+    },
+}
+
+/// Event together with the time when it was recorded
+pub struct JournalRecord {
+    pub time: DateTime<Utc>,
+    pub event: JournalEvent,
+}
+
+/// Append-only file with server events.
+///
+/// Each event is stored as a block: u32 (big endian) length followed by the serialized
+/// pair (time, event). Events are buffered, they hit the disk when the journal is flushed.
+pub struct Journal {
+    file: BufWriter<File>,
+    /// Some events were written since the last flush
+    dirty: bool,
+}
+
+impl Journal {
+    pub fn create(path: &Path) -> crate::Result<Self> {
+        let mut file = BufWriter::new(OpenOptions::new().write(true).create_new(true).open(path)?);
+        file.write_all(HQ_JOURNAL_HEADER)?;
+        file.write_u32::<byteorder::BigEndian>(HQ_JOURNAL_VERSION)?;
+        file.write_u64::<byteorder::BigEndian>(0)?; // Reserved bytes
+        file.flush()?;
+        Ok(Journal { file, dirty: false })
+    }
+
+    pub fn write(&mut self, time: DateTime<Utc>, event: &JournalEvent) -> crate::Result<()> {
+        let data = tako::transfer::auth::serialize(&(time, event))?;
+        self.file
+            .write_u32::<byteorder::BigEndian>(data.len() as u32)?;
+        self.file.write_all(&data)?;
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Writes buffered events to the disk, events that were not flushed are lost in a crash
+    pub fn flush(&mut self) -> crate::Result<()> {
+        if self.dirty {
+            self.file.flush()?;
+            self.file.get_ref().sync_data()?;
+            self.dirty = false;
+        }
+        Ok(())
+    }
+}
+
+/// Periodically writes buffered events of the server journal to the disk
+pub async fn flush_journal_process(state_ref: StateRef) {
+    let mut interval = tokio::time::interval(JOURNAL_FLUSH_INTERVAL);
+    loop {
+        interval.tick().await;
+        state_ref.get_mut().flush_journal();
+    }
+}
+
+/// Reads all records from a journal file.
+///
+/// An incomplete event at the end of the file (e.g. when the server crashed during writing)
+/// is ignored.
+pub fn read_journal(path: &Path) -> anyhow::Result<Vec<JournalRecord>> {
+    let mut file = BufReader::new(File::open(path)?);
+
+    let mut header = [0u8; 6];
+    file.read_exact(&mut header)?;
+    if header != HQ_JOURNAL_HEADER {
+        anyhow::bail!("Invalid journal format");
+    }
+    let version = file.read_u32::<byteorder::BigEndian>()?;
+    if version != HQ_JOURNAL_VERSION {
+        anyhow::bail!("Invalid journal version: {}", version);
+    }
+    let _ = file.read_u64::<byteorder::BigEndian>()?; // Reserved bytes
+
+    let mut events = Vec::new();
+    loop {
+        let size = match file.read_u32::<byteorder::BigEndian>() {
+            Ok(size) => size,
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
+        };
+        let mut buffer = vec![0; size as usize];
+        match file.read_exact(&mut buffer) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => {
+                log::warn!("Journal ends with an incomplete event, ignoring it");
+                break;
+            }
+            Err(e) => return Err(e.into()),
+        }
+        let (time, event) = tako::transfer::auth::deserialize(&buffer)?;
+        events.push(JournalRecord { time, event });
+    }
+    Ok(events)
+}
+
+/// Part of a restored state that has to be passed to tako and the stream server
+pub struct RestoredState {
+    tasks: Vec<TaskDef>,
+    streams: Vec<(JobId, PathBuf)>,
+}
+
+impl RestoredState {
+    pub fn n_tasks(&self) -> usize {
+        self.tasks.len()
+    }
+
+    pub async fn submit(self, backend: &Backend) -> crate::Result<()> {
+        for (job_id, path) in self.streams {
+            log::info!(
+                "Log of job {} is restored, new output is appended to {}",
+                job_id,
+                path.display()
+            );
+            let (sender, receiver) = oneshot::channel();
+            backend.send_stream_control(StreamServerControlMessage::RegisterStream {
+                job_id,
+                path,
+                append: true,
+                response: sender,
+            });
+            assert!(receiver.await.is_ok());
+        }
+
+        if self.tasks.is_empty() {
+            return Ok(());
+        }
+        match backend
+            .send_tako_message(FromGatewayMessage::NewTasks(NewTasksMessage {
+                tasks: self.tasks,
+            }))
+            .await?
+        {
+            ToGatewayMessage::NewTasksResponse(_) => Ok(()),
+            ToGatewayMessage::Error(e) => error(format!("Cannot restore tasks: {}", e.message)),
+            _ => panic!("Invalid response"),
+        }
+    }
+}
+
+/// Sets a final state of a task and resolves its dependants,
+/// returns tasks that became ready.
+///
+/// Dependants of failed or canceled tasks are not canceled here,
+/// their cancellation is stored in the journal as separate events.
+fn restore_task_state(
+    state: &mut State,
+    task_id: TakoTaskId,
+    task_state: JobTaskState,
+    time: DateTime<Utc>,
+) -> Vec<TaskDef> {
+    let job = match state.get_job_mut_by_tako_task_id(task_id) {
+        Some(job) => job,
+        None => {
+            log::warn!("Journal contains an unknown task {}", task_id);
+            return Vec::new();
+        }
+    };
+    let is_finished = matches!(task_state, JobTaskState::Finished { .. });
+    if let Err(e) = job.restore_task_state(task_id, task_state, time) {
+        log::warn!("Journal record of task {} is ignored: {}", task_id, e);
+        return Vec::new();
+    }
+    if is_finished {
+        job.resolve_dependants(task_id)
+    } else {
+        Vec::new()
+    }
+}
+
+/// Rebuilds jobs in the state from journal records.
+///
+/// Records are also written into the journal of the state, so the new journal
+/// contains the whole history.
+pub fn restore_state(state: &mut State, records: Vec<JournalRecord>) -> RestoredState {
+    let mut task_defs: Vec<TaskDef> = Vec::new();
+    let mut log_paths: Map<JobId, PathBuf> = Map::new();
+    let mut reprioritized_jobs: Set<JobId> = Set::new();
+
+    for JournalRecord { time, event } in records {
+        match &event {
+            JournalEvent::JobSubmitted {
+                job_id,
+                base_task_id,
+                request,
+            } => {
+                if let Some(log) = &request.log {
+                    log_paths.insert(*job_id, request.submit_dir.join(log));
+                }
+                let pools = state.resource_pools().clone();
+                let (mut job, tasks) = prepare_job(*job_id, *base_task_id, request.clone(), &pools);
+                job.submitted_at = time;
+                state.restore_job(job);
+                task_defs.extend(tasks);
+            }
+            JournalEvent::TaskFinished { task_id, worker } => {
+                task_defs.extend(restore_task_state(
+                    state,
+                    *task_id,
+                    JobTaskState::Finished { worker: *worker },
+                    time,
+                ));
+            }
+            JournalEvent::TaskFailed {
+                task_id,
+                worker,
+                error,
+            } => {
+                let (error, failure) = decode_task_failure(error);
+                restore_task_state(
+                    state,
+                    *task_id,
+                    JobTaskState::Failed {
+                        worker: *worker,
+                        error,
+                        failure,
+                    },
+                    time,
+                );
+            }
+            JournalEvent::TaskCanceled { task_id } => {
+                restore_task_state(state, *task_id, JobTaskState::Canceled, time);
+            }
+            JournalEvent::TaskRetried { task_id } => {
+                match state.get_job_mut_by_tako_task_id(*task_id) {
+                    Some(job) => {
+                        if let Err(e) = job.restore_task_retry(*task_id) {
+                            log::warn!("Journal record of task {} is ignored: {}", task_id, e);
+                        }
+                    }
+                    None => log::warn!("Journal contains an unknown task {}", task_id),
+                }
+            }
+            JournalEvent::JobHeld { job_id } => match state.get_job_mut(*job_id) {
+                // Waiting tasks are held when the whole history is known
+                Some(job) => {
+                    job.hold();
+                }
+                None => log::warn!("Journal contains an unknown job {}", job_id),
+            },
+            JournalEvent::JobReleased { job_id } => match state.get_job_mut(*job_id) {
+                Some(job) => task_defs.extend(job.release()),
+                None => log::warn!("Journal contains an unknown job {}", job_id),
+            },
+            JournalEvent::JobUpdated {
+                job_id,
+                priority,
+                max_fails,
+            } => match state.get_job_mut(*job_id) {
+                Some(job) => {
+                    if let Some(priority) = priority {
+                        job.set_priority(*priority);
+                        reprioritized_jobs.insert(*job_id);
+                    }
+                    if max_fails.is_some() {
+                        job.max_fails = *max_fails;
+                    }
+                }
+                None => log::warn!("Journal contains an unknown job {}", job_id),
+            },
+            JournalEvent::ResourcePoolChanged { name, size } => {
+                state.resource_pools().get_mut().set_size(name, *size);
+            }
+        }
+        state.write_journal_at(time, event);
+    }
+
+    // Held jobs are released (or canceled) only after the whole history is known
+    let (ready, _) = state.resolve_job_dependencies();
+    task_defs.extend(ready);
+
+    let waiting: Set<TakoTaskId> = state
+        .jobs()
+        .flat_map(|job| job.non_finished_task_ids())
+        .collect();
+    let mut job_tasks: Map<JobId, Vec<TaskDef>> = Map::new();
+    for mut t in task_defs.into_iter().filter(|t| waiting.contains(&t.id)) {
+        let job = state.get_job_mut_by_tako_task_id(t.id).unwrap();
+        // Priority of the job may be changed after its submission
+        if reprioritized_jobs.contains(&job.job_id) {
+            t.priority = job.task_priority(t.id);
+        }
+        job_tasks.entry(job.job_id).or_default().push(t);
+    }
+    let job_ids: Vec<JobId> = state.jobs().map(|job| job.job_id).collect();
+    let mut tasks = Vec::new();
+    for job_id in job_ids {
+        // Throttled tasks are submitted even when the job has no other waiting tasks
+        let defs = job_tasks.remove(&job_id).unwrap_or_default();
+        tasks.extend(state.get_job_mut(job_id).unwrap().submit_or_hold(defs));
+    }
+    let streams = log_paths
+        .into_iter()
+        .filter(|(job_id, _)| !state.get_job(*job_id).unwrap().is_terminated())
+        .collect();
+    RestoredState { tasks, streams }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use tempdir::TempDir;
+
+    use chrono::Utc;
+    use tako::common::resources::ResourceRequest;
+    use tako::messages::common::{ProgramDefinition, StdioDef};
+
+    use crate::common::arraydef::ArrayDef;
+    use crate::server::job::JobTaskState;
+    use crate::server::journal::{read_journal, restore_state, Journal, JournalEvent};
+    use crate::server::state::StateRef;
+    use crate::transfer::messages::{JobType, SubmitRequest, TaskDependency};
+
+    fn dependent_tasks_request() -> SubmitRequest {
+        SubmitRequest {
+            job_type: JobType::Array(ArrayDef::simple_range(0, 3)),
+            name: "deps".to_string(),
+            max_fails: None,
+            spec: ProgramDefinition {
+                args: vec![],
+                env: Default::default(),
+                stdout: StdioDef::Null,
+                stderr: StdioDef::Null,
+                cwd: None,
+            },
+            resources: ResourceRequest::default(),
+            pin: false,
+            entries: None,
+            entry_format: Default::default(),
+            submit_dir: Default::default(),
+            priority: 0,
+            log: None,
+            task_deps: vec![
+                TaskDependency {
+                    task_id: 1,
+                    depends_on: vec![0],
+                },
+                TaskDependency {
+                    task_id: 2,
+                    depends_on: vec![1],
+                },
+            ],
+            after: None,
+            max_retries: 0,
+            retry_delay: Default::default(),
+            time_limit: None,
+            task_groups: Vec::new(),
+            max_parallel: None,
+            pool_usage: Vec::new(),
+            generic_resources: Vec::new(),
+            required_labels: Vec::new(),
+            time_request: None,
+        }
+    }
+
+    #[test]
+    fn test_journal_roundtrip() {
+        let path = TempDir::new("hq").unwrap().into_path().join("journal");
+        let mut journal = Journal::create(&path).unwrap();
+        let time = Utc::now();
+        journal
+            .write(
+                time,
+                &JournalEvent::TaskFinished {
+                    task_id: 10,
+                    worker: 2,
+                },
+            )
+            .unwrap();
+        journal
+            .write(time, &JournalEvent::TaskCanceled { task_id: 11 })
+            .unwrap();
+        drop(journal);
+
+        let events = read_journal(&path).unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].time, time);
+        assert!(matches!(
+            events[0].event,
+            JournalEvent::TaskFinished {
+                task_id: 10,
+                worker: 2
+            }
+        ));
+        assert!(matches!(
+            events[1].event,
+            JournalEvent::TaskCanceled { task_id: 11 }
+        ));
+    }
+
+    #[test]
+    fn test_journal_incomplete_event() {
+        let path = TempDir::new("hq").unwrap().into_path().join("journal");
+        let mut journal = Journal::create(&path).unwrap();
+        journal
+            .write(Utc::now(), &JournalEvent::TaskCanceled { task_id: 1 })
+            .unwrap();
+        drop(journal);
+
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        file.write_all(&[0, 0, 0, 100, 1, 2]).unwrap();
+
+        let events = read_journal(&path).unwrap();
+        assert_eq!(events.len(), 1);
+    }
+
+    #[test]
+    fn test_restore_canceled_dependants() {
+        let path = TempDir::new("hq").unwrap().into_path().join("journal");
+        let mut journal = Journal::create(&path).unwrap();
+        let time = Utc::now();
+        // Events written by the server when task 0 is canceled, tasks 1 and 2 depend on it
+        let events = vec![
+            JournalEvent::JobSubmitted {
+                job_id: 1,
+                base_task_id: 100,
+                request: dependent_tasks_request(),
+            },
+            JournalEvent::TaskCanceled { task_id: 100 },
+            JournalEvent::TaskCanceled { task_id: 101 },
+            JournalEvent::TaskCanceled { task_id: 102 },
+        ];
+        for event in &events {
+            journal.write(time, event).unwrap();
+        }
+        drop(journal);
+
+        let state_ref = StateRef::new();
+        let mut state = state_ref.get_mut();
+        let restored = restore_state(&mut state, read_journal(&path).unwrap());
+        assert_eq!(restored.n_tasks(), 0);
+
+        let job = state.get_job_mut(1).unwrap();
+        assert_eq!(job.counters.n_canceled_tasks, 3);
+        assert!(job.is_terminated());
+        for task_id in 100..103 {
+            assert!(matches!(
+                job.get_task_state_mut(task_id).1,
+                JobTaskState::Canceled
+            ));
+        }
+    }
+
+    #[test]
+    fn test_restore_ignores_invalid_records() {
+        let path = TempDir::new("hq").unwrap().into_path().join("journal");
+        let mut journal = Journal::create(&path).unwrap();
+        let time = Utc::now();
+        let events = vec![
+            JournalEvent::JobSubmitted {
+                job_id: 1,
+                base_task_id: 100,
+                request: dependent_tasks_request(),
+            },
+            JournalEvent::TaskFinished {
+                task_id: 100,
+                worker: 1,
+            },
+            JournalEvent::TaskCanceled { task_id: 100 },
+            JournalEvent::TaskRetried { task_id: 100 },
+        ];
+        for event in &events {
+            journal.write(time, event).unwrap();
+        }
+        drop(journal);
+
+        let state_ref = StateRef::new();
+        let mut state = state_ref.get_mut();
+        restore_state(&mut state, read_journal(&path).unwrap());
+
+        let job = state.get_job_mut(1).unwrap();
+        assert_eq!(job.counters.n_finished_tasks, 1);
+        assert_eq!(job.counters.n_canceled_tasks, 0);
+        assert!(matches!(
+            job.get_task_state_mut(100).1,
+            JobTaskState::Finished { worker: 1 }
+        ));
+    }
+}
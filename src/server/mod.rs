@@ -1,6 +1,10 @@
+pub mod autoalloc;
 pub mod bootstrap;
 pub mod client;
+pub mod dependency;
 pub mod job;
+pub mod journal;
+pub mod pools;
 pub mod rpc;
 pub mod state;
 pub mod worker;
@@ -0,0 +1,109 @@
+use crate::common::WrappedRcRefCell;
+use crate::transfer::messages::ResourcePoolInfo;
+use crate::Map;
+
+pub type PoolAmount = u32;
+
+/// Units of a resource pool that are used by a task
+pub type PoolUsage = Vec<(String, PoolAmount)>;
+
+struct ResourcePool {
+    size: PoolAmount,
+    used: PoolAmount,
+}
+
+/// Named counters managed by the server (e.g. floating licenses or database connections).
+///
+/// Pools are not bound to workers, a task that uses a pool is submitted to tako only when
+/// enough units of the pool are free and its units are returned when the task leaves tako.
+#[derive(Default)]
+pub struct ResourcePools {
+    pools: Map<String, ResourcePool>,
+}
+
+pub type ResourcePoolsRef = WrappedRcRefCell<ResourcePools>;
+
+impl ResourcePools {
+    /// Creates a pool or changes the size of an existing pool.
+    /// When a pool is shrunk, tasks that already use it are not affected.
+    pub fn set_size(&mut self, name: &str, size: PoolAmount) {
+        match self.pools.get_mut(name) {
+            Some(pool) => pool.size = size,
+            None => {
+                self.pools
+                    .insert(name.to_string(), ResourcePool { size, used: 0 });
+            }
+        }
+    }
+
+    pub fn size(&self, name: &str) -> Option<PoolAmount> {
+        self.pools.get(name).map(|pool| pool.size)
+    }
+
+    /// Takes units of all pools in `usage`, returns false (and takes nothing)
+    /// when some pool does not have enough free units
+    pub fn allocate(&mut self, usage: &[(String, PoolAmount)]) -> bool {
+        let available = usage
+            .iter()
+            .all(|(name, amount)| match self.pools.get(name) {
+                Some(pool) => pool.used + amount <= pool.size,
+                None => false,
+            });
+        if available {
+            for (name, amount) in usage {
+                self.pools.get_mut(name).unwrap().used += amount;
+            }
+        }
+        available
+    }
+
+    pub fn release(&mut self, usage: &[(String, PoolAmount)]) {
+        for (name, amount) in usage {
+            let pool = self.pools.get_mut(name).unwrap();
+            assert!(pool.used >= *amount);
+            pool.used -= amount;
+        }
+    }
+
+    pub fn make_info(&self) -> Vec<ResourcePoolInfo> {
+        let mut pools: Vec<ResourcePoolInfo> = self
+            .pools
+            .iter()
+            .map(|(name, pool)| ResourcePoolInfo {
+                name: name.clone(),
+                size: pool.size,
+                used: pool.used,
+            })
+            .collect();
+        pools.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+        pools
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::server::pools::ResourcePools;
+
+    #[test]
+    fn test_allocate_and_release() {
+        let mut pools = ResourcePools::default();
+        pools.set_size("matlab", 2);
+        pools.set_size("db", 1);
+        let usage = vec![("matlab".to_string(), 1), ("db".to_string(), 1)];
+        assert!(pools.allocate(&usage));
+        assert!(!pools.allocate(&usage));
+        // Nothing is taken when some pool is exhausted
+        assert!(pools.allocate(&[("matlab".to_string(), 1)]));
+        assert!(!pools.allocate(&[("matlab".to_string(), 1)]));
+
+        pools.release(&usage);
+        assert!(pools.allocate(&usage));
+        assert!(!pools.allocate(&[("unknown".to_string(), 1)]));
+
+        pools.set_size("matlab", 1);
+        let info = pools.make_info();
+        assert_eq!(info[0].name, "db");
+        assert_eq!(info[1].name, "matlab");
+        assert_eq!((info[1].size, info[1].used), (1, 2));
+    }
+}
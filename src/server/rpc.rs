@@ -101,7 +101,7 @@ impl Backend {
                                 .process_task_failed(&state_ref, &server2, msg);
                         }
                         ToGatewayMessage::NewWorker(msg) => {
-                            state_ref.get_mut().process_worker_new(msg)
+                            state_ref.get_mut().process_worker_new(msg, &server2)
                         }
                         ToGatewayMessage::LostWorker(msg) => {
                             state_ref.get_mut().process_worker_lost(msg)
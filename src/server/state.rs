@@ -1,17 +1,30 @@
 use std::collections::BTreeMap;
 
 use tako::messages::gateway::{
-    CancelTasks, FromGatewayMessage, LostWorkerMessage, LostWorkerReason, NewWorkerMessage,
-    TaskFailedMessage, TaskState, TaskUpdate, ToGatewayMessage,
+    CancelTasks, FromGatewayMessage, LostWorkerMessage, LostWorkerReason, NewTasksMessage,
+    NewWorkerMessage, TaskDef, TaskFailedMessage, TaskState, TaskUpdate, ToGatewayMessage,
 };
 
 use crate::common::WrappedRcRefCell;
+use crate::server::autoalloc::AutoAllocState;
 use crate::server::job::Job;
+use crate::server::journal::{Journal, JournalEvent};
+use crate::server::pools::ResourcePoolsRef;
 use crate::server::rpc::Backend;
 use crate::server::worker::Worker;
-use crate::transfer::messages::LostWorkerReasonInfo;
+use crate::transfer::messages::{
+    decode_task_failure, JobDependency, JobDependencyMode, LostWorkerReasonInfo, TaskFailure,
+};
 use crate::{JobId, JobTaskCount, Map, TakoTaskId, WorkerId};
-use std::cmp::min;
+use std::cmp::{max, min};
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+use chrono::{DateTime, Utc};
+
+/// Tasks rejected by workers are submitted again after this delay,
+/// so that they do not bounce between workers that cannot run them
+const REJECTED_TASK_DELAY: Duration = Duration::from_millis(500);
 
 pub struct State {
     jobs: crate::Map<JobId, Job>,
@@ -33,6 +46,16 @@ pub struct State {
     base_task_id_to_job_id: BTreeMap<TakoTaskId, JobId>,
     job_id_counter: JobId,
     task_id_counter: TakoTaskId,
+
+    /// Resource pools shared by all jobs
+    resource_pools: ResourcePoolsRef,
+
+    /// Workers that wait until they are asked to drain, waiters of a lost worker are dropped
+    drain_waiters: Map<WorkerId, Vec<oneshot::Sender<()>>>,
+    /// Queues of automatically submitted PBS/SLURM allocations
+    autoalloc: AutoAllocState,
+
+    journal: Option<Journal>,
 }
 
 pub type StateRef = WrappedRcRefCell<State>;
@@ -56,9 +79,22 @@ fn cancel_tasks_from_callback(
             ToGatewayMessage::CancelTasksResponse(msg) => {
                 let mut state = state_ref.get_mut();
                 let job = state.get_job_mut(job_id).unwrap();
-                for tako_id in msg.cancelled_tasks {
-                    job.set_cancel_state(tako_id, &tako_ref);
+                let mut canceled = Vec::new();
+                for tako_id in &msg.cancelled_tasks {
+                    job.set_cancel_state(*tako_id, &tako_ref);
+                    canceled.push(*tako_id);
+                    canceled.extend(
+                        job.cancel_dependants(*tako_id)
+                            .into_iter()
+                            .map(|(tako_id, _)| tako_id),
+                    );
                 }
+                job.unregister_stream_if_terminated(&tako_ref);
+                for task_id in canceled {
+                    state.write_journal(JournalEvent::TaskCanceled { task_id });
+                }
+                submit_tasks_from_callback(&tako_ref, state.take_pool_tasks());
+                state.resolve_held_jobs_if_terminated(job_id, &tako_ref);
             }
             ToGatewayMessage::Error(msg) => {
                 log::debug!("Canceling job {} failed: {}", job_id, msg.message);
@@ -70,6 +106,51 @@ fn cancel_tasks_from_callback(
     });
 }
 
+pub fn submit_tasks_from_callback(tako_ref: &Backend, tasks: Vec<TaskDef>) {
+    if tasks.is_empty() {
+        return;
+    }
+    let tako_ref = tako_ref.clone();
+    tokio::task::spawn_local(async move {
+        let message = FromGatewayMessage::NewTasks(NewTasksMessage { tasks });
+        let response = tako_ref.send_tako_message(message).await.unwrap();
+
+        match response {
+            ToGatewayMessage::NewTasksResponse(_) => { /* Ok */ }
+            ToGatewayMessage::Error(msg) => {
+                log::error!("Submitting tasks failed: {}", msg.message);
+            }
+            _ => {
+                panic!("Invalid message");
+            }
+        };
+    });
+}
+
+fn submit_delayed_task_from_callback(
+    state_ref: &StateRef,
+    tako_ref: &Backend,
+    job_id: JobId,
+    task_id: TakoTaskId,
+    delay: Duration,
+) {
+    let tako_ref = tako_ref.clone();
+    let state_ref = state_ref.clone();
+    tokio::task::spawn_local(async move {
+        tokio::time::sleep(delay).await;
+        // The task may be canceled or its job may be held in the meantime
+        let defs = state_ref
+            .get_mut()
+            .get_job_mut(job_id)
+            .and_then(|job| {
+                let def = job.take_delayed_task(task_id)?;
+                Some(job.submit_or_hold(vec![def]))
+            })
+            .unwrap_or_default();
+        submit_tasks_from_callback(&tako_ref, defs);
+    });
+}
+
 impl State {
     pub fn get_job(&self, job_id: JobId) -> Option<&Job> {
         self.jobs.get(&job_id)
@@ -97,6 +178,80 @@ impl State {
         assert!(self.jobs.insert(job_id, job).is_none());
     }
 
+    /// Adds a job restored from a journal and moves id counters behind its ids
+    pub fn restore_job(&mut self, job: Job) {
+        self.job_id_counter = max(self.job_id_counter, job.job_id + 1);
+        self.task_id_counter = max(
+            self.task_id_counter,
+            job.base_task_id + job.n_tasks() as TakoTaskId,
+        );
+        self.add_job(job);
+    }
+
+    #[inline]
+    pub fn resource_pools(&self) -> &ResourcePoolsRef {
+        &self.resource_pools
+    }
+
+    #[inline]
+    pub fn autoalloc(&self) -> &AutoAllocState {
+        &self.autoalloc
+    }
+
+    #[inline]
+    pub fn autoalloc_mut(&mut self) -> &mut AutoAllocState {
+        &mut self.autoalloc
+    }
+
+    /// Number of tasks of all jobs that wait for their execution (held tasks are not counted)
+    pub fn n_waiting_tasks(&self) -> JobTaskCount {
+        self.jobs
+            .values()
+            .map(|job| job.counters.n_waiting_tasks(job.n_tasks()))
+            .sum()
+    }
+
+    /// Returns throttled tasks of jobs that use resource pools and that can be submitted now,
+    /// it should be called whenever some units of resource pools are returned
+    pub fn take_pool_tasks(&mut self) -> Vec<TaskDef> {
+        let mut job_ids: Vec<JobId> = self
+            .jobs
+            .values()
+            .filter(|job| job.uses_pools())
+            .map(|job| job.job_id)
+            .collect();
+        job_ids.sort_unstable();
+        let mut defs = Vec::new();
+        for job_id in job_ids {
+            defs.extend(self.jobs.get_mut(&job_id).unwrap().take_throttled_tasks());
+        }
+        defs
+    }
+
+    pub fn set_journal(&mut self, journal: Journal) {
+        self.journal = Some(journal);
+    }
+
+    pub fn write_journal(&mut self, event: JournalEvent) {
+        self.write_journal_at(Utc::now(), event)
+    }
+
+    pub fn write_journal_at(&mut self, time: DateTime<Utc>, event: JournalEvent) {
+        if let Some(journal) = &mut self.journal {
+            if let Err(e) = journal.write(time, &event) {
+                log::error!("Cannot write event into journal: {}", e);
+            }
+        }
+    }
+
+    pub fn flush_journal(&mut self) {
+        if let Some(journal) = &mut self.journal {
+            if let Err(e) = journal.flush() {
+                log::error!("Cannot flush journal: {}", e);
+            }
+        }
+    }
+
     pub fn get_job_mut_by_tako_task_id(&mut self, task_id: TakoTaskId) -> Option<&mut Job> {
         let job_id: JobId = *self
             .base_task_id_to_job_id
@@ -118,6 +273,11 @@ impl State {
         id
     }
 
+    /// Id of the last submitted job, zero when no job was submitted
+    pub fn last_job_id(&self) -> JobId {
+        self.job_id_counter - 1
+    }
+
     pub fn last_n_ids(&self, n: JobId) -> impl Iterator<Item = JobId> {
         let n = min(n, self.job_id_counter - 1);
         (self.job_id_counter - n)..self.job_id_counter
@@ -149,15 +309,83 @@ impl State {
     ) {
         log::debug!("Task id={} failed", msg.id);
 
+        let (error, failure) = decode_task_failure(&msg.info.message);
+        if let TaskFailure::Rejected(reason) = &failure {
+            log::debug!("Task id={} was rejected by its worker: {}", msg.id, reason);
+            let job = self.get_job_mut_by_tako_task_id(msg.id).unwrap();
+            let def = job.reject_task(msg.id);
+            let job_id = job.job_id;
+            let runnable = self.has_worker_for(&self.jobs[&job_id]);
+            let job = self.jobs.get_mut(&job_id).unwrap();
+            if runnable {
+                job.delay_task(def);
+                submit_delayed_task_from_callback(
+                    state_ref,
+                    tako_ref,
+                    job_id,
+                    msg.id,
+                    REJECTED_TASK_DELAY,
+                );
+            } else {
+                log::debug!(
+                    "Task id={} is parked until a worker that can run it connects",
+                    msg.id
+                );
+                job.park_task(def);
+            }
+            submit_tasks_from_callback(tako_ref, job.take_throttled_tasks());
+            submit_tasks_from_callback(tako_ref, self.take_pool_tasks());
+            return;
+        }
+        let job = self.get_job_mut_by_tako_task_id(msg.id).unwrap();
+        if let Some(def) = job.retry_task(msg.id) {
+            log::debug!("Task id={} will be retried: {}", msg.id, msg.info.message);
+            let job_id = job.job_id;
+            let delay = job.retry_delay;
+            if delay.as_nanos() == 0 {
+                submit_tasks_from_callback(tako_ref, job.submit_or_hold(vec![def]));
+            } else {
+                job.delay_task(def);
+                submit_tasks_from_callback(tako_ref, job.take_throttled_tasks());
+                submit_delayed_task_from_callback(state_ref, tako_ref, job_id, msg.id, delay);
+            }
+            self.write_journal(JournalEvent::TaskRetried { task_id: msg.id });
+            submit_tasks_from_callback(tako_ref, self.take_pool_tasks());
+            return;
+        }
+        let worker = job.set_failed_state(msg.id, error, failure, tako_ref);
+        self.write_journal(JournalEvent::TaskFailed {
+            task_id: msg.id,
+            worker,
+            error: msg.info.message,
+        });
+
         let job = self.get_job_mut_by_tako_task_id(msg.id).unwrap();
-        job.set_failed_state(msg.id, msg.info.message, tako_ref);
+        let dependants = job.cancel_dependants(msg.id);
+        job.unregister_stream_if_terminated(tako_ref);
+        for (task_id, _) in dependants {
+            self.write_journal(JournalEvent::TaskCanceled { task_id });
+        }
 
+        let job = self.get_job_mut_by_tako_task_id(msg.id).unwrap();
         if let Some(max_fails) = job.max_fails {
             if job.counters.n_failed_tasks > max_fails {
                 let task_ids = job.non_finished_task_ids();
-                cancel_tasks_from_callback(state_ref, tako_ref, job.job_id, task_ids);
+                let job_id = job.job_id;
+                let blocked = job.cancel_unsubmitted_tasks();
+                job.unregister_stream_if_terminated(tako_ref);
+                for (task_id, _) in blocked {
+                    self.write_journal(JournalEvent::TaskCanceled { task_id });
+                }
+                cancel_tasks_from_callback(state_ref, tako_ref, job_id, task_ids);
             }
         }
+
+        let job = self.get_job_mut_by_tako_task_id(msg.id).unwrap();
+        let job_id = job.job_id;
+        submit_tasks_from_callback(tako_ref, job.take_throttled_tasks());
+        submit_tasks_from_callback(tako_ref, self.take_pool_tasks());
+        self.resolve_held_jobs_if_terminated(job_id, tako_ref);
     }
 
     pub fn process_task_update(&mut self, msg: TaskUpdate, backend: &Backend) {
@@ -169,7 +397,18 @@ impl State {
             }
             TaskState::Finished => {
                 let job = self.get_job_mut_by_tako_task_id(msg.id).unwrap();
-                job.set_finished_state(msg.id, backend)
+                let worker = job.set_finished_state(msg.id, backend);
+                self.write_journal(JournalEvent::TaskFinished {
+                    task_id: msg.id,
+                    worker,
+                });
+                let job = self.get_job_mut_by_tako_task_id(msg.id).unwrap();
+                let ready = job.resolve_dependants(msg.id);
+                let ready = job.submit_or_hold(ready);
+                let job_id = job.job_id;
+                submit_tasks_from_callback(backend, ready);
+                submit_tasks_from_callback(backend, self.take_pool_tasks());
+                self.resolve_held_jobs_if_terminated(job_id, backend);
             }
             TaskState::Waiting => {
                 let job = self.get_job_mut_by_tako_task_id(msg.id).unwrap();
@@ -181,13 +420,134 @@ impl State {
         };
     }
 
-    pub fn process_worker_new(&mut self, msg: NewWorkerMessage) {
+    /// Checks whether job dependencies of a held job are resolved.
+    /// Returns `Some(true)` when the job can be released, `Some(false)` when its dependencies
+    /// cannot be satisfied anymore and `None` when the job has to keep waiting.
+    fn job_dependency_outcome(&self, after: &JobDependency) -> Option<bool> {
+        let mut waiting = false;
+        for job_id in &after.jobs {
+            let job = match self.jobs.get(job_id) {
+                Some(job) => job,
+                None => return Some(false),
+            };
+            if !job.is_terminated() {
+                waiting = true;
+                continue;
+            }
+            let success = job.counters.n_finished_tasks == job.n_tasks();
+            match after.mode {
+                JobDependencyMode::Ok if !success => return Some(false),
+                JobDependencyMode::NotOk if success => return Some(false),
+                _ => { /* Outcome is acceptable */ }
+            }
+        }
+        if waiting {
+            None
+        } else {
+            Some(true)
+        }
+    }
+
+    /// Releases held jobs whose job dependencies are satisfied and cancels held jobs
+    /// whose dependencies cannot be satisfied anymore.
+    /// Returns tasks that should be submitted to tako and ids of canceled jobs.
+    ///
+    /// Cancellation of held jobs is not journaled, it is derived again when the state is restored.
+    pub fn resolve_job_dependencies(&mut self) -> (Vec<TaskDef>, Vec<JobId>) {
+        let mut ready = Vec::new();
+        let mut canceled = Vec::new();
+        loop {
+            // A canceled job may resolve other held jobs, so we repeat until nothing changes
+            let decisions: Vec<(JobId, bool)> = self
+                .jobs
+                .values()
+                .filter(|job| job.waits_for_jobs())
+                .filter_map(|job| {
+                    self.job_dependency_outcome(job.job_dependency().unwrap())
+                        .map(|release| (job.job_id, release))
+                })
+                .collect();
+            if decisions.is_empty() {
+                break;
+            }
+            for (job_id, release) in decisions {
+                let job = self.jobs.get_mut(&job_id).unwrap();
+                if release {
+                    ready.extend(job.release_held_tasks());
+                } else {
+                    job.cancel_unsubmitted_tasks();
+                    canceled.push(job_id);
+                }
+            }
+        }
+        (ready, canceled)
+    }
+
+    pub fn resolve_held_jobs_if_terminated(&mut self, job_id: JobId, backend: &Backend) {
+        if !self.jobs[&job_id].is_terminated() {
+            return;
+        }
+        let (ready, canceled) = self.resolve_job_dependencies();
+        for job_id in canceled {
+            self.jobs[&job_id].unregister_stream_if_terminated(backend);
+        }
+        submit_tasks_from_callback(backend, ready);
+    }
+
+    /// Checks whether some connected worker is able to run tasks of the job
+    fn has_worker_for(&self, job: &Job) -> bool {
+        let now = Utc::now();
+        self.workers
+            .values()
+            .any(|worker| worker.can_run_tasks_of(job, now))
+    }
+
+    pub fn process_worker_new(&mut self, msg: NewWorkerMessage, backend: &Backend) {
         log::debug!("New worker id={}", msg.worker_id);
-        self.add_worker(Worker::new(msg.worker_id, msg.configuration));
+        let worker = Worker::new(msg.worker_id, msg.configuration);
+        let now = Utc::now();
+        let mut defs = Vec::new();
+        for job in self.jobs.values_mut() {
+            defs.extend(job.take_parked_tasks(&worker, now));
+        }
+        self.add_worker(worker);
+        submit_tasks_from_callback(backend, defs);
+        submit_tasks_from_callback(backend, self.take_pool_tasks());
+    }
+
+    /// The worker stops starting new tasks, it asks to be stopped when its running tasks end
+    pub fn drain_worker(&mut self, worker_id: WorkerId) {
+        log::debug!("Draining worker id={}", worker_id);
+        self.workers
+            .get_mut(&worker_id)
+            .unwrap()
+            .set_draining_state();
+        for waiter in self.drain_waiters.remove(&worker_id).unwrap_or_default() {
+            let _ = waiter.send(());
+        }
+    }
+
+    /// Returns a receiver that is resolved when the worker is asked to drain.
+    /// The worker may not be registered yet; the receiver fails when the worker is lost.
+    pub fn wait_for_drain(&mut self, worker_id: WorkerId) -> oneshot::Receiver<()> {
+        let (sender, receiver) = oneshot::channel();
+        match self.workers.get(&worker_id) {
+            Some(worker) if worker.is_draining() => {
+                let _ = sender.send(());
+            }
+            Some(worker) if worker.is_offline() => { /* The receiver fails */ }
+            _ => self
+                .drain_waiters
+                .entry(worker_id)
+                .or_default()
+                .push(sender),
+        }
+        receiver
     }
 
     pub fn process_worker_lost(&mut self, msg: LostWorkerMessage) {
         log::debug!("Worker lost id={}", msg.worker_id);
+        self.drain_waiters.remove(&msg.worker_id);
         let worker = self.workers.get_mut(&msg.worker_id).unwrap();
         worker.set_offline_state(match msg.reason {
             LostWorkerReason::Stopped => LostWorkerReasonInfo::Stopped,
@@ -210,19 +570,31 @@ impl StateRef {
             base_task_id_to_job_id: Default::default(),
             job_id_counter: 1,
             task_id_counter: 1,
+            resource_pools: Default::default(),
+            drain_waiters: Default::default(),
+            autoalloc: Default::default(),
+            journal: None,
         })
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use tako::messages::common::{ProgramDefinition, StdioDef};
+    use tako::messages::common::{ProgramDefinition, StdioDef, WorkerConfiguration};
 
     use crate::common::arraydef::ArrayDef;
-    use crate::server::job::Job;
+    use crate::common::fsutils::test_utils::run_concurrent;
+    use crate::common::labels::WORKER_LABELS_KEY;
+    use crate::server::job::{Job, JobTaskState};
+    use crate::server::rpc::Backend;
     use crate::server::state::StateRef;
+    use crate::server::worker::Worker;
     use crate::transfer::messages::JobType;
-    use tako::common::resources::ResourceRequest;
+    use crate::{Map, TakoTaskId, WorkerId};
+    use chrono::Utc;
+    use std::time::Duration;
+    use tako::common::resources::{ResourceDescriptor, ResourceRequest};
+    use tako::messages::gateway::{TaskDef, TaskState, TaskUpdate};
 
     fn dummy_program_definition() -> ProgramDefinition {
         ProgramDefinition {
@@ -234,6 +606,257 @@ mod tests {
         }
     }
 
+    fn dummy_task_def(id: TakoTaskId) -> TaskDef {
+        TaskDef {
+            id,
+            type_id: 0,
+            body: Vec::new(),
+            keep: false,
+            observe: true,
+            n_outputs: 0,
+            priority: 0,
+            resources: ResourceRequest::default(),
+        }
+    }
+
+    fn dummy_worker(worker_id: WorkerId, labels: &str) -> Worker {
+        let mut extra = Map::new();
+        extra.insert(WORKER_LABELS_KEY.to_string(), labels.to_string());
+        Worker::new(
+            worker_id,
+            WorkerConfiguration {
+                resources: ResourceDescriptor::new_with_socket_size(1, 1),
+                listen_address: Default::default(),
+                hostname: "localhost".to_string(),
+                work_dir: Default::default(),
+                log_dir: Default::default(),
+                heartbeat_interval: Duration::from_secs(8),
+                idle_timeout: None,
+                extra,
+                hw_state_poll_interval: None,
+            },
+        )
+    }
+
+    #[test]
+    fn test_hold_and_release_job() {
+        let mut job = Job::new(
+            JobType::Array(ArrayDef::simple_range(0, 3)),
+            1,
+            100,
+            "".to_string(),
+            dummy_program_definition(),
+            ResourceRequest::default(),
+            false,
+            None,
+            None,
+            0,
+            None,
+        );
+        job.set_task_defs(&[
+            dummy_task_def(100),
+            dummy_task_def(101),
+            dummy_task_def(102),
+        ]);
+        job.set_running_state(100, 1);
+
+        let mut waiting = job.hold();
+        waiting.sort_unstable();
+        assert_eq!(waiting, vec![101, 102]);
+        assert!(job.hold_canceled_tasks(&waiting).is_empty());
+        assert_eq!(job.counters.n_held_tasks, 2);
+        assert_eq!(job.counters.n_waiting_tasks(job.n_tasks()), 0);
+        assert!(!job.is_terminated());
+        assert!(matches!(job.get_task_state_mut(101).1, JobTaskState::Held));
+
+        // Tasks that are submitted again while the job is held are held too
+        job.set_waiting_state(100);
+        assert!(job.submit_or_hold(vec![dummy_task_def(100)]).is_empty());
+
+        let mut released: Vec<TakoTaskId> = job.release().iter().map(|def| def.id).collect();
+        released.sort_unstable();
+        assert_eq!(released, vec![100, 101, 102]);
+        assert_eq!(job.counters.n_held_tasks, 0);
+        assert_eq!(job.submit_or_hold(vec![dummy_task_def(101)]).len(), 1);
+    }
+
+    #[test]
+    fn test_hold_task_started_before_cancel() {
+        let mut job = Job::new(
+            JobType::Array(ArrayDef::simple_range(0, 2)),
+            1,
+            100,
+            "".to_string(),
+            dummy_program_definition(),
+            ResourceRequest::default(),
+            false,
+            None,
+            None,
+            0,
+            None,
+        );
+        job.set_task_defs(&[dummy_task_def(100), dummy_task_def(101)]);
+
+        let waiting = job.hold();
+        // Task 101 was started by tako before it received the cancel request
+        job.set_running_state(101, 1);
+        assert!(job.hold_canceled_tasks(&waiting).is_empty());
+        assert_eq!(job.counters.n_held_tasks, 2);
+        assert_eq!(job.counters.n_running_tasks, 0);
+        assert!(matches!(job.get_task_state_mut(101).1, JobTaskState::Held));
+    }
+
+    #[test]
+    fn test_set_priority_keeps_group_offsets() {
+        let mut job = Job::new(
+            JobType::Array(ArrayDef::simple_range(0, 2)),
+            1,
+            100,
+            "".to_string(),
+            dummy_program_definition(),
+            ResourceRequest::default(),
+            false,
+            None,
+            None,
+            0,
+            None,
+        );
+        let mut group_def = dummy_task_def(101);
+        group_def.priority = 5;
+        job.set_task_defs(&[dummy_task_def(100), group_def]);
+
+        let mut waiting = job.set_priority(10);
+        waiting.sort_unstable();
+        assert_eq!(waiting, vec![100, 101]);
+        assert_eq!(job.task_priority(100), 10);
+        assert_eq!(job.task_priority(101), 15);
+        assert!(job.set_priority(10).is_empty());
+
+        // Task started before tako received the cancel request is submitted again
+        job.set_running_state(101, 1);
+        let mut resubmitted: Vec<(TakoTaskId, tako::Priority)> = job
+            .resubmit_canceled_tasks(&waiting)
+            .iter()
+            .map(|def| (def.id, def.priority))
+            .collect();
+        resubmitted.sort_unstable();
+        assert_eq!(resubmitted, vec![(100, 10), (101, 15)]);
+        assert_eq!(job.counters.n_running_tasks, 0);
+    }
+
+    #[test]
+    fn test_rejected_task_is_parked_until_suitable_worker() {
+        let mut job = Job::new(
+            JobType::Simple,
+            1,
+            100,
+            "".to_string(),
+            dummy_program_definition(),
+            ResourceRequest::default(),
+            false,
+            None,
+            None,
+            0,
+            None,
+        );
+        job.required_labels = vec!["gpu".to_string()];
+        job.set_task_defs(&[dummy_task_def(100)]);
+
+        let worker = dummy_worker(1, "ssd");
+        assert!(!worker.can_run_tasks_of(&job, Utc::now()));
+        job.set_running_state(100, 1);
+        let def = job.reject_task(100);
+        assert!(job.make_job_info().started_at.is_none());
+        job.park_task(def);
+        assert!(job.non_finished_task_ids().is_empty());
+        assert_eq!(job.counters.n_waiting_tasks(job.n_tasks()), 1);
+        assert!(job.take_parked_tasks(&worker, Utc::now()).is_empty());
+
+        let worker = dummy_worker(2, "gpu,ssd");
+        assert!(worker.can_run_tasks_of(&job, Utc::now()));
+        let submitted: Vec<TakoTaskId> = job
+            .take_parked_tasks(&worker, Utc::now())
+            .iter()
+            .map(|def| def.id)
+            .collect();
+        assert_eq!(submitted, vec![100]);
+        assert_eq!(job.non_finished_task_ids(), vec![100]);
+    }
+
+    #[tokio::test]
+    async fn test_max_parallel() {
+        let state_ref = StateRef::new();
+        let (backend, backend_fut) = Backend::start(state_ref.clone(), Default::default(), None)
+            .await
+            .unwrap();
+        let mut job = Job::new(
+            JobType::Array(ArrayDef::simple_range(0, 5)),
+            1,
+            100,
+            "".to_string(),
+            dummy_program_definition(),
+            ResourceRequest::default(),
+            false,
+            None,
+            None,
+            0,
+            None,
+        );
+        job.max_parallel = Some(2);
+        let defs: Vec<TaskDef> = (100..105).map(dummy_task_def).collect();
+        job.set_task_defs(&defs);
+
+        let submitted: Vec<TakoTaskId> =
+            job.submit_or_hold(defs).iter().map(|def| def.id).collect();
+        assert_eq!(submitted, vec![100, 101]);
+        assert!(job.take_throttled_tasks().is_empty());
+        let mut waiting = job.non_finished_task_ids();
+        waiting.sort_unstable();
+        assert_eq!(waiting, vec![100, 101]);
+        state_ref.get_mut().add_job(job);
+
+        // Finished task releases a throttled task
+        run_concurrent(backend_fut, async {
+            let mut state = state_ref.get_mut();
+            state.process_task_update(
+                TaskUpdate {
+                    id: 100,
+                    state: TaskState::Running(1),
+                },
+                &backend,
+            );
+            state.process_task_update(
+                TaskUpdate {
+                    id: 100,
+                    state: TaskState::Finished,
+                },
+                &backend,
+            );
+        })
+        .await;
+        let mut state = state_ref.get_mut();
+        let job = state.get_job_mut(1).unwrap();
+        let mut waiting = job.non_finished_task_ids();
+        waiting.sort_unstable();
+        assert_eq!(waiting, vec![101, 102]);
+        assert!(job.take_throttled_tasks().is_empty());
+
+        // Throttled tasks are held together with the job
+        let mut waiting = job.hold();
+        waiting.sort_unstable();
+        assert_eq!(waiting, vec![101, 102]);
+        assert_eq!(job.counters.n_held_tasks, 2);
+        assert!(job.take_throttled_tasks().is_empty());
+
+        let canceled: Vec<TakoTaskId> = job
+            .cancel_unsubmitted_tasks()
+            .iter()
+            .map(|(tako_id, _)| *tako_id)
+            .collect();
+        assert_eq!(canceled.len(), 2);
+        assert_eq!(job.counters.n_held_tasks, 0);
+    }
+
     #[test]
     fn test_find_job_id_by_task_id() {
         let state_ref = StateRef::new();
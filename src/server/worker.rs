@@ -1,12 +1,19 @@
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use tako::messages::common::WorkerConfiguration;
 
+use crate::common::labels::worker_labels;
+use crate::server::job::Job;
 use crate::server::worker::WorkerState::Offline;
 use crate::transfer::messages::{LostWorkerReasonInfo, WorkerExitInfo, WorkerInfo};
+use crate::worker::lifetime::worker_end_time;
+use crate::worker::parser::GenericResourceKind;
+use crate::worker::resources::worker_resource_definitions;
 use crate::WorkerId;
 
 pub enum WorkerState {
     Online,
+    /// Worker does not start new tasks, it is stopped when its running tasks end
+    Draining,
     Offline(WorkerExitInfo),
 }
 
@@ -33,6 +40,61 @@ impl Worker {
         &self.configuration
     }
 
+    #[inline]
+    pub fn is_draining(&self) -> bool {
+        matches!(self.state, WorkerState::Draining)
+    }
+
+    #[inline]
+    pub fn is_offline(&self) -> bool {
+        matches!(self.state, Offline(_))
+    }
+
+    /// Checks whether the worker is able to start tasks of the job, i.e. it has all required
+    /// labels, enough units of generic resources (free or not) and enough remaining lifetime.
+    /// Tasks that fail this check would be rejected by the worker.
+    pub fn can_run_tasks_of(&self, job: &Job, now: DateTime<Utc>) -> bool {
+        if !matches!(self.state, WorkerState::Online) {
+            return false;
+        }
+        let labels = worker_labels(&self.configuration);
+        if !job
+            .required_labels
+            .iter()
+            .all(|label| labels.contains(&label.as_str()))
+        {
+            return false;
+        }
+        if !job.generic_resources.is_empty() {
+            let defs = worker_resource_definitions(&self.configuration);
+            let has_resources = job.generic_resources.iter().all(|request| {
+                defs.iter()
+                    .find(|def| def.name == request.name)
+                    .map_or(false, |def| {
+                        let size = match &def.kind {
+                            GenericResourceKind::Sum(amount) => *amount,
+                            GenericResourceKind::Indices(indices) => indices.len() as u32,
+                        };
+                        size >= request.amount
+                    })
+            });
+            if !has_resources {
+                return false;
+            }
+        }
+        match (job.time_request, worker_end_time(&self.configuration)) {
+            (Some(time_request), Some(end_time)) => {
+                (end_time - now).to_std().unwrap_or_default() >= time_request
+            }
+            _ => true,
+        }
+    }
+
+    pub fn set_draining_state(&mut self) {
+        assert!(matches!(self.state, WorkerState::Online));
+        self.state = WorkerState::Draining;
+    }
+
     pub fn set_offline_state(&mut self, reason: LostWorkerReasonInfo) {
         self.state = Offline(WorkerExitInfo {
             ended_at: Utc::now(),
@@ -45,9 +107,10 @@ impl Worker {
             id: self.worker_id,
             configuration: self.configuration.clone(),
             ended: match &self.state {
-                WorkerState::Online => None,
+                WorkerState::Online | WorkerState::Draining => None,
                 Offline(d) => Some(d.clone()),
             },
+            draining: self.is_draining(),
         }
     }
 }
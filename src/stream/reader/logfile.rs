@@ -52,24 +52,21 @@ impl TaskInfo {
             .find(|info| info.instance_id == instance_id)
     }
 
-    pub fn new_instance(&mut self, instance_id: InstanceId) -> anyhow::Result<()> {
+    pub fn new_instance(&mut self, instance_id: InstanceId) {
+        let info = InstanceInfo {
+            instance_id,
+            channels: [Vec::new(), Vec::new()],
+            finished: false,
+        };
         match self
             .instances
             .binary_search_by(|info| info.instance_id.cmp(&instance_id))
         {
-            Ok(_) => {
-                anyhow::bail!("Instance already exists")
-            }
-            Err(pos) => self.instances.insert(
-                pos,
-                InstanceInfo {
-                    instance_id,
-                    channels: [Vec::new(), Vec::new()],
-                    finished: false,
-                },
-            ),
+            // Log of a restored job continues with instances of the new server,
+            // an instance of the previous server with the same id is replaced by the new one
+            Ok(pos) => self.instances[pos] = info,
+            Err(pos) => self.instances.insert(pos, info),
         }
-        Ok(())
     }
 }
 
@@ -109,6 +106,33 @@ enum Block {
 }
 
 impl LogFile {
+    /// Returns the length of the valid part of a log file. An incomplete block at the end
+    /// (e.g. when the server was stopped during writing) is not counted, so new blocks
+    /// can be appended after the valid part. Zero is returned when the header is not valid.
+    pub fn valid_length(path: &Path) -> anyhow::Result<u64> {
+        let mut file = BufReader::new(File::open(path)?);
+        let file_size = file.get_ref().metadata()?.len();
+        if LogFile::check_header(&mut file).is_err() {
+            return Ok(0);
+        }
+        let mut length = file.stream_position()?;
+        loop {
+            match LogFile::read_block(&mut file) {
+                Ok(Some(Block::StreamChunk { size, .. })) => {
+                    file.seek_relative(size as i64)?;
+                }
+                Ok(Some(_)) => {}
+                Ok(None) | Err(_) => break,
+            }
+            let position = file.stream_position()?;
+            if position > file_size {
+                break;
+            }
+            length = position;
+        }
+        Ok(length)
+    }
+
     pub fn open(path: &Path) -> anyhow::Result<Self> {
         let mut file = BufReader::new(File::open(path)?);
         LogFile::check_header(&mut file)?;
@@ -373,7 +397,7 @@ impl LogFile {
                     let task_info = index.entry(task_id).or_insert_with(|| TaskInfo {
                         instances: Default::default(),
                     });
-                    task_info.new_instance(instance_id)?;
+                    task_info.new_instance(instance_id);
                 }
                 Some(Block::StreamChunk {
                     task_id,
@@ -8,6 +8,8 @@ pub enum StreamServerControlMessage {
     RegisterStream {
         job_id: JobId,
         path: PathBuf,
+        /// Output is appended to an existing log file (e.g. of a restored job)
+        append: bool,
         response: oneshot::Sender<()>,
     },
     UnregisterStream(JobId),
@@ -6,7 +6,7 @@ use futures::StreamExt;
 use orion::aead::streaming::StreamOpener;
 use tako::server::rpc::ConnectionDescriptor;
 use tako::transfer::auth::{forward_queue_to_sealed_sink, open_message};
-use tokio::fs::File;
+use tokio::fs::{File, OpenOptions};
 use tokio::io::AsyncWriteExt;
 use tokio::sync::mpsc::{
     channel, unbounded_channel, Receiver, Sender, UnboundedReceiver, UnboundedSender,
@@ -18,7 +18,8 @@ use super::control::StreamServerControlMessage;
 
 use crate::common::WrappedRcRefCell;
 use crate::stream::reader::logfile::{
-    BLOCK_STREAM_CHUNK, BLOCK_STREAM_END, BLOCK_STREAM_START, HQ_LOG_HEADER, HQ_LOG_VERSION,
+    LogFile, BLOCK_STREAM_CHUNK, BLOCK_STREAM_END, BLOCK_STREAM_START, HQ_LOG_HEADER,
+    HQ_LOG_VERSION,
 };
 use crate::transfer::messages::StreamStats;
 use crate::transfer::stream::{
@@ -36,7 +37,8 @@ enum StreamMessage {
 
 struct StreamServerState {
     streams: Map<JobId, Sender<StreamMessage>>,
-    registrations: Map<JobId, PathBuf>,
+    /// Log file of a job and whether the output is appended to it
+    registrations: Map<JobId, (PathBuf, bool)>,
     connections: Set<String>,
 }
 
@@ -44,13 +46,14 @@ impl StreamServerState {
     fn get_stream(&mut self, job_id: JobId) -> anyhow::Result<Sender<StreamMessage>> {
         if let Some(s) = self.streams.get(&job_id) {
             Ok(s.clone())
-        } else if let Some(path) = self.registrations.get(&job_id) {
+        } else if let Some((path, append)) = self.registrations.get(&job_id) {
             log::debug!("Starting new stream for job {}", job_id);
             let (sender, mut receiver) = channel(STREAM_BUFFER_SIZE);
             self.streams.insert(job_id, sender.clone());
             let path = path.clone();
+            let append = *append;
             tokio::task::spawn_local(async move {
-                if let Err(e) = file_writer(&mut receiver, path).await {
+                if let Err(e) = file_writer(&mut receiver, path, append).await {
                     error_state(receiver, e.to_string()).await;
                 }
             });
@@ -92,15 +95,32 @@ async fn error_state(mut receiver: Receiver<StreamMessage>, message: String) {
     }
 }
 
-async fn file_writer(receiver: &mut Receiver<StreamMessage>, path: PathBuf) -> anyhow::Result<()> {
-    let mut file = BufWriter::new(File::create(&path).await?);
+async fn file_writer(
+    receiver: &mut Receiver<StreamMessage>,
+    path: PathBuf,
+    append: bool,
+) -> anyhow::Result<()> {
+    let file = if append && path.exists() {
+        // Incomplete block at the end of the file is dropped, new blocks continue after valid ones
+        let length = LogFile::valid_length(&path)?;
+        let file = OpenOptions::new().append(true).open(&path).await?;
+        file.set_len(length).await?;
+        file
+    } else {
+        File::create(&path).await?
+    };
+    // Appended file already contains the header, unless it was empty
+    let write_header = file.metadata().await?.len() == 0;
+    let mut file = BufWriter::new(file);
     let mut buffer = BytesMut::with_capacity(24);
-    buffer.put_slice(HQ_LOG_HEADER);
-    buffer.put_u32(HQ_LOG_VERSION);
-    buffer.put_u64(0); // Reserved bytes
-    buffer.put_u64(0); // Reserved bytes
-    file.write_all(&buffer).await?;
-    file.flush().await?; // Make sure that header is written to avoid empty files for long time
+    if write_header {
+        buffer.put_slice(HQ_LOG_HEADER);
+        buffer.put_u32(HQ_LOG_VERSION);
+        buffer.put_u64(0); // Reserved bytes
+        buffer.put_u64(0); // Reserved bytes
+        file.write_all(&buffer).await?;
+        file.flush().await?; // Make sure that header is written to avoid empty files for long time
+    }
 
     while let Some(msg) = receiver.recv().await {
         buffer.clear();
@@ -219,11 +239,12 @@ async fn stream_server_main(mut control_receiver: UnboundedReceiver<StreamServer
             StreamServerControlMessage::RegisterStream {
                 job_id,
                 path,
+                append,
                 response,
             } => {
                 log::debug!("Registering stream {}: {}", job_id, path.display());
                 let mut state = state_ref.get_mut();
-                assert!(state.registrations.insert(job_id, path).is_none());
+                assert!(state.registrations.insert(job_id, (path, append)).is_none());
                 let _ = response.send(());
             }
             StreamServerControlMessage::UnregisterStream(job_id) => {
@@ -256,7 +277,7 @@ async fn stream_server_main(mut control_receiver: UnboundedReceiver<StreamServer
                     registrations: state
                         .registrations
                         .iter()
-                        .map(|(job_id, path)| (*job_id, path.clone()))
+                        .map(|(job_id, (path, _))| (*job_id, path.clone()))
                         .collect(),
                 });
             }
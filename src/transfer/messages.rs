@@ -1,15 +1,23 @@
 use chrono::{DateTime, Utc};
 use serde::Deserialize;
 use serde::Serialize;
-use tako::messages::common::{ProgramDefinition, WorkerConfiguration};
+use tako::messages::common::{ProgramDefinition, StdioDef, WorkerConfiguration};
 
+use crate::client::resources::GenericResourceRequest;
 use crate::client::status::Status;
 use crate::common::arraydef::ArrayDef;
+use crate::common::entries::EntryFormat;
+use crate::server::autoalloc::QueueId;
 use crate::server::job::{JobTaskCounters, JobTaskInfo};
+use crate::server::pools::{PoolAmount, PoolUsage};
 use crate::{JobId, JobTaskCount, JobTaskId, WorkerId};
 use bstr::BString;
+use hashbrown::HashMap;
+use humantime::format_duration;
+use std::fmt;
 use std::path::PathBuf;
-use tako::common::resources::ResourceRequest;
+use std::time::Duration;
+use tako::common::resources::{CpuRequest, ResourceRequest};
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct TaskBody {
@@ -17,6 +25,78 @@ pub struct TaskBody {
     pub pin: bool,
     pub job_id: JobId,
     pub task_id: JobTaskId,
+    /// Task is terminated when it runs longer than the time limit
+    pub time_limit: Option<Duration>,
+    /// Generic resources that are taken by the task on its worker
+    pub resources: Vec<GenericResourceRequest>,
+    /// Task runs only on a worker that has all these labels
+    pub required_labels: Vec<String>,
+    /// Task is started only on a worker whose remaining lifetime is at least this long
+    pub time_request: Option<Duration>,
+}
+
+/// Reason of a task failure reported by a worker
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum TaskFailure {
+    ExitCode(i32),
+    /// Program was terminated by a signal
+    Signal(i32),
+    /// Program cannot be started
+    SpawnError(String),
+    /// Program was terminated because its time limit was exceeded
+    Timeout(Duration),
+    /// Worker cannot run the task (e.g. it does not have free requested resources),
+    /// the task is submitted again by the server
+    Rejected(String),
+    /// Other errors, e.g. failures of streaming
+    Other(String),
+}
+
+impl TaskFailure {
+    pub fn exit_code(&self) -> Option<i32> {
+        match self {
+            TaskFailure::ExitCode(code) => Some(*code),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for TaskFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TaskFailure::ExitCode(code) => write!(f, "Program terminated with exit code {}", code),
+            TaskFailure::Signal(signal) => write!(f, "Program terminated by signal {}", signal),
+            TaskFailure::SpawnError(e) => write!(f, "Cannot start program: {}", e),
+            TaskFailure::Timeout(time_limit) => {
+                write!(f, "Time limit {} exceeded", format_duration(*time_limit))
+            }
+            TaskFailure::Rejected(reason) => write!(f, "Task was rejected: {}", reason),
+            TaskFailure::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// Tako transfers only an error message of a failed task,
+/// so the failure is serialized into the message behind this marker
+const TASK_FAILURE_MARKER: &str = "hq-failure:";
+
+pub fn encode_task_failure(failure: &TaskFailure) -> String {
+    format!(
+        "{}{}",
+        TASK_FAILURE_MARKER,
+        serde_json::to_string(failure).unwrap()
+    )
+}
+
+/// Parses an error message of a failed task, returns a readable message and the failure
+pub fn decode_task_failure(message: &str) -> (String, TaskFailure) {
+    if let Some(position) = message.find(TASK_FAILURE_MARKER) {
+        let data = &message[position + TASK_FAILURE_MARKER.len()..];
+        if let Ok(failure) = serde_json::from_str::<TaskFailure>(data) {
+            return (format!("{}{}", &message[..position], failure), failure);
+        }
+    }
+    (message.to_string(), TaskFailure::Other(message.to_string()))
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -25,7 +105,7 @@ pub enum JobType {
     Array(ArrayDef),
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct SubmitRequest {
     pub job_type: JobType,
     pub name: String,
@@ -34,32 +114,145 @@ pub struct SubmitRequest {
     pub resources: ResourceRequest,
     pub pin: bool,
     pub entries: Option<Vec<BString>>,
+    pub entry_format: EntryFormat,
     pub submit_dir: PathBuf,
     pub priority: tako::Priority,
     pub log: Option<PathBuf>,
+    pub task_deps: Vec<TaskDependency>,
+    pub after: Option<JobDependency>,
+    pub max_retries: u32,
+    pub retry_delay: Duration,
+    pub time_limit: Option<Duration>,
+    pub task_groups: Vec<TaskGroup>,
+    /// Maximal number of tasks of the job that are submitted to tako at the same time
+    pub max_parallel: Option<JobTaskCount>,
+    /// Units of resource pools that are used by each task of the job
+    pub pool_usage: PoolUsage,
+    /// Generic resources of workers that are used by each task of the job
+    pub generic_resources: Vec<GenericResourceRequest>,
+    /// Labels that a worker needs to have to run tasks of the job
+    pub required_labels: Vec<String>,
+    /// Minimal remaining lifetime of a worker that starts a task of the job
+    pub time_request: Option<Duration>,
+}
+
+/// Tasks of a job that have their own configuration (e.g. when the job is defined by a job file).
+/// Tasks that are not part of any group use the configuration of the job.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TaskGroup {
+    pub tasks: ArrayDef,
+    pub spec: ProgramDefinition,
+    pub resources: ResourceRequest,
+    pub pin: bool,
+    pub priority: tako::Priority,
+}
+
+/// Task `task_id` is not started before all tasks in `depends_on` are finished
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TaskDependency {
+    pub task_id: JobTaskId,
+    pub depends_on: Vec<JobTaskId>,
+}
+
+/// Outcome of referenced jobs that is required before a dependent job is started
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub enum JobDependencyMode {
+    /// All referenced jobs have to finish successfully
+    Ok,
+    /// Referenced jobs have to terminate, their outcome does not matter
+    Any,
+    /// All referenced jobs have to terminate unsuccessfully (failed or canceled tasks)
+    NotOk,
+}
+
+/// Job is held by the server until all jobs in `jobs` are terminated
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct JobDependency {
+    pub jobs: Vec<JobId>,
+    pub mode: JobDependencyMode,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ResubmitRequest {
     pub job_id: JobId,
     pub status: Option<Vec<Status>>,
+    pub overrides: ResubmitOverrides,
+}
+
+/// Properties that replace the properties of the original job when it is resubmitted,
+/// `None` keeps the original value
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ResubmitOverrides {
+    pub name: Option<String>,
+    pub args: Option<Vec<BString>>,
+    /// Variables that are added to the original environment (or replace it)
+    pub env: HashMap<BString, BString>,
+    pub cwd: Option<PathBuf>,
+    pub stdout: Option<StdioDef>,
+    pub stderr: Option<StdioDef>,
+    pub cpus: Option<CpuRequest>,
+    pub pin: Option<bool>,
+    pub priority: Option<tako::Priority>,
+    pub max_fails: Option<JobTaskCount>,
+    pub max_retries: Option<u32>,
+    pub retry_delay: Option<Duration>,
+    pub time_limit: Option<Duration>,
+    pub max_parallel: Option<JobTaskCount>,
+    /// Replaces the resource pools used by the original job
+    pub pool_usage: Option<PoolUsage>,
+    /// Replaces the generic resources requested by the original job
+    pub generic_resources: Option<Vec<GenericResourceRequest>>,
+    /// Replaces the labels required by the original job
+    pub required_labels: Option<Vec<String>>,
+    pub time_request: Option<Duration>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct CancelRequest {
     pub selector: JobSelector,
+    /// Cancel only the given tasks of selected jobs
+    pub tasks: Option<ArrayDef>,
+}
+
+/// Running tasks are canceled and submitted again
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RequeueRequest {
+    pub job_id: JobId,
+    pub tasks: ArrayDef,
+}
+
+/// Holds (`hold: true`) or releases (`hold: false`) selected jobs
+#[derive(Serialize, Deserialize, Debug)]
+pub struct HoldRequest {
+    pub selector: JobSelector,
+    pub hold: bool,
 }
 
+/// Changes properties of a submitted job, `None` values are not changed
 #[derive(Serialize, Deserialize, Debug)]
+pub struct UpdateJobRequest {
+    pub job_id: JobId,
+    pub priority: Option<tako::Priority>,
+    pub max_fails: Option<JobTaskCount>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum JobSelector {
     All,
     LastN(JobId),
-    Specific(Vec<JobId>),
+    /// Ids of jobs, ranges are kept as they are, so that a huge range is not expanded
+    Specific(ArrayDef),
+    /// Jobs with a name that matches a pattern, `*` matches any sequence of characters
+    Name(String),
+    /// Jobs in one of the given states
+    Status(Vec<Status>),
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct JobInfoRequest {
     pub selector: JobSelector,
+    /// Return only jobs with a task that failed with the given exit code
+    pub exit_code: Option<i32>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -73,23 +266,85 @@ pub struct StopWorkerMessage {
     pub selector: WorkerSelector,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DrainWorkerMessage {
+    pub selector: WorkerSelector,
+}
+
+/// Sent by a worker, the server responds when the worker is asked to drain
+#[derive(Serialize, Deserialize, Debug)]
+pub struct WaitForDrainMessage {
+    pub worker_id: WorkerId,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub enum FromClientMessage {
     Submit(SubmitRequest),
     Resubmit(ResubmitRequest),
     Cancel(CancelRequest),
+    Hold(HoldRequest),
+    UpdateJob(UpdateJobRequest),
+    Requeue(RequeueRequest),
     JobDetail(JobDetailRequest),
     JobInfo(JobInfoRequest),
     WorkerList,
     WorkerInfo(WorkerInfoRequest),
     Stats,
     StopWorker(StopWorkerMessage),
+    DrainWorker(DrainWorkerMessage),
+    WaitForDrain(WaitForDrainMessage),
+    ResourcePools,
+    SetResourcePool(ResourcePoolRequest),
+    AutoAlloc(AutoAllocRequest),
     Stop,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub enum CancelJobResponse {
-    Canceled(Vec<JobTaskId>, JobTaskCount),
+    Canceled {
+        canceled: Vec<JobTaskId>,
+        /// Blocked tasks that were canceled because they depend on canceled tasks
+        dependants: Vec<JobTaskId>,
+        /// Tasks that were already terminated
+        already_finished: Vec<JobTaskId>,
+        /// Requested tasks that are not part of the job
+        invalid: Vec<JobTaskId>,
+    },
+    InvalidJob,
+    Failed(String),
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub enum RequeueResponse {
+    Requeued {
+        requeued: Vec<JobTaskId>,
+        /// Requested tasks that were not running
+        not_running: Vec<JobTaskId>,
+        /// Requested tasks that are not part of the job
+        invalid: Vec<JobTaskId>,
+    },
+    InvalidJob,
+    Failed(String),
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub enum HoldJobResponse {
+    /// Job is held, the number of its held tasks
+    Held(JobTaskCount),
+    /// Job is released, the number of released tasks
+    Released(JobTaskCount),
+    /// Job was not held by a user
+    NotHeld,
+    /// All tasks of the job are already terminated
+    Terminated,
+    InvalidJob,
+    Failed(String),
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub enum UpdateJobResponse {
+    /// Job is updated, the number of waiting tasks that were submitted again with a new priority
+    Updated(JobTaskCount),
     InvalidJob,
     Failed(String),
 }
@@ -102,6 +357,110 @@ pub enum StopWorkerResponse {
     Failed(String),
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+pub enum DrainWorkerResponse {
+    Draining,
+    AlreadyDraining,
+    AlreadyStopped,
+    InvalidWorker,
+}
+
+/// Creates a resource pool or changes its size
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ResourcePoolRequest {
+    pub name: String,
+    pub size: PoolAmount,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ResourcePoolInfo {
+    pub name: String,
+    pub size: PoolAmount,
+    /// Units taken by tasks that were submitted to tako
+    pub used: PoolAmount,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum ManagerType {
+    Pbs,
+    Slurm,
+}
+
+impl fmt::Display for ManagerType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ManagerType::Pbs => write!(f, "PBS"),
+            ManagerType::Slurm => write!(f, "SLURM"),
+        }
+    }
+}
+
+/// Creates a queue of allocations that run HQ workers
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AddQueueRequest {
+    pub manager: ManagerType,
+    /// PBS queue or SLURM partition
+    pub queue: String,
+    pub time_limit: Duration,
+    /// Maximal number of workers (allocations) that are queued or running at the same time
+    pub max_workers: u32,
+    /// Maximal number of allocations that wait in the queue of the manager
+    pub backlog: u32,
+    /// Arguments that are passed to `qsub`/`sbatch`
+    pub additional_args: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub enum AutoAllocRequest {
+    List,
+    Info { queue_id: QueueId },
+    AddQueue(AddQueueRequest),
+    RemoveQueue { queue_id: QueueId },
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub enum AutoAllocResponse {
+    Queues(Vec<AllocationQueueInfo>),
+    Allocations(Vec<AllocationInfo>),
+    QueueCreated(QueueId),
+    QueueRemoved(QueueId),
+    InvalidQueue(QueueId),
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AllocationQueueInfo {
+    pub id: QueueId,
+    pub manager: ManagerType,
+    pub queue: String,
+    pub time_limit: Duration,
+    pub max_workers: u32,
+    pub backlog: u32,
+    pub additional_args: Vec<String>,
+    /// No more allocations are submitted because too many allocations failed in a row
+    pub stopped: bool,
+    pub n_queued: u32,
+    pub n_running: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum AllocationStatus {
+    Queued,
+    Running,
+    Finished,
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AllocationInfo {
+    /// Id of the allocation in PBS/SLURM
+    pub id: String,
+    pub status: AllocationStatus,
+    pub working_dir: PathBuf,
+    pub queued_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub ended_at: Option<DateTime<Utc>>,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct StreamStats {
     pub connections: Vec<String>,
@@ -122,7 +481,15 @@ pub enum ToClientMessage {
     WorkerInfoResponse(Option<WorkerInfo>),
     StatsResponse(StatsResponse),
     StopWorkerResponse(Vec<(WorkerId, StopWorkerResponse)>),
+    DrainWorkerResponse(Vec<(WorkerId, DrainWorkerResponse)>),
+    /// Response to `WaitForDrain`
+    WorkerDraining,
     CancelJobResponse(Vec<(JobId, CancelJobResponse)>),
+    HoldJobResponse(Vec<(JobId, HoldJobResponse)>),
+    UpdateJobResponse(UpdateJobResponse),
+    RequeueResponse(RequeueResponse),
+    ResourcePoolsResponse(Vec<ResourcePoolInfo>),
+    AutoAllocResponse(AutoAllocResponse),
     Error(String),
 }
 
@@ -144,6 +511,12 @@ pub struct JobInfo {
     pub n_tasks: JobTaskCount,
     pub counters: JobTaskCounters,
     pub resources: ResourceRequest,
+
+    pub submitted_at: DateTime<Utc>,
+    /// Start of the first task
+    pub started_at: Option<DateTime<Utc>>,
+    /// End of the last task, set only when the job is terminated
+    pub ended_at: Option<DateTime<Utc>>,
 }
 
 // We need to duplicate LostWorkerReason because of serialization problems (msgpack vs. binpack)
@@ -166,6 +539,8 @@ pub struct WorkerInfo {
     pub id: WorkerId,
     pub configuration: WorkerConfiguration,
     pub ended: Option<WorkerExitInfo>,
+    /// Worker does not start new tasks and it stops when its running tasks end
+    pub draining: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -182,8 +557,19 @@ pub struct JobDetail {
     pub resources: ResourceRequest,
     pub pin: bool,
     pub entries: Option<Vec<BString>>,
+    pub entry_format: EntryFormat,
     pub max_fails: Option<JobTaskCount>,
     pub priority: tako::Priority,
+    pub after: Option<JobDependency>,
+    pub max_retries: u32,
+    pub retry_delay: Duration,
+    pub time_limit: Option<Duration>,
+    pub task_groups: Vec<TaskGroup>,
+    pub max_parallel: Option<JobTaskCount>,
+    pub pool_usage: PoolUsage,
+    pub generic_resources: Vec<GenericResourceRequest>,
+    pub required_labels: Vec<String>,
+    pub time_request: Option<Duration>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -211,3 +597,31 @@ pub struct WorkerInfoRequest {
 pub struct WorkerInfoResponse {
     pub worker: WorkerInfo,
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::transfer::messages::{decode_task_failure, encode_task_failure, TaskFailure};
+
+    #[test]
+    fn test_task_failure_roundtrip() {
+        let message = format!("Error: {}", encode_task_failure(&TaskFailure::ExitCode(3)));
+        let (error, failure) = decode_task_failure(&message);
+        assert_eq!(error, "Error: Program terminated with exit code 3");
+        assert_eq!(failure, TaskFailure::ExitCode(3));
+
+        let (error, failure) = decode_task_failure(&encode_task_failure(&TaskFailure::Timeout(
+            Duration::from_secs(2),
+        )));
+        assert_eq!(error, "Time limit 2s exceeded");
+        assert_eq!(failure, TaskFailure::Timeout(Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn test_task_failure_plain_message() {
+        let (error, failure) = decode_task_failure("Worker lost");
+        assert_eq!(error, "Worker lost");
+        assert_eq!(failure, TaskFailure::Other("Worker lost".to_string()));
+    }
+}
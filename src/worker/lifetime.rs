@@ -0,0 +1,28 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use tako::messages::common::WorkerConfiguration;
+
+/// Key of `WorkerConfiguration::extra` that contains the time (RFC 3339)
+/// when the worker stops because of its time limit
+pub const WORKER_END_TIME_KEY: &str = "END_TIME";
+
+pub fn worker_end_time(configuration: &WorkerConfiguration) -> Option<DateTime<Utc>> {
+    configuration
+        .extra
+        .get(WORKER_END_TIME_KEY)
+        .and_then(|time| DateTime::parse_from_rfc3339(time).ok())
+        .map(|time| time.with_timezone(&Utc))
+}
+
+/// Time that remains until the end of the worker's lifetime (rounded to seconds),
+/// `None` when the worker has no time limit
+pub fn remaining_lifetime(
+    configuration: &WorkerConfiguration,
+    now: DateTime<Utc>,
+) -> Option<Duration> {
+    worker_end_time(configuration).map(|end| {
+        let remaining = (end - now).to_std().unwrap_or_default();
+        Duration::from_secs(remaining.as_secs())
+    })
+}
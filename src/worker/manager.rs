@@ -0,0 +1,210 @@
+use std::time::Duration;
+
+use crate::Map;
+
+/// Keys of `WorkerConfiguration::extra` filled by the detection of the HPC job manager
+pub const MANAGER_QUEUE_KEY: &str = "MANAGER_QUEUE";
+pub const MANAGER_NODES_KEY: &str = "MANAGER_NODES";
+pub const MANAGER_WALLTIME_KEY: &str = "MANAGER_WALLTIME";
+
+/// Environment variables that override paths to the commands that are used to query
+/// information about the job of the worker
+pub const QSTAT_PATH_ENV: &str = "HQ_QSTAT_PATH";
+pub const SCONTROL_PATH_ENV: &str = "HQ_SCONTROL_PATH";
+
+/// Information about the PBS/SLURM job in which the worker runs
+#[derive(Debug, Default, PartialEq)]
+pub struct ManagerJobInfo {
+    pub queue: Option<String>,
+    /// Hostnames of nodes of the job (SLURM keeps its compressed form, e.g. `node[01-04]`)
+    pub nodes: Option<String>,
+    pub walltime: Option<Duration>,
+    /// Time that the job has been running for
+    pub used_walltime: Option<Duration>,
+}
+
+impl ManagerJobInfo {
+    pub fn remaining_walltime(&self) -> Option<Duration> {
+        self.walltime.map(|walltime| {
+            walltime
+                .checked_sub(self.used_walltime.unwrap_or_default())
+                .unwrap_or_default()
+        })
+    }
+}
+
+/// Parses a duration used by PBS and SLURM in the format `[[hours:]minutes:]seconds`
+/// or `days-hours[:minutes[:seconds]]` (e.g. `01:30:00`, `2-12` or `1-02:30`)
+pub fn parse_hms_duration(text: &str) -> Option<Duration> {
+    let parse_parts = |text: &str| {
+        let parts = text
+            .split(':')
+            .map(|p| p.parse::<u64>().ok())
+            .collect::<Option<Vec<u64>>>()?;
+        if parts.len() > 3 {
+            None
+        } else {
+            Some(parts)
+        }
+    };
+    let seconds = match text.find('-') {
+        Some(position) => {
+            let days = text[..position].parse::<u64>().ok()?;
+            let parts = parse_parts(&text[position + 1..])?;
+            parts
+                .iter()
+                .zip(&[3600, 60, 1])
+                .fold(days * 24 * 3600, |acc, (part, unit)| acc + part * unit)
+        }
+        None => parse_parts(text)?
+            .iter()
+            .fold(0, |acc, part| acc * 60 + part),
+    };
+    Some(Duration::from_secs(seconds))
+}
+
+/// Parses the output of `qstat -f -F json <job-id>`
+pub fn parse_pbs_job_info(output: &str, job_id: &str) -> anyhow::Result<ManagerJobInfo> {
+    let value: serde_json::Value = serde_json::from_str(output)?;
+    let job = &value["Jobs"][job_id];
+    if job.is_null() {
+        anyhow::bail!("Job {} not found in the output of qstat", job_id);
+    }
+    let get_str = |value: &serde_json::Value| value.as_str().map(|s| s.to_string());
+
+    let nodes = job["exec_host"].as_str().map(|hosts| {
+        let mut nodes: Vec<&str> = Vec::new();
+        for host in hosts.split('+') {
+            let host = host.split('/').next().unwrap_or_default();
+            if !nodes.contains(&host) {
+                nodes.push(host);
+            }
+        }
+        nodes.join(",")
+    });
+
+    Ok(ManagerJobInfo {
+        queue: get_str(&job["queue"]),
+        nodes,
+        walltime: job["Resource_List"]["walltime"]
+            .as_str()
+            .and_then(parse_hms_duration),
+        used_walltime: job["resources_used"]["walltime"]
+            .as_str()
+            .and_then(parse_hms_duration),
+    })
+}
+
+/// Parses the output of `scontrol show job <job-id>`
+pub fn parse_slurm_job_info(output: &str) -> ManagerJobInfo {
+    let values: Map<&str, &str> = output
+        .split_whitespace()
+        .filter_map(|item| {
+            let mut parts = item.splitn(2, '=');
+            Some((parts.next()?, parts.next()?))
+        })
+        .collect();
+    let get_value = |key: &str| {
+        values
+            .get(key)
+            .filter(|value| !value.is_empty() && **value != "(null)")
+            .map(|value| value.to_string())
+    };
+
+    ManagerJobInfo {
+        queue: get_value("Partition"),
+        nodes: get_value("NodeList"),
+        walltime: values
+            .get("TimeLimit")
+            .and_then(|time| parse_hms_duration(time)),
+        used_walltime: values
+            .get("RunTime")
+            .and_then(|time| parse_hms_duration(time)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::worker::manager::{
+        parse_hms_duration, parse_pbs_job_info, parse_slurm_job_info, ManagerJobInfo,
+    };
+
+    #[test]
+    fn test_parse_hms_duration() {
+        assert_eq!(
+            parse_hms_duration("01:30:00"),
+            Some(Duration::from_secs(5400))
+        );
+        assert_eq!(parse_hms_duration("30:05"), Some(Duration::from_secs(1805)));
+        assert_eq!(parse_hms_duration("42"), Some(Duration::from_secs(42)));
+        assert_eq!(
+            parse_hms_duration("2-12:00:00"),
+            Some(Duration::from_secs(60 * 3600))
+        );
+        assert_eq!(
+            parse_hms_duration("2-12"),
+            Some(Duration::from_secs((2 * 24 + 12) * 3600))
+        );
+        assert_eq!(
+            parse_hms_duration("1-2:30"),
+            Some(Duration::from_secs((24 + 2) * 3600 + 30 * 60))
+        );
+        assert_eq!(parse_hms_duration("1-"), None);
+        assert_eq!(parse_hms_duration("1-1:2:3:4"), None);
+        assert_eq!(parse_hms_duration("UNLIMITED"), None);
+        assert_eq!(parse_hms_duration("1:2:3:4"), None);
+        assert_eq!(parse_hms_duration(""), None);
+    }
+
+    #[test]
+    fn test_parse_pbs_job_info() {
+        let output = r#"{
+            "Jobs": {
+                "123.pbs": {
+                    "queue": "qprod",
+                    "exec_host": "cn1/0*24+cn2/0*24",
+                    "Resource_List": {"walltime": "02:00:00"},
+                    "resources_used": {"walltime": "00:30:00"}
+                }
+            }
+        }"#;
+        let info = parse_pbs_job_info(output, "123.pbs").unwrap();
+        assert_eq!(
+            info,
+            ManagerJobInfo {
+                queue: Some("qprod".to_string()),
+                nodes: Some("cn1,cn2".to_string()),
+                walltime: Some(Duration::from_secs(7200)),
+                used_walltime: Some(Duration::from_secs(1800)),
+            }
+        );
+        assert_eq!(info.remaining_walltime(), Some(Duration::from_secs(5400)));
+        assert!(parse_pbs_job_info(output, "456.pbs").is_err());
+        assert!(parse_pbs_job_info("x", "123.pbs").is_err());
+    }
+
+    #[test]
+    fn test_parse_slurm_job_info() {
+        let output = concat!(
+            "JobId=5678 JobName=hq\n",
+            "   JobState=RUNNING Reason=None Dependency=(null)\n",
+            "   RunTime=00:10:00 TimeLimit=1-00:00:00 TimeMin=N/A\n",
+            "   Partition=cpu AllocNode:Sid=login1:1234\n",
+            "   NodeList=cn[01-04]\n"
+        );
+        let info = parse_slurm_job_info(output);
+        assert_eq!(info.queue.as_deref(), Some("cpu"));
+        assert_eq!(info.nodes.as_deref(), Some("cn[01-04]"));
+        assert_eq!(info.walltime, Some(Duration::from_secs(24 * 3600)));
+        assert_eq!(
+            info.remaining_walltime(),
+            Some(Duration::from_secs(24 * 3600 - 600))
+        );
+
+        let info = parse_slurm_job_info("JobId=1 TimeLimit=UNLIMITED NodeList=(null)");
+        assert_eq!(info, ManagerJobInfo::default());
+        assert_eq!(info.remaining_walltime(), None);
+    }
+}
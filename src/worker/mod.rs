@@ -1,5 +1,8 @@
 pub mod hwdetect;
+pub mod lifetime;
+pub mod manager;
 pub mod output;
 pub mod parser;
+pub mod resources;
 pub mod start;
 pub mod streamer;
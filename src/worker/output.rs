@@ -1,8 +1,14 @@
+use chrono::Utc;
 use cli_table::{print_stdout, Cell, Style, Table};
 use humantime::format_duration;
 use tako::messages::common::WorkerConfiguration;
 
 use crate::client::globalsettings::GlobalSettings;
+use crate::client::utils::format_datetime;
+use crate::client::worker::format_worker_labels;
+use crate::worker::lifetime::{remaining_lifetime, worker_end_time};
+use crate::worker::manager::{MANAGER_NODES_KEY, MANAGER_QUEUE_KEY, MANAGER_WALLTIME_KEY};
+use crate::worker::resources::format_worker_resources;
 use crate::WorkerId;
 
 pub fn print_worker_configuration(
@@ -10,7 +16,7 @@ pub fn print_worker_configuration(
     worker_id: WorkerId,
     configuration: WorkerConfiguration,
 ) {
-    let rows = vec![
+    let mut rows = vec![
         vec!["Worker ID".cell().bold(true), worker_id.cell()],
         vec!["Hostname".cell().bold(true), configuration.hostname.cell()],
         vec![
@@ -39,10 +45,32 @@ pub fn print_worker_configuration(
                 .unwrap_or_else(|| "None".to_string())
                 .cell(),
         ],
+        vec![
+            "Time limit".cell().bold(true),
+            worker_end_time(&configuration)
+                .map(format_datetime)
+                .unwrap_or_else(|| "None".to_string())
+                .cell(),
+        ],
+        vec![
+            "Remaining lifetime".cell().bold(true),
+            remaining_lifetime(&configuration, Utc::now())
+                .map(|x| format_duration(x).to_string())
+                .unwrap_or_else(|| "N/A".to_string())
+                .cell(),
+        ],
         vec![
             "Resources".cell().bold(true),
             configuration.resources.summary().cell(),
         ],
+        vec![
+            "Generic resources".cell().bold(true),
+            format_worker_resources(&configuration).cell(),
+        ],
+        vec![
+            "Labels".cell().bold(true),
+            format_worker_labels(&configuration).cell(),
+        ],
         vec![
             "Manager".cell().bold(true),
             configuration
@@ -62,6 +90,25 @@ pub fn print_worker_configuration(
                 .cell(),
         ],
     ];
+    rows.extend(
+        [
+            ("Manager queue", MANAGER_QUEUE_KEY),
+            ("Manager nodes", MANAGER_NODES_KEY),
+            ("Manager walltime", MANAGER_WALLTIME_KEY),
+        ]
+        .iter()
+        .map(|(name, key)| {
+            vec![
+                name.cell().bold(true),
+                configuration
+                    .extra
+                    .get(*key)
+                    .map(|x| x.as_str())
+                    .unwrap_or("N/A")
+                    .cell(),
+            ]
+        }),
+    );
     let table = rows.table().color_choice(gsettings.color_policy());
     assert!(print_stdout(table).is_ok());
 }
@@ -1,10 +1,30 @@
-use crate::common::parser::{p_uint, NomResult};
+use crate::common::parser::{format_parse_error, p_resource_name, p_uint, NomResult};
 use anyhow::anyhow;
+use nom::branch::alt;
 use nom::bytes::complete::tag;
-use nom::combinator::{all_consuming, map, opt};
-use nom::sequence::{preceded, tuple};
+use nom::character::complete::{char, multispace0};
+use nom::combinator::{all_consuming, map, map_res, opt};
+use nom::multi::separated_list1;
+use nom::sequence::{delimited, preceded, separated_pair, tuple};
 use tako::common::resources::ResourceDescriptor;
 
+/// Amount of a generic resource that is provided by a worker
+#[derive(Debug, Clone, PartialEq)]
+pub enum GenericResourceKind {
+    /// Resource that is consumed by tasks as an amount (e.g. memory)
+    Sum(u32),
+    /// Resource with individual units that are assigned to tasks (e.g. GPUs or FPGAs)
+    Indices(Vec<u32>),
+}
+
+/// Named resource of a worker in the format `<name>=<amount>`, `<name>=[<index>,<index>,...]`
+/// or `<name>=range(<start>-<end>)`
+#[derive(Debug, Clone, PartialEq)]
+pub struct GenericResourceDef {
+    pub name: String,
+    pub kind: GenericResourceKind,
+}
+
 fn p_cpu_definition(input: &str) -> NomResult<ResourceDescriptor> {
     map(
         tuple((p_uint, opt(preceded(tag("x"), p_uint)))),
@@ -21,6 +41,65 @@ pub fn parse_cpu_definition(input: &str) -> anyhow::Result<ResourceDescriptor> {
         .map_err(|e| anyhow!(e.to_string()))
 }
 
+fn p_resource_kind(input: &str) -> NomResult<GenericResourceKind> {
+    alt((
+        map(
+            delimited(
+                char('['),
+                separated_list1(char(','), delimited(multispace0, p_uint, multispace0)),
+                char(']'),
+            ),
+            GenericResourceKind::Indices,
+        ),
+        map_res(
+            delimited(
+                tag("range("),
+                separated_pair(p_uint, char('-'), p_uint),
+                char(')'),
+            ),
+            |(start, end)| {
+                if start > end {
+                    return Err(anyhow!("Invalid range"));
+                }
+                Ok(GenericResourceKind::Indices((start..=end).collect()))
+            },
+        ),
+        map(p_uint, GenericResourceKind::Sum),
+    ))(input)
+}
+
+fn p_resource_definition(input: &str) -> NomResult<GenericResourceDef> {
+    map(
+        separated_pair(p_resource_name, char('='), p_resource_kind),
+        |(name, kind)| GenericResourceDef {
+            name: name.to_string(),
+            kind,
+        },
+    )(input)
+}
+
+pub fn parse_resource_definition(input: &str) -> anyhow::Result<GenericResourceDef> {
+    all_consuming(p_resource_definition)(input)
+        .map(|r| r.1)
+        .map_err(format_parse_error)
+}
+
+/// Formats a definition in the format accepted by `parse_resource_definition`
+pub fn format_resource_definition(def: &GenericResourceDef) -> String {
+    match &def.kind {
+        GenericResourceKind::Sum(amount) => format!("{}={}", def.name, amount),
+        GenericResourceKind::Indices(indices) => format!(
+            "{}=[{}]",
+            def.name,
+            indices
+                .iter()
+                .map(|i| i.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        ),
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -36,4 +115,39 @@ mod test {
             vec![vec![0, 1, 2], vec![3, 4, 5]]
         );
     }
+
+    #[test]
+    fn test_parse_resource_def() {
+        assert_eq!(
+            parse_resource_definition("mem=64000").unwrap(),
+            GenericResourceDef {
+                name: "mem".to_string(),
+                kind: GenericResourceKind::Sum(64000)
+            }
+        );
+        assert_eq!(
+            parse_resource_definition("fpga=[0, 1,5]").unwrap().kind,
+            GenericResourceKind::Indices(vec![0, 1, 5])
+        );
+        assert_eq!(
+            parse_resource_definition("gpu=range(2-4)").unwrap().kind,
+            GenericResourceKind::Indices(vec![2, 3, 4])
+        );
+        assert!(parse_resource_definition("gpu=range(4-2)").is_err());
+        assert!(parse_resource_definition("gpu=[]").is_err());
+        assert!(parse_resource_definition("=5").is_err());
+        assert!(parse_resource_definition("mem").is_err());
+    }
+
+    #[test]
+    fn test_format_resource_def() {
+        for input in &["mem=64000", "gpu=[0,1,5]"] {
+            let def = parse_resource_definition(input).unwrap();
+            assert_eq!(format_resource_definition(&def), *input);
+        }
+        assert_eq!(
+            format_resource_definition(&parse_resource_definition("gpu=range(2-4)").unwrap()),
+            "gpu=[2,3,4]"
+        );
+    }
 }
@@ -0,0 +1,217 @@
+use tako::messages::common::WorkerConfiguration;
+
+use crate::client::resources::GenericResourceRequest;
+use crate::common::WrappedRcRefCell;
+use crate::worker::parser::{
+    format_resource_definition, parse_resource_definition, GenericResourceDef, GenericResourceKind,
+};
+use crate::Map;
+
+/// Key of `WorkerConfiguration::extra` that contains generic resources of a worker,
+/// definitions are separated by ';'
+pub const WORKER_RESOURCES_KEY: &str = "GENERIC_RESOURCES";
+
+/// Joins definitions into the value stored in `WorkerConfiguration::extra`
+pub fn format_resource_definitions(defs: &[GenericResourceDef]) -> String {
+    defs.iter()
+        .map(format_resource_definition)
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
+pub fn worker_resource_definitions(configuration: &WorkerConfiguration) -> Vec<GenericResourceDef> {
+    configuration
+        .extra
+        .get(WORKER_RESOURCES_KEY)
+        .map(|defs| {
+            defs.split(';')
+                .filter_map(|def| parse_resource_definition(def).ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+pub fn format_worker_resources(configuration: &WorkerConfiguration) -> String {
+    let defs = worker_resource_definitions(configuration);
+    if defs.is_empty() {
+        "None".to_string()
+    } else {
+        defs.iter()
+            .map(format_resource_definition)
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Units of a generic resource that were assigned to a task
+#[derive(Debug, PartialEq)]
+pub enum AllocatedUnits {
+    Sum(u32),
+    Indices(Vec<u32>),
+}
+
+/// Generic resources are not known to tako, tasks take them when they are started
+/// on the worker. A task is rejected by the worker when its resources are not available.
+pub struct GenericResourcePool {
+    free: Map<String, GenericResourceKind>,
+}
+
+pub type GenericResourcePoolRef = WrappedRcRefCell<GenericResourcePool>;
+
+impl GenericResourcePool {
+    pub fn new(defs: Vec<GenericResourceDef>) -> Self {
+        GenericResourcePool {
+            free: defs.into_iter().map(|def| (def.name, def.kind)).collect(),
+        }
+    }
+
+    /// Takes all requested resources or nothing, the error contains a reason of the rejection
+    pub fn allocate(
+        &mut self,
+        requests: &[GenericResourceRequest],
+    ) -> Result<Vec<(String, AllocatedUnits)>, String> {
+        for request in requests {
+            let available = match self.free.get(&request.name) {
+                Some(GenericResourceKind::Sum(amount)) => *amount,
+                Some(GenericResourceKind::Indices(indices)) => indices.len() as u32,
+                None => return Err(format!("Worker has no resource '{}'", request.name)),
+            };
+            if available < request.amount {
+                return Err(format!(
+                    "Worker has not enough free units of resource '{}'",
+                    request.name
+                ));
+            }
+        }
+        Ok(requests
+            .iter()
+            .map(|request| {
+                let units = match self.free.get_mut(&request.name).unwrap() {
+                    GenericResourceKind::Sum(amount) => {
+                        *amount -= request.amount;
+                        AllocatedUnits::Sum(request.amount)
+                    }
+                    GenericResourceKind::Indices(indices) => {
+                        AllocatedUnits::Indices(indices.drain(..request.amount as usize).collect())
+                    }
+                };
+                (request.name.clone(), units)
+            })
+            .collect())
+    }
+
+    pub fn release(&mut self, allocation: Vec<(String, AllocatedUnits)>) {
+        for (name, units) in allocation {
+            match (self.free.get_mut(&name), units) {
+                (Some(GenericResourceKind::Sum(amount)), AllocatedUnits::Sum(n)) => *amount += n,
+                (Some(GenericResourceKind::Indices(indices)), AllocatedUnits::Indices(units)) => {
+                    indices.extend(units);
+                    indices.sort_unstable();
+                }
+                _ => panic!("Released units do not match resource '{}'", name),
+            }
+        }
+    }
+}
+
+/// Resources taken by a running task, they are returned to the pool when the task ends
+pub struct GenericResourceAllocation {
+    pool_ref: GenericResourcePoolRef,
+    units: Vec<(String, AllocatedUnits)>,
+}
+
+impl GenericResourceAllocation {
+    pub fn new(
+        pool_ref: &GenericResourcePoolRef,
+        requests: &[GenericResourceRequest],
+    ) -> Result<Self, String> {
+        let units = pool_ref.get_mut().allocate(requests)?;
+        Ok(GenericResourceAllocation {
+            pool_ref: pool_ref.clone(),
+            units,
+        })
+    }
+
+    pub fn units(&self) -> &[(String, AllocatedUnits)] {
+        &self.units
+    }
+}
+
+impl Drop for GenericResourceAllocation {
+    fn drop(&mut self) {
+        self.pool_ref
+            .get_mut()
+            .release(std::mem::take(&mut self.units));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::client::resources::GenericResourceRequest;
+    use crate::worker::parser::parse_resource_definition;
+    use crate::worker::resources::{
+        AllocatedUnits, GenericResourceAllocation, GenericResourcePool, GenericResourcePoolRef,
+    };
+
+    fn request(name: &str, amount: u32) -> GenericResourceRequest {
+        GenericResourceRequest {
+            name: name.to_string(),
+            amount,
+        }
+    }
+
+    fn pool_ref() -> GenericResourcePoolRef {
+        GenericResourcePoolRef::wrap(GenericResourcePool::new(vec![
+            parse_resource_definition("mem=100").unwrap(),
+            parse_resource_definition("gpu=[0,1,2]").unwrap(),
+        ]))
+    }
+
+    #[test]
+    fn test_allocate_resources() {
+        let pool_ref = pool_ref();
+        let a1 =
+            GenericResourceAllocation::new(&pool_ref, &[request("mem", 60), request("gpu", 2)])
+                .unwrap();
+        assert_eq!(
+            a1.units(),
+            &[
+                ("mem".to_string(), AllocatedUnits::Sum(60)),
+                ("gpu".to_string(), AllocatedUnits::Indices(vec![0, 1]))
+            ]
+        );
+        assert!(GenericResourceAllocation::new(&pool_ref, &[request("mem", 50)]).is_err());
+        // Nothing is taken when the request cannot be fully satisfied
+        assert!(GenericResourceAllocation::new(
+            &pool_ref,
+            &[request("gpu", 1), request("mem", 50)]
+        )
+        .is_err());
+        let a2 = GenericResourceAllocation::new(&pool_ref, &[request("gpu", 1)]).unwrap();
+        assert_eq!(
+            a2.units(),
+            &[("gpu".to_string(), AllocatedUnits::Indices(vec![2]))]
+        );
+        assert!(GenericResourceAllocation::new(&pool_ref, &[request("gpu", 1)]).is_err());
+    }
+
+    #[test]
+    fn test_release_resources() {
+        let pool_ref = pool_ref();
+        let allocation =
+            GenericResourceAllocation::new(&pool_ref, &[request("mem", 100), request("gpu", 3)])
+                .unwrap();
+        drop(allocation);
+        assert!(GenericResourceAllocation::new(
+            &pool_ref,
+            &[request("mem", 100), request("gpu", 3)]
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn test_unknown_resource() {
+        let pool_ref = pool_ref();
+        assert!(GenericResourceAllocation::new(&pool_ref, &[request("fpga", 1)]).is_err());
+    }
+}
@@ -4,6 +4,7 @@ use std::str::FromStr;
 
 use anyhow::{anyhow, Context};
 use bstr::{BString, ByteSlice};
+use chrono::{DateTime, Utc};
 use clap::Clap;
 use futures::TryFutureExt;
 use humantime::format_rfc3339;
@@ -17,29 +18,54 @@ use tokio::net::lookup_host;
 use tokio::task::LocalSet;
 
 use crate::client::globalsettings::GlobalSettings;
-use crate::common::env::{HQ_CPUS, HQ_INSTANCE_ID, HQ_JOB_ID, HQ_PIN, HQ_SUBMIT_DIR, HQ_TASK_ID};
+use crate::common::env::{
+    HQ_CPUS, HQ_ENTRY_FIELD_PREFIX, HQ_INSTANCE_ID, HQ_JOB_ID, HQ_PARAM_PREFIX, HQ_PIN,
+    HQ_RESOURCE_VALUES_PREFIX, HQ_SUBMIT_DIR, HQ_TASK_ID,
+};
 use crate::common::error::error;
-use crate::common::serverdir::ServerDir;
+use crate::common::labels::{format_labels, validate_label, worker_labels, WORKER_LABELS_KEY};
+use crate::common::serverdir::{AccessRecord, ServerDir};
 use crate::common::timeutils::ArgDuration;
-use crate::transfer::messages::TaskBody;
+use crate::transfer::connection::ClientConnection;
+use crate::transfer::messages::{
+    encode_task_failure, FromClientMessage, StopWorkerMessage, TaskBody, TaskFailure,
+    ToClientMessage, WaitForDrainMessage, WorkerSelector,
+};
 use crate::transfer::stream::ChannelId;
 use crate::worker::hwdetect::detect_resource;
+use crate::worker::lifetime::{remaining_lifetime, worker_end_time, WORKER_END_TIME_KEY};
+use crate::worker::manager::{
+    parse_pbs_job_info, parse_slurm_job_info, ManagerJobInfo, MANAGER_NODES_KEY, MANAGER_QUEUE_KEY,
+    MANAGER_WALLTIME_KEY, QSTAT_PATH_ENV, SCONTROL_PATH_ENV,
+};
 use crate::worker::output::print_worker_configuration;
-use crate::worker::parser::parse_cpu_definition;
+use crate::worker::parser::{parse_cpu_definition, parse_resource_definition, GenericResourceDef};
+use crate::worker::resources::{
+    format_resource_definitions, worker_resource_definitions, AllocatedUnits,
+    GenericResourceAllocation, GenericResourcePool, GenericResourcePoolRef, WORKER_RESOURCES_KEY,
+};
 use crate::worker::streamer::StreamSender;
 use crate::worker::streamer::StreamerRef;
-use crate::{JobId, JobTaskId, Map};
+use crate::{rpc_call, JobId, JobTaskId, Map, WorkerId};
 use hashbrown::HashMap;
+use std::cell::Cell;
 use std::future::Future;
+use std::os::unix::process::ExitStatusExt;
 use std::pin::Pin;
+use std::process::ExitStatus;
+use std::rc::Rc;
 use std::time::Duration;
 use tako::common::error::DsError;
 use tako::InstanceId;
 use tokio::io::AsyncReadExt;
-use tokio::sync::oneshot;
+use tokio::process::{Child, Command};
+use tokio::sync::{oneshot, Notify};
 
 const STDIO_BUFFER_SIZE: usize = 16 * 1024; // 16kB
 
+/// How long a task has to terminate after SIGTERM when its time limit is exceeded
+const TIME_LIMIT_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
 #[derive(Clap)]
 pub enum ManagerOpts {
     Detect,
@@ -81,9 +107,32 @@ pub struct WorkerStartOpts {
     #[clap(long)]
     idle_timeout: Option<ArgDuration>,
 
+    /// Stop the worker after the given time (e.g. "4h"),
+    /// it should not exceed the walltime of the PBS/SLURM job that runs the worker
+    #[clap(long)]
+    time_limit: Option<ArgDuration>,
+
     /// What HPC job manager should be used by the worker.
     #[clap(long, default_value = "detect", possible_values = &["detect", "slurm", "pbs", "none"])]
     manager: ManagerOpts,
+
+    /// Label of the worker, it can be used multiple times (e.g. `--label bigmem --label ssd`)
+    #[clap(long = "label", multiple_occurrences(true))]
+    labels: Vec<String>,
+
+    /// Generic resource of the worker, it can be used multiple times
+    /// (e.g. `--resource mem=64000 --resource "gpu=[0,1]"`)
+    #[clap(long = "resource", multiple_occurrences(true))]
+    resources: Vec<String>,
+
+    /// Shell command that is executed before the worker connects to the server,
+    /// the worker is not started when the command fails
+    #[clap(long)]
+    on_start: Option<String>,
+
+    /// Shell command that is executed after the worker is stopped
+    #[clap(long)]
+    on_stop: Option<String>,
 }
 
 /// Replace placeholders in user-defined program attributes
@@ -95,6 +144,22 @@ fn replace_placeholders(program: &mut ProgramDefinition) {
             .unwrap_or_default(),
     );
 
+    // Fields of structured entries and parameters of sweeps,
+    // e.g. `HQ_ENTRY_input` is available as `%{ENTRY:input}` and `HQ_PARAM_lr` as `%{PARAM:lr}`
+    let entry_fields: Vec<(String, String)> = program
+        .env
+        .iter()
+        .filter_map(|(key, value)| {
+            [(HQ_ENTRY_FIELD_PREFIX, "ENTRY"), (HQ_PARAM_PREFIX, "PARAM")]
+                .iter()
+                .find_map(|(prefix, kind)| {
+                    key.strip_prefix(prefix.as_bytes())
+                        .map(|field| format!("%{{{}:{}}}", kind, field.as_bstr()))
+                })
+                .map(|placeholder| (placeholder, value.to_string()))
+        })
+        .collect();
+
     let mut placeholder_map = HashMap::new();
     placeholder_map.insert(
         "%{JOB_ID}",
@@ -113,6 +178,9 @@ fn replace_placeholders(program: &mut ProgramDefinition) {
         program.env[&BString::from(HQ_SUBMIT_DIR)].to_string(),
     );
     placeholder_map.insert("%{DATE}", date);
+    for (placeholder, value) in &entry_fields {
+        placeholder_map.insert(placeholder.as_str(), value.clone());
+    }
 
     let replace = |replacement_map: &HashMap<&str, String>, path: &PathBuf| -> PathBuf {
         let mut result: String = path.to_str().unwrap().into();
@@ -139,6 +207,15 @@ fn replace_placeholders(program: &mut ProgramDefinition) {
         .map_filename(|path| submit_dir.join(replace(&placeholder_map, &path)));
     program.stderr = std::mem::take(&mut program.stderr)
         .map_filename(|path| submit_dir.join(replace(&placeholder_map, &path)));
+
+    // Entry fields and parameters may be also used in arguments of the program
+    for arg in program.args.iter_mut() {
+        for (placeholder, value) in &entry_fields {
+            if arg.find(placeholder).is_some() {
+                *arg = arg.replace(placeholder, value).into();
+            }
+        }
+    }
 }
 
 async fn resend_stdio(
@@ -163,7 +240,68 @@ async fn resend_stdio(
     Ok(())
 }
 
-async fn launcher_main(streamer_ref: StreamerRef, task_ref: TaskRef) -> tako::Result<()> {
+/// State of the worker that is shared by launchers of tasks
+struct LauncherContext {
+    streamer_ref: StreamerRef,
+    resource_pool_ref: GenericResourcePoolRef,
+    labels: Vec<String>,
+    end_time: Option<DateTime<Utc>>,
+    /// Worker was asked to drain, it does not start new tasks
+    draining: Cell<bool>,
+    /// Number of tasks that were started by the worker and did not end yet
+    running_tasks: Cell<u32>,
+    /// Notified whenever a task started by the worker ends
+    task_ended: Notify,
+}
+
+/// Counts a started task as running until the task ends
+struct RunningTaskGuard {
+    context: Rc<LauncherContext>,
+}
+
+impl RunningTaskGuard {
+    fn new(context: &Rc<LauncherContext>) -> Self {
+        context.running_tasks.set(context.running_tasks.get() + 1);
+        RunningTaskGuard {
+            context: context.clone(),
+        }
+    }
+}
+
+impl Drop for RunningTaskGuard {
+    fn drop(&mut self) {
+        let running_tasks = &self.context.running_tasks;
+        running_tasks.set(running_tasks.get() - 1);
+        self.context.task_ended.notify_one();
+    }
+}
+
+/// Checks whether the worker can run the task, the error contains a reason of the rejection
+fn check_task_requirements(context: &LauncherContext, body: &TaskBody) -> Result<(), String> {
+    if context.draining.get() {
+        return Err("Worker is draining".to_string());
+    }
+    if let Some(label) = body
+        .required_labels
+        .iter()
+        .find(|label| !context.labels.contains(label))
+    {
+        return Err(format!("Worker does not have label '{}'", label));
+    }
+    if let (Some(time_request), Some(end_time)) = (body.time_request, context.end_time) {
+        let remaining = (end_time - Utc::now()).to_std().unwrap_or_default();
+        if remaining < time_request {
+            return Err(format!(
+                "Remaining lifetime of the worker {} is shorter than the time request {}",
+                humantime::format_duration(Duration::from_secs(remaining.as_secs())),
+                humantime::format_duration(time_request)
+            ));
+        }
+    }
+    Ok(())
+}
+
+async fn launcher_main(context: Rc<LauncherContext>, task_ref: TaskRef) -> tako::Result<()> {
     log::debug!(
         "Starting program launcher {} {:?} {:?}",
         task_ref.get().id,
@@ -171,17 +309,25 @@ async fn launcher_main(streamer_ref: StreamerRef, task_ref: TaskRef) -> tako::Re
         task_ref.get().resource_allocation()
     );
 
-    let (program, job_id, job_task_id, instance_id): (
+    let (program, job_id, job_task_id, instance_id, time_limit, _generic_allocation): (
         ProgramDefinition,
         JobId,
         JobTaskId,
         InstanceId,
+        Option<Duration>,
+        GenericResourceAllocation,
     ) = {
         let task = task_ref.get();
         let body: TaskBody = tako::transfer::auth::deserialize(&task.spec)?;
         let allocation = task
             .resource_allocation()
             .expect("Missing resource allocation for running task");
+        check_task_requirements(&context, &body)
+            .map_err(|reason| task_failure(TaskFailure::Rejected(reason)))?;
+        // Resources are returned to the pool when the allocation is dropped at the end of the task
+        let generic_allocation =
+            GenericResourceAllocation::new(&context.resource_pool_ref, &body.resources)
+                .map_err(|reason| task_failure(TaskFailure::Rejected(reason)))?;
         let mut program = body.program;
 
         if body.pin {
@@ -197,11 +343,41 @@ async fn launcher_main(streamer_ref: StreamerRef, task_ref: TaskRef) -> tako::Re
             .env
             .insert(HQ_INSTANCE_ID.into(), task.instance_id.to_string().into());
 
+        for (name, units) in generic_allocation.units() {
+            if let AllocatedUnits::Indices(indices) = units {
+                program.env.insert(
+                    format!("{}{}", HQ_RESOURCE_VALUES_PREFIX, name).into(),
+                    indices
+                        .iter()
+                        .map(|i| i.to_string())
+                        .collect::<Vec<_>>()
+                        .join(",")
+                        .into(),
+                );
+            }
+        }
+
         replace_placeholders(&mut program);
-        (program, body.job_id, body.task_id, task.instance_id)
+        (
+            program,
+            body.job_id,
+            body.task_id,
+            task.instance_id,
+            body.time_limit,
+            generic_allocation,
+        )
     };
-
-    run_task(streamer_ref, &program, job_id, job_task_id, instance_id).await
+    let _running_guard = RunningTaskGuard::new(&context);
+
+    run_task(
+        context.streamer_ref.clone(),
+        &program,
+        job_id,
+        job_task_id,
+        instance_id,
+        time_limit,
+    )
+    .await
 }
 
 /// Zero-worker mode measures pure overhead of HyperQueue.
@@ -213,10 +389,116 @@ async fn run_task(
     _job_id: JobId,
     _job_task_id: JobTaskId,
     _instance_id: InstanceId,
+    _time_limit: Option<Duration>,
 ) -> tako::Result<()> {
     Ok(())
 }
 
+/// Waits until the child terminates.
+/// When it runs longer than `time_limit`, its process group is terminated by SIGTERM and after
+/// a grace period by SIGKILL.
+///
+/// A task that is terminated because of its time limit always fails with `TaskFailure::Timeout`,
+/// even if it exits successfully during the grace period, because its work was interrupted.
+///
+/// When the returned future is dropped before the child terminates (e.g. when the worker stops
+/// because of its time limit), the process group of the child is killed.
+#[cfg(not(feature = "zero-worker"))]
+async fn wait_for_child(
+    child: &mut Child,
+    time_limit: Option<Duration>,
+) -> tako::Result<ExitStatus> {
+    let mut guard = ProcessGroupGuard::new(child);
+    let result = wait_for_child_with_limit(child, time_limit).await;
+    guard.disarm();
+    result
+}
+
+#[cfg(not(feature = "zero-worker"))]
+async fn wait_for_child_with_limit(
+    child: &mut Child,
+    time_limit: Option<Duration>,
+) -> tako::Result<ExitStatus> {
+    let time_limit = match time_limit {
+        Some(time_limit) => time_limit,
+        None => return Ok(child.wait().await?),
+    };
+    if let Ok(status) = tokio::time::timeout(time_limit, child.wait()).await {
+        return Ok(status?);
+    }
+    log::debug!("Time limit exceeded, sending SIGTERM to task");
+    signal_process_group(child, libc::SIGTERM);
+    if tokio::time::timeout(TIME_LIMIT_GRACE_PERIOD, child.wait())
+        .await
+        .is_err()
+    {
+        log::debug!("Task did not terminate after SIGTERM, killing it");
+        signal_process_group(child, libc::SIGKILL);
+        child.wait().await?;
+    }
+    Err(task_failure(TaskFailure::Timeout(time_limit)))
+}
+
+/// Kills the process group of a running task when it is dropped
+#[cfg(not(feature = "zero-worker"))]
+struct ProcessGroupGuard {
+    pid: Option<u32>,
+}
+
+#[cfg(not(feature = "zero-worker"))]
+impl ProcessGroupGuard {
+    fn new(child: &Child) -> Self {
+        ProcessGroupGuard { pid: child.id() }
+    }
+
+    /// The child has terminated, there is nothing to kill
+    fn disarm(&mut self) {
+        self.pid = None;
+    }
+}
+
+#[cfg(not(feature = "zero-worker"))]
+impl Drop for ProcessGroupGuard {
+    fn drop(&mut self) {
+        if let Some(pid) = self.pid {
+            log::debug!("Killing process group {} of an interrupted task", pid);
+            unsafe {
+                libc::kill(-(pid as libc::pid_t), libc::SIGKILL);
+            }
+        }
+    }
+}
+
+/// Sends a signal to all processes in the process group of the child
+#[cfg(not(feature = "zero-worker"))]
+fn signal_process_group(child: &Child, signal: libc::c_int) {
+    if let Some(pid) = child.id() {
+        unsafe {
+            libc::kill(-(pid as libc::pid_t), signal);
+        }
+    }
+}
+
+fn task_failure(failure: TaskFailure) -> DsError {
+    DsError::GenericError(encode_task_failure(&failure))
+}
+
+#[cfg(not(feature = "zero-worker"))]
+fn spawn_child(command: &mut Command) -> tako::Result<Child> {
+    // The task runs in its own process group, so that its subprocesses can be terminated with it
+    unsafe {
+        command.pre_exec(|| {
+            if libc::setpgid(0, 0) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    command
+        .spawn()
+        .map_err(|e| task_failure(TaskFailure::SpawnError(e.to_string())))
+}
+
 #[cfg(not(feature = "zero-worker"))]
 async fn run_task(
     streamer_ref: StreamerRef,
@@ -224,6 +506,7 @@ async fn run_task(
     job_id: JobId,
     job_task_id: JobTaskId,
     instance_id: InstanceId,
+    time_limit: Option<Duration>,
 ) -> tako::Result<()> {
     let mut command = command_from_definitions(program)?;
 
@@ -232,7 +515,7 @@ async fn run_task(
     {
         let streamer_error =
             |e: DsError| DsError::GenericError(format!("Streamer: {:?}", e.to_string()));
-        let mut child = command.spawn()?;
+        let mut child = spawn_child(&mut command)?;
         let (close_sender, close_responder) = oneshot::channel();
         let stream = streamer_ref.get_mut().get_stream(
             &streamer_ref,
@@ -249,7 +532,7 @@ async fn run_task(
             let stderr = child.stderr.take();
 
             let response = tokio::try_join!(
-                child.wait().map_err(DsError::from),
+                wait_for_child(&mut child, time_limit),
                 resend_stdio(job_id, job_task_id, 0, stdout, stream.clone())
                     .map_err(streamer_error),
                 resend_stdio(job_id, job_task_id, 1, stderr, stream.clone())
@@ -266,25 +549,27 @@ async fn run_task(
         )?
         .0
     } else {
-        command.status().await?
+        let mut child = spawn_child(&mut command)?;
+        wait_for_child(&mut child, time_limit).await?
     };
     if !status.success() {
-        let code = status.code().unwrap_or(-1);
-        return tako::Result::Err(DsError::GenericError(format!(
-            "Program terminated with exit code {}",
-            code
-        )));
+        let failure = match (status.code(), status.signal()) {
+            (Some(code), _) => TaskFailure::ExitCode(code),
+            (None, Some(signal)) => TaskFailure::Signal(signal),
+            (None, None) => TaskFailure::Other("Program terminated".to_string()),
+        };
+        return Err(task_failure(failure));
     }
     Ok(())
 }
 
 fn launcher(
-    streamer_ref: &StreamerRef,
+    context: &Rc<LauncherContext>,
     task_ref: &TaskRef,
 ) -> Pin<Box<dyn Future<Output = tako::Result<()>> + 'static>> {
     let task_ref = task_ref.clone();
-    let streamer_ref = streamer_ref.clone();
-    Box::pin(async move { launcher_main(streamer_ref, task_ref).await })
+    let context = context.clone();
+    Box::pin(async move { launcher_main(context, task_ref).await })
 }
 
 pub async fn start_hq_worker(
@@ -300,9 +585,28 @@ pub async fn start_hq_worker(
             server_dir.access_filename()
         )
     })?;
+
+    if let Some(command) = &opts.on_start {
+        run_hook("start", command).await?;
+    }
+    // The stop hook runs whenever the start hook succeeded, even if the worker fails
+    let on_stop = opts.on_stop.clone();
+    let result = run_hq_worker(gsettings, opts, record).await;
+    if let Some(command) = on_stop {
+        if let Err(e) = run_hook("stop", &command).await {
+            log::error!("{:?}", e);
+        }
+    }
+    result
+}
+
+async fn run_hq_worker(
+    gsettings: &GlobalSettings,
+    opts: WorkerStartOpts,
+    record: AccessRecord,
+) -> anyhow::Result<()> {
     let server_address = format!("{}:{}", record.host(), record.worker_port());
     log::info!("Connecting to: {}", server_address);
-
     let configuration = gather_configuration(opts)?;
 
     let server_addr = lookup_host(&server_address)
@@ -317,28 +621,153 @@ pub async fn start_hq_worker(
         record.tako_secret_key().clone(),
     );
 
+    let context = Rc::new(LauncherContext {
+        streamer_ref,
+        resource_pool_ref: GenericResourcePoolRef::wrap(GenericResourcePool::new(
+            worker_resource_definitions(&configuration),
+        )),
+        labels: worker_labels(&configuration)
+            .into_iter()
+            .map(|label| label.to_string())
+            .collect(),
+        end_time: worker_end_time(&configuration),
+        draining: Cell::new(false),
+        running_tasks: Cell::new(0),
+        task_ended: Notify::new(),
+    });
+    // Without this connection the worker could not be drained
+    let connection = ClientConnection::connect_to_server(&record)
+        .await
+        .context("Cannot connect to the server")?;
+
     log::debug!("Starting Tako worker ...");
     let ((worker_id, configuration), worker_future) = run_worker(
         server_addr,
         configuration,
         Some(record.tako_secret_key().clone()),
-        Box::new(move |task_ref| launcher(&streamer_ref, task_ref)),
+        Box::new({
+            let context = context.clone();
+            move |task_ref| launcher(&context, task_ref)
+        }),
     )
     .await?;
+    let lifetime = remaining_lifetime(&configuration, Utc::now());
     print_worker_configuration(gsettings, worker_id, configuration);
 
+    let time_limit_future = async move {
+        match lifetime {
+            Some(remaining) => tokio::time::sleep(remaining).await,
+            None => futures::future::pending().await,
+        }
+    };
+
     let local_set = LocalSet::new();
     local_set
         .run_until(async move {
             tokio::select! {
                 () = worker_future => {}
                 () = streamer_future => {}
+                () = drain_worker(connection, worker_id, context) => {}
+                () = time_limit_future => {
+                    log::info!("Time limit of the worker was reached, stopping the worker");
+                }
             }
         })
         .await;
+    // Running tasks are dropped together with the local set, their processes are killed
+    // and the server schedules them to other workers
+    drop(local_set);
+    Ok(())
+}
+
+/// Runs a start/stop hook of the worker by `sh -c <command>`
+async fn run_hook(name: &str, command: &str) -> anyhow::Result<()> {
+    log::info!("Running {} hook: {}", name, command);
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .status()
+        .await
+        .with_context(|| format!("Cannot run worker {} hook '{}'", name, command))?;
+    if !status.success() {
+        anyhow::bail!("Worker {} hook '{}' failed with {}", name, command, status);
+    }
     Ok(())
 }
 
+/// Waits until the server asks the worker to drain. Then the worker rejects new tasks
+/// and when its running tasks end, it asks the server to stop it.
+async fn drain_worker(
+    mut connection: ClientConnection,
+    worker_id: WorkerId,
+    context: Rc<LauncherContext>,
+) {
+    let result = async {
+        let message = FromClientMessage::WaitForDrain(WaitForDrainMessage { worker_id });
+        rpc_call!(connection, message, ToClientMessage::WorkerDraining).await?;
+        log::info!("Worker is draining, new tasks are rejected");
+        context.draining.set(true);
+
+        while context.running_tasks.get() > 0 {
+            context.task_ended.notified().await;
+        }
+        log::info!("Running tasks of the worker have ended, the worker is stopped");
+        let message = FromClientMessage::StopWorker(StopWorkerMessage {
+            selector: WorkerSelector::Specific(vec![worker_id]),
+        });
+        rpc_call!(connection, message, ToClientMessage::StopWorkerResponse(_)).await
+    }
+    .await;
+    if let Err(e) = result {
+        // The connection fails when the worker is lost by the server or when the server ends,
+        // the worker ends together with its connection to the server
+        log::debug!("Drain of the worker was interrupted: {}", e);
+        futures::future::pending::<()>().await;
+    }
+}
+
+fn run_manager_command(program: &str, args: &[&str]) -> anyhow::Result<String> {
+    log::debug!("Running {} {:?}", program, args);
+    let output = std::process::Command::new(program)
+        .args(args)
+        .output()
+        .with_context(|| format!("Cannot run {}", program))?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "{} exited with {}: {}",
+            program,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Stores the job information into the worker configuration, the remaining walltime
+/// of the job becomes the time limit of the worker
+fn insert_manager_job_info(extra: &mut Map<String, String>, info: ManagerJobInfo) {
+    if let Some(remaining) = info.remaining_walltime() {
+        if let Ok(remaining) = chrono::Duration::from_std(remaining) {
+            extra.insert(
+                WORKER_END_TIME_KEY.to_string(),
+                (Utc::now() + remaining).to_rfc3339(),
+            );
+        }
+    }
+    if let Some(walltime) = info.walltime {
+        extra.insert(
+            MANAGER_WALLTIME_KEY.to_string(),
+            humantime::format_duration(walltime).to_string(),
+        );
+    }
+    if let Some(queue) = info.queue {
+        extra.insert(MANAGER_QUEUE_KEY.to_string(), queue);
+    }
+    if let Some(nodes) = info.nodes {
+        extra.insert(MANAGER_NODES_KEY.to_string(), nodes);
+    }
+}
+
 fn try_get_pbs_info() -> anyhow::Result<Map<String, String>> {
     log::debug!("Detecting PBS environment");
 
@@ -349,9 +778,15 @@ fn try_get_pbs_info() -> anyhow::Result<Map<String, String>> {
 
     let mut result = Map::with_capacity(2);
     result.insert("MANAGER".to_string(), "PBS".to_string());
-    result.insert("MANAGER_JOB_ID".to_string(), manager_job_id);
+    result.insert("MANAGER_JOB_ID".to_string(), manager_job_id.clone());
 
-    // TODO: Run "qstat -f -F json $PBS_JOBID" to get walltime
+    let qstat = std::env::var(QSTAT_PATH_ENV).unwrap_or_else(|_| "qstat".to_string());
+    match run_manager_command(&qstat, &["-f", "-F", "json", &manager_job_id])
+        .and_then(|output| parse_pbs_job_info(&output, &manager_job_id))
+    {
+        Ok(info) => insert_manager_job_info(&mut result, info),
+        Err(e) => log::warn!("Cannot get information about PBS job: {:?}", e),
+    }
 
     log::info!("PBS environment detected");
     Ok(result)
@@ -368,9 +803,13 @@ fn try_get_slurm_info() -> anyhow::Result<Map<String, String>> {
 
     let mut result = Map::with_capacity(2);
     result.insert("MANAGER".to_string(), "SLURM".to_string());
-    result.insert("MANAGER_JOB_ID".to_string(), manager_job_id);
+    result.insert("MANAGER_JOB_ID".to_string(), manager_job_id.clone());
 
-    // TODO: Get walltime info
+    let scontrol = std::env::var(SCONTROL_PATH_ENV).unwrap_or_else(|_| "scontrol".to_string());
+    match run_manager_command(&scontrol, &["show", "job", &manager_job_id]) {
+        Ok(output) => insert_manager_job_info(&mut result, parse_slurm_job_info(&output)),
+        Err(e) => log::warn!("Cannot get information about SLURM job: {:?}", e),
+    }
 
     log::info!("SLURM environment detected");
     Ok(result)
@@ -410,7 +849,41 @@ fn gather_configuration(opts: WorkerStartOpts) -> anyhow::Result<WorkerConfigura
         (tmpdir.join("work"), tmpdir.join("logs"))
     };
 
-    let extra = gather_manager_info(opts.manager)?;
+    for label in &opts.labels {
+        validate_label(label)?;
+    }
+    let mut resource_defs: Vec<GenericResourceDef> = Vec::new();
+    for resource in &opts.resources {
+        let def = parse_resource_definition(resource)
+            .with_context(|| format!("Invalid resource definition '{}'", resource))?;
+        if resource_defs.iter().any(|d| d.name == def.name) {
+            anyhow::bail!("Resource {} is defined more than once", def.name);
+        }
+        resource_defs.push(def);
+    }
+
+    let mut extra = gather_manager_info(opts.manager)?;
+    if !opts.labels.is_empty() {
+        extra.insert(WORKER_LABELS_KEY.to_string(), format_labels(&opts.labels));
+    }
+    if !resource_defs.is_empty() {
+        extra.insert(
+            WORKER_RESOURCES_KEY.to_string(),
+            format_resource_definitions(&resource_defs),
+        );
+    }
+    if let Some(time_limit) = opts.time_limit {
+        let end_time = Utc::now() + chrono::Duration::from_std(time_limit.into_duration())?;
+        // The worker cannot outlive the PBS/SLURM job
+        let end_time = match extra
+            .get(WORKER_END_TIME_KEY)
+            .and_then(|time| DateTime::parse_from_rfc3339(time).ok())
+        {
+            Some(job_end_time) if job_end_time < end_time => job_end_time.with_timezone(&Utc),
+            _ => end_time,
+        };
+        extra.insert(WORKER_END_TIME_KEY.to_string(), end_time.to_rfc3339());
+    }
 
     Ok(WorkerConfiguration {
         resources,
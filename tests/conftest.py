@@ -121,9 +121,14 @@ class HqEnv(Env):
             "start",
         ]
 
-    def start_server(self, server_dir="hq-server", args=None) -> subprocess.Popen:
+    def start_server(
+        self, server_dir="hq-server", args=None, env=None
+    ) -> subprocess.Popen:
         self.server_dir = os.path.join(self.work_path, server_dir)
-        env = self.make_default_env()
+        server_env = self.make_default_env()
+        if env:
+            server_env.update(env)
+        env = server_env
         server_args = self.server_args(self.server_dir, debug=self.debug)
         if args:
             server_args += args
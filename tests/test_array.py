@@ -187,3 +187,107 @@ def test_array_mix_with_simple_jobs(hq_env: HqEnv):
         assert table[i][0] == str(i)
         assert table[i][2] == "FINISHED"
         assert table[i][3] == "4" if i % 2 == 1 else "1"
+
+
+def test_array_params(hq_env: HqEnv):
+    hq_env.start_server()
+    hq_env.start_worker(cpus=2)
+    table = hq_env.command(
+        [
+            "submit",
+            "--param",
+            "lr=0.1,0.01",
+            "--param",
+            "depth=3..5",
+            "--stdout=out.%{PARAM:lr}.%{PARAM:depth}",
+            "--",
+            "bash",
+            "-c",
+            "echo $HQ_PARAM_lr-$HQ_PARAM_depth %{PARAM:depth}",
+        ],
+        as_table=True,
+    )
+    table.check_value_row("Tasks", "6; Ids: 0-5")
+    wait_for_job_state(hq_env, 1, "FINISHED")
+
+    for lr in ("0.1", "0.01"):
+        for depth in ("3", "4", "5"):
+            with open(f"out.{lr}.{depth}") as f:
+                assert f.read() == f"{lr}-{depth} {depth}\n"
+
+    table = hq_env.command(["job", "1", "--tasks"], as_table=True)[JOB_TABLE_ROWS:]
+    assert table.get_column_value("Parameters") == [
+        "lr=0.1 depth=3",
+        "lr=0.1 depth=4",
+        "lr=0.1 depth=5",
+        "lr=0.01 depth=3",
+        "lr=0.01 depth=4",
+        "lr=0.01 depth=5",
+    ]
+
+
+def test_array_params_invalid(hq_env: HqEnv):
+    hq_env.start_server()
+    hq_env.command(
+        ["submit", "--param", "x=1", "--param", "x=2", "hostname"],
+        expect_fail="Parameter x is defined more than once",
+    )
+    hq_env.command(
+        ["submit", "--param", "x=1", "--array", "1-3", "hostname"],
+        expect_fail="Only one of",
+    )
+    hq_env.command(
+        ["submit", "--param", "x-y=1", "hostname"],
+        expect_fail="Invalid parameter name 'x-y'",
+    )
+    hq_env.command(
+        ["submit", "--param", "x=1..1000", "--param", "y=1..1001", "hostname"],
+        expect_fail="Parameters have more than 1000000 combinations",
+    )
+
+
+def test_array_max_parallel(hq_env: HqEnv):
+    hq_env.start_server()
+    hq_env.start_worker(cpus=4)
+    table = hq_env.command(
+        ["submit", "--array=1-6%2", "--", "sleep", "1"],
+        as_table=True,
+    )
+    table.check_value_row("Max parallel", "2")
+    time.sleep(0.5)
+
+    table = hq_env.command(["job", "1"], as_table=True)
+    states = table.get_row_value("State").split("\n")
+    assert "RUNNING (2)" in states
+    assert "WAITING (4)" in states
+
+    wait_for_job_state(hq_env, 1, "FINISHED")
+    table = hq_env.command(["job", "1", "--tasks"], as_table=True)[JOB_TABLE_ROWS + 1 :]
+    assert set(table.get_column_value("State")) == {"FINISHED"}
+
+
+def test_array_max_parallel_cancel_tasks(hq_env: HqEnv):
+    hq_env.start_server()
+    hq_env.start_worker(cpus=4)
+    hq_env.command(["submit", "--array=0-3", "--max-parallel=1", "--", "sleep", "100"])
+    time.sleep(0.5)
+    hq_env.command(["cancel", "1", "--tasks=0"])
+    time.sleep(0.5)
+
+    table = hq_env.command(["job", "1"], as_table=True)
+    states = table.get_row_value("State").split("\n")
+    assert "RUNNING (1)" in states
+    assert "CANCELED (1)" in states
+    assert "WAITING (2)" in states
+
+
+def test_array_max_parallel_invalid(hq_env: HqEnv):
+    hq_env.start_server()
+    hq_env.command(
+        ["submit", "--array=1-10%2", "--max-parallel=3", "hostname"],
+        expect_fail="--max-parallel does not match",
+    )
+    hq_env.command(
+        ["submit", "--array=1-10%0", "hostname"],
+        expect_fail="--max-parallel has to be at least 1",
+    )
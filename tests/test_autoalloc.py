@@ -0,0 +1,250 @@
+import os
+import time
+
+from .conftest import HqEnv
+from .utils import wait_until
+
+
+def write_script(path, content: str):
+    with open(path, "w") as f:
+        f.write(f"#!/bin/bash\n{content}\n")
+    os.chmod(path, 0o755)
+
+
+def prepare_slurm(tmp_path, sbatch_fails=False):
+    """
+    Creates fake `sbatch` and `squeue` commands.
+    `sbatch` stores its arguments into `sbatch-args` and adds the submitted job into
+    `squeue-status` as PENDING, `squeue` prints `squeue-status`.
+    """
+    bin_dir = tmp_path / "bin"
+    bin_dir.mkdir()
+    status = tmp_path / "squeue-status"
+    status.write_text("")
+    if sbatch_fails:
+        sbatch = "echo 'sbatch: error: invalid partition' >&2\nexit 1"
+    else:
+        sbatch = f"""
+echo "$@" >> {tmp_path}/sbatch-args
+ID=$(($(wc -l < {tmp_path}/sbatch-args)))
+echo "$ID PENDING" >> {status}
+echo "Submitted batch job $ID"
+"""
+    write_script(bin_dir / "sbatch", sbatch)
+    # Without --states=all, squeue shows only pending and running jobs
+    write_script(
+        bin_dir / "squeue",
+        f"""
+if [[ " $* " == *" --states=all "* ]]; then
+    cat {status}
+else
+    grep -E " (PENDING|RUNNING)$" {status} || true
+fi
+""",
+    )
+    return {"PATH": f"{bin_dir}:{os.environ['PATH']}"}
+
+
+def prepare_pbs(tmp_path):
+    """
+    Creates fake `qsub` and `qstat` commands.
+    `qsub` submits jobs `<n>.pbs` and stores their status into `qstat-<n>.pbs`,
+    `qstat` prints the status of a single job or fails when the file does not exist.
+    """
+    bin_dir = tmp_path / "bin"
+    bin_dir.mkdir()
+    qsub = f"""
+echo "$@" >> {tmp_path}/qsub-args
+ID=$(($(wc -l < {tmp_path}/qsub-args))).pbs
+echo '{{"Jobs": {{"'$ID'": {{"job_state": "Q"}}}}}}' > {tmp_path}/qstat-$ID
+echo $ID
+"""
+    qstat = f"""
+ID="${{@: -1}}"
+if [ -f {tmp_path}/qstat-$ID ]; then
+    cat {tmp_path}/qstat-$ID
+else
+    echo "qstat: Unknown Job Id $ID" >&2
+    exit 153
+fi
+"""
+    write_script(bin_dir / "qsub", qsub)
+    write_script(bin_dir / "qstat", qstat)
+    return {"PATH": f"{bin_dir}:{os.environ['PATH']}"}
+
+
+def start_server(hq_env: HqEnv, env):
+    hq_env.start_server(args=["--autoalloc-interval", "100ms"], env=env)
+
+
+def allocation_states(hq_env: HqEnv, queue_id=1):
+    table = hq_env.command(["alloc", "info", str(queue_id)], as_table=True)
+    return [(row[0], row[1]) for row in table[1:]]
+
+
+def test_autoalloc_add_queue(hq_env: HqEnv, tmp_path):
+    start_server(hq_env, prepare_slurm(tmp_path))
+    output = hq_env.command(
+        [
+            "alloc",
+            "add",
+            "slurm",
+            "--partition",
+            "cpu",
+            "--time-limit",
+            "1h",
+            "--max-workers",
+            "10",
+            "--",
+            "--account=abc",
+        ]
+    )
+    assert "INFO" in output
+    assert "Allocation queue 1 was created" in output
+
+    table = hq_env.command(["alloc", "list"], as_table=True)
+    assert len(table) == 2
+    table.check_value_columns(
+        ["Id", "State", "Manager", "Queue", "Time limit", "Max workers", "Args"],
+        0,
+        ["1", "ACTIVE", "SLURM", "cpu", "1h", "10", "--account=abc"],
+    )
+
+    output = hq_env.command(["alloc", "remove", "1"])
+    assert "Allocation queue 1 was removed" in output
+    table = hq_env.command(["alloc", "list"], as_table=True)
+    assert len(table) == 1
+    hq_env.command(["alloc", "info", "1"], expect_fail="Allocation queue 1 does not exist")
+
+
+def test_autoalloc_invalid_options(hq_env: HqEnv):
+    hq_env.start_server()
+    hq_env.command(
+        ["alloc", "add", "slurm", "--partition", "cpu", "--time-limit", "0s"],
+        expect_fail="--time-limit has to be at least one second",
+    )
+    hq_env.command(
+        [
+            "alloc",
+            "add",
+            "pbs",
+            "--queue",
+            "q",
+            "--time-limit",
+            "1h",
+            "--max-workers",
+            "0",
+        ],
+        expect_fail="--max-workers has to be at least 1",
+    )
+
+
+def test_autoalloc_submit_when_tasks_wait(hq_env: HqEnv, tmp_path):
+    start_server(hq_env, prepare_slurm(tmp_path))
+    hq_env.command(
+        [
+            "alloc",
+            "add",
+            "slurm",
+            "--partition",
+            "cpu",
+            "--time-limit",
+            "1h",
+            "--max-workers",
+            "2",
+            "--",
+            "--account=abc",
+        ]
+    )
+    # Nothing is submitted without waiting tasks
+    time.sleep(0.5)
+    assert allocation_states(hq_env) == []
+
+    hq_env.command(["submit", "--array", "1-10", "--", "hostname"])
+    wait_until(lambda: allocation_states(hq_env) == [("1", "QUEUED")])
+    # The backlog is full
+    time.sleep(0.3)
+    assert allocation_states(hq_env) == [("1", "QUEUED")]
+
+    args = (tmp_path / "sbatch-args").read_text()
+    assert "--partition=cpu" in args
+    assert "--time=01:00:00" in args
+    assert "--account=abc" in args
+    script = args.split()[-1]
+    with open(script) as f:
+        assert "worker start --manager slurm" in f.read()
+
+    (tmp_path / "squeue-status").write_text("1 RUNNING\n")
+    wait_until(
+        lambda: allocation_states(hq_env) == [("1", "RUNNING"), ("2", "QUEUED")]
+    )
+    table = hq_env.command(["alloc", "list"], as_table=True)
+    table.check_value_columns(["Queued", "Running"], 0, ["1", "1"])
+
+    # The allocation is not reported anymore by squeue
+    (tmp_path / "squeue-status").write_text("2 PENDING\n")
+    wait_until(
+        lambda: allocation_states(hq_env) == [("1", "FINISHED"), ("2", "QUEUED")]
+    )
+    # The backlog is full again
+    time.sleep(0.3)
+    assert allocation_states(hq_env) == [("1", "FINISHED"), ("2", "QUEUED")]
+
+
+def test_autoalloc_stop_after_failures(hq_env: HqEnv, tmp_path):
+    start_server(hq_env, prepare_slurm(tmp_path, sbatch_fails=True))
+    hq_env.command(
+        ["alloc", "add", "slurm", "--partition", "cpu", "--time-limit", "1h"]
+    )
+    hq_env.command(["submit", "--", "hostname"])
+
+    def is_stopped():
+        table = hq_env.command(["alloc", "list"], as_table=True)
+        return table.get_column_value("State")[0] == "STOPPED"
+
+    wait_until(is_stopped)
+    assert allocation_states(hq_env) == []
+
+
+def test_autoalloc_slurm_failed_allocation(hq_env: HqEnv, tmp_path):
+    start_server(hq_env, prepare_slurm(tmp_path))
+    hq_env.command(
+        ["alloc", "add", "slurm", "--partition", "cpu", "--time-limit", "1h"]
+    )
+    hq_env.command(["submit", "--", "hostname"])
+    wait_until(lambda: allocation_states(hq_env) == [("1", "QUEUED")])
+
+    (tmp_path / "squeue-status").write_text("1 FAILED\n")
+    wait_until(lambda: allocation_states(hq_env)[0] == ("1", "FAILED"))
+
+
+def test_autoalloc_pbs_unknown_allocation(hq_env: HqEnv, tmp_path):
+    start_server(hq_env, prepare_pbs(tmp_path))
+    hq_env.command(
+        [
+            "alloc",
+            "add",
+            "pbs",
+            "--queue",
+            "q",
+            "--time-limit",
+            "1h",
+            "--max-workers",
+            "2",
+            "--backlog",
+            "2",
+        ]
+    )
+    hq_env.command(["submit", "--", "hostname"])
+    wait_until(
+        lambda: allocation_states(hq_env)
+        == [("1.pbs", "QUEUED"), ("2.pbs", "QUEUED")]
+    )
+
+    # The first allocation was removed from the history of PBS
+    (tmp_path / "qstat-1.pbs").unlink()
+    (tmp_path / "qstat-2.pbs").write_text('{"Jobs": {"2.pbs": {"job_state": "R"}}}')
+    wait_until(
+        lambda: allocation_states(hq_env)[:2]
+        == [("1.pbs", "FINISHED"), ("2.pbs", "RUNNING")]
+    )
@@ -0,0 +1,98 @@
+from .conftest import HqEnv
+from .utils import wait_for_job_state
+
+
+def test_task_deps_order(hq_env: HqEnv):
+    hq_env.start_server()
+    hq_env.command(
+        [
+            "submit",
+            "--array=0-2",
+            "--task-dep=2:0,1",
+            "--task-dep=1:0",
+            "--",
+            "bash",
+            "-c",
+            "echo $HQ_TASK_ID >> order.txt",
+        ]
+    )
+    table = hq_env.command(["job", "1", "--tasks"], as_table=True)
+    states = {row[0]: row[1] for row in table[1:] if row and row[0].isdigit()}
+    assert states["0"] == "WAITING"
+    assert states["1"] == "BLOCKED"
+    assert states["2"] == "BLOCKED"
+
+    hq_env.start_worker(cpus=4)
+    wait_for_job_state(hq_env, 1, "FINISHED")
+    with open("order.txt") as f:
+        assert f.read().split() == ["0", "1", "2"]
+
+
+def test_task_deps_fail_cancels_dependants(hq_env: HqEnv):
+    hq_env.start_server()
+    hq_env.start_worker(cpus=4)
+    hq_env.command(
+        [
+            "submit",
+            "--array=0-3",
+            "--task-dep=1:0",
+            "--task-dep=2:1",
+            "--",
+            "bash",
+            "-c",
+            "test $HQ_TASK_ID != 0",
+        ]
+    )
+    wait_for_job_state(hq_env, 1, "CANCELED")
+    table = hq_env.command(["job", "1", "--tasks"], as_table=True)
+    states = {row[0]: row[1] for row in table[1:] if row and row[0].isdigit()}
+    assert states == {
+        "0": "FAILED",
+        "1": "CANCELED",
+        "2": "CANCELED",
+        "3": "FINISHED",
+    }
+
+
+def test_task_deps_cancel_blocked(hq_env: HqEnv):
+    hq_env.start_server()
+    hq_env.command(["submit", "--array=0-1", "--task-dep=1:0", "--", "sleep", "1"])
+    hq_env.command(["cancel", "1"])
+    wait_for_job_state(hq_env, 1, "CANCELED")
+    table = hq_env.command(["job", "1", "--tasks"], as_table=True)
+    states = [row[1] for row in table[1:] if row and row[0].isdigit()]
+    assert states == ["CANCELED", "CANCELED"]
+
+
+def test_task_deps_cancel_reports_dependants(hq_env: HqEnv):
+    hq_env.start_server()
+    hq_env.command(
+        [
+            "submit",
+            "--array=0-2",
+            "--task-dep=1:0",
+            "--task-dep=2:1",
+            "--",
+            "sleep",
+            "1",
+        ]
+    )
+    output = hq_env.command(["cancel", "1", "--tasks=0"])
+    assert "2 dependent tasks of job 1 were canceled too" in output
+    wait_for_job_state(hq_env, 1, "CANCELED")
+
+
+def test_task_deps_invalid(hq_env: HqEnv):
+    hq_env.start_server()
+    hq_env.command(
+        ["submit", "--array=0-2", "--task-dep=1:2", "--task-dep=2:1", "--", "ls"],
+        expect_fail="cycle",
+    )
+    hq_env.command(
+        ["submit", "--array=0-2", "--task-dep=1:7", "--", "ls"],
+        expect_fail="not part of the job",
+    )
+    hq_env.command(
+        ["submit", "--task-dep=1:0", "--", "ls"],
+        expect_fail="only in task arrays",
+    )
@@ -60,3 +60,71 @@ def test_entries_with_newline(hq_env: HqEnv):
 
     table = hq_env.command(["job", "1"], as_table=True)
     assert table.get_row_value("State").split("\n")[-1] == "FINISHED (4)"
+
+
+def test_entries_from_json(hq_env: HqEnv):
+    hq_env.start_server()
+    hq_env.start_worker(cpus=2)
+
+    with open("input.jsonl", "w") as f:
+        f.write('{"name": "a", "size": 1}\n\n{"name": "b", "size": [2]}\n')
+
+    hq_env.command(
+        [
+            "submit",
+            "--from-json=input.jsonl",
+            "--",
+            "bash",
+            "-c",
+            "echo $HQ_ENTRY_name $HQ_ENTRY_size %{ENTRY:name}",
+        ]
+    )
+    wait_for_job_state(hq_env, 1, "FINISHED")
+
+    for i, expected in enumerate(["a 1 a\n", "b [2] b\n"]):
+        with open(f"stdout.1.{i}") as f:
+            assert f.read() == expected
+
+
+def test_entries_from_csv(hq_env: HqEnv):
+    hq_env.start_server()
+    hq_env.start_worker(cpus=2)
+
+    with open("input.csv", "w") as f:
+        f.write('input,size\nx.txt,10\n"y,z.txt",20\n')
+
+    hq_env.command(
+        [
+            "submit",
+            "--from-csv=input.csv",
+            "--stdout=%{ENTRY:size}.out",
+            "--",
+            "bash",
+            "-c",
+            "echo $HQ_ENTRY_input",
+        ]
+    )
+    wait_for_job_state(hq_env, 1, "FINISHED")
+
+    with open("10.out") as f:
+        assert f.read() == "x.txt\n"
+    with open("20.out") as f:
+        assert f.read() == "y,z.txt\n"
+
+
+def test_entries_invalid(hq_env: HqEnv):
+    hq_env.start_server()
+
+    with open("input.jsonl", "w") as f:
+        f.write('{"name": "a"}\n[1, 2]\n')
+    hq_env.command(
+        ["submit", "--from-json=input.jsonl", "hostname"],
+        expect_fail="Entry is not a JSON object",
+    )
+
+    with open("input.csv", "w") as f:
+        f.write("a,b\n1,2,3\n")
+    hq_env.command(
+        ["submit", "--from-csv=input.csv", "hostname"],
+        expect_fail="Record has 3 columns, but the header has 2 columns",
+    )
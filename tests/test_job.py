@@ -305,6 +305,57 @@ def test_cancel_all(hq_env: HqEnv):
     assert "Job 3 canceled" in r[0]
 
 
+def test_cancel_tasks(hq_env: HqEnv):
+    hq_env.start_server()
+    hq_env.command(["submit", "--array=1-10", "hostname"])
+    r = hq_env.command(["cancel", "1", "--tasks", "3-5,20"])
+    assert "Job 1 canceled (3 tasks canceled, 0 tasks already finished)" in r
+    assert "Job 1 does not contain tasks 20" in r
+
+    hq_env.start_worker(cpus=2)
+    wait_for_job_state(hq_env, 1, "CANCELED")
+    table = hq_env.command(["job", "1", "--tasks"], as_table=True)[JOB_TABLE_ROWS:]
+    states = dict(
+        zip(table.get_column_value("Task Id"), table.get_column_value("State"))
+    )
+    for task_id in range(1, 11):
+        expected = "CANCELED" if task_id in (3, 4, 5) else "FINISHED"
+        assert states[str(task_id)] == expected
+
+    r = hq_env.command(["cancel", "1", "--tasks", "1"])
+    assert "Canceling job 1 failed" in r
+
+
+def test_task_requeue(hq_env: HqEnv):
+    hq_env.start_server()
+    hq_env.start_worker(cpus=2)
+    hq_env.command(
+        [
+            "submit",
+            "--array=1-2",
+            "--",
+            "bash",
+            "-c",
+            "echo x >> out-$HQ_TASK_ID; sleep 2",
+        ]
+    )
+    wait_for_job_state(hq_env, 1, "RUNNING")
+    time.sleep(0.5)
+    r = hq_env.command(["task", "requeue", "1", "--tasks", "2,5"])
+    assert "Job 1: 1 tasks requeued" in r
+    assert "Job 1 does not contain tasks 5" in r
+
+    wait_for_job_state(hq_env, 1, "FINISHED")
+    with open("out-1") as f:
+        assert f.read().split() == ["x"]
+    with open("out-2") as f:
+        assert f.read().split() == ["x", "x"]
+
+    r = hq_env.command(["task", "requeue", "1", "--tasks", "1"])
+    assert "Tasks 1 of job 1 are not running" in r
+    assert "Job 1: 0 tasks requeued" in r
+
+
 def test_reporting_state_after_worker_lost(hq_env: HqEnv):
     hq_env.start_server()
     hq_env.start_workers(2, cpus=1)
@@ -497,6 +548,18 @@ def test_job_resubmit_with_status(hq_env: HqEnv):
     table.check_value_row("Tasks", "3; Ids: 3, 7, 9")
 
 
+def test_job_resubmit_failure(hq_env: HqEnv):
+    hq_env.start_server()
+    hq_env.command(["submit", "--", "/bin/hostname"])
+    hq_env.start_worker(cpus=1)
+    wait_for_job_state(hq_env, 1, "FINISHED")
+
+    hq_env.command(
+        ["resubmit", "1", "--status=failed"],
+        expect_fail="Resubmitting job 1 failed: Nothing was resubmitted",
+    )
+
+
 def test_job_resubmit_all(hq_env: HqEnv):
     hq_env.start_server()
     hq_env.command(["submit", "--array=2,7,9", "--", "/bin/hostname"])
@@ -507,6 +570,79 @@ def test_job_resubmit_all(hq_env: HqEnv):
     table.check_value_row("Tasks", "3; Ids: 2, 7, 9")
 
 
+def test_job_resubmit_with_overrides(hq_env: HqEnv):
+    hq_env.start_server()
+    hq_env.command(
+        ["submit", "--array=1-4", "--env=FOO=BAR", "--priority=3", "--", "hostname"]
+    )
+    table = hq_env.command(
+        [
+            "resubmit",
+            "1",
+            "--cpus=2",
+            "--priority=-1",
+            "--env=FOO2=BAR2",
+            "--name=rerun",
+            "--",
+            "bash",
+            "-c",
+            "echo $FOO2",
+        ],
+        as_table=True,
+    )
+    table.check_value_row("Name", "rerun")
+    table.check_value_row("Tasks", "4; Ids: 1-4")
+    table.check_value_row("Resources", "2 compact")
+    table.check_value_row("Priority", "-1")
+    table.check_value_row("Environment", "FOO=BAR\nFOO2=BAR2")
+    table.check_value_row("Command", "bash\n-c\necho $FOO2")
+
+    table = hq_env.command(["job", "1"], as_table=True)
+    table.check_value_row("Priority", "3")
+    table.check_value_row("Command", "hostname")
+
+    hq_env.command(
+        ["resubmit", "1", "--name=a\tb"], expect_fail="name cannot have a newline"
+    )
+
+
+def test_job_resubmit_with_resource_overrides(hq_env: HqEnv):
+    hq_env.start_server()
+    hq_env.command(["server", "resource", "add", "matlab=2"])
+    hq_env.command(
+        [
+            "submit",
+            "--use=matlab=1",
+            "--resource=gpu=1",
+            "--require=ssd",
+            "--time-request=10m",
+            "--",
+            "hostname",
+        ]
+    )
+    table = hq_env.command(
+        [
+            "resubmit",
+            "1",
+            "--use=matlab=2",
+            "--resource=gpu=2",
+            "--require=bigmem",
+            "--time-request=1h",
+        ],
+        as_table=True,
+    )
+    table.check_value_row("Resource pools", "matlab=2")
+    table.check_value_row("Resources", "1 compact, gpu=2")
+    table.check_value_row("Required labels", "bigmem")
+    table.check_value_row("Time request", "1h")
+
+    table = hq_env.command(["resubmit", "1"], as_table=True)
+    table.check_value_row("Resource pools", "matlab=1")
+    table.check_value_row("Resources", "1 compact, gpu=1")
+    table.check_value_row("Required labels", "ssd")
+    table.check_value_row("Time request", "10m")
+
+
 def test_job_priority(hq_env: HqEnv, tmp_path):
     hq_env.start_server()
     hq_env.command(
@@ -629,3 +765,240 @@ def test_job_wait_cancellation_exit_code(hq_env: HqEnv):
     hq_env.command(["cancel", "last"])
 
     assert process.wait() == 1
+
+
+def test_job_retries(hq_env: HqEnv):
+    hq_env.start_server()
+    hq_env.start_worker()
+    # Fails in the first two attempts
+    hq_env.command(
+        [
+            "submit",
+            "--max-retries=2",
+            "--",
+            "bash",
+            "-c",
+            "echo x >> attempts.txt; test $(wc -l < attempts.txt) -ge 3",
+        ]
+    )
+    wait_for_job_state(hq_env, 1, "FINISHED")
+
+    table = hq_env.command(["job", "1", "--tasks"], as_table=True)
+    table.check_value_row("Retries", "2")
+    table[JOB_TABLE_ROWS + 1 :].check_value_column("Attempt", 0, "3")
+
+
+def test_job_retries_exhausted(hq_env: HqEnv):
+    hq_env.start_server()
+    hq_env.start_worker()
+    hq_env.command(
+        [
+            "submit",
+            "--array=0-1",
+            "--max-retries=1",
+            "--retry-delay=1s",
+            "--",
+            "bash",
+            "-c",
+            "echo $HQ_TASK_ID >> attempts.txt; test $HQ_TASK_ID = 1",
+        ]
+    )
+    wait_for_job_state(hq_env, 1, "FAILED")
+    with open("attempts.txt") as f:
+        assert f.read().split().count("0") == 2
+
+
+def test_job_time_limit(hq_env: HqEnv):
+    hq_env.start_server()
+    hq_env.start_worker()
+    hq_env.command(["submit", "--time-limit=1s", "--", "sleep", "100"])
+    wait_for_job_state(hq_env, 1, "FAILED")
+
+    table = hq_env.command(["job", "1", "--tasks"], as_table=True)
+    table.check_value_row("Time limit", "1s")
+    table = table[JOB_TABLE_ROWS + 1 :]
+    assert "Time limit 1s exceeded" in table.get_column_value("Message")[0]
+
+
+def test_job_time_limit_ignored_sigterm(hq_env: HqEnv):
+    hq_env.start_server()
+    hq_env.start_worker()
+    hq_env.command(
+        ["submit", "--time-limit=1s", "--", "bash", "-c", "trap '' TERM; sleep 100"]
+    )
+    wait_for_job_state(hq_env, 1, "FAILED")
+
+
+def test_job_time_limit_kills_subprocesses(hq_env: HqEnv):
+    hq_env.start_server()
+    hq_env.start_worker()
+    hq_env.command(
+        [
+            "submit",
+            "--time-limit=1s",
+            "--",
+            "bash",
+            "-c",
+            "(sleep 2; touch subprocess.txt) & sleep 100",
+        ]
+    )
+    wait_for_job_state(hq_env, 1, "FAILED")
+    time.sleep(2)
+    assert not os.path.exists("subprocess.txt")
+
+
+def test_job_timestamps(hq_env: HqEnv):
+    hq_env.start_server()
+    hq_env.command(["submit", "--", "sleep", "1"])
+
+    table = hq_env.command(["job", "1", "--tasks"], as_table=True)
+    datetime.strptime(table.get_row_value("Submission date"), "%d.%m.%Y %H:%M:%S")
+    table.check_value_row("Makespan", "")
+    table[JOB_TABLE_ROWS:].check_value_column("Times", 0, "")
+
+    hq_env.start_worker()
+    wait_for_job_state(hq_env, 1, "FINISHED")
+
+    table = hq_env.command(["job", "1", "--tasks"], as_table=True)
+    assert table.get_row_value("Makespan").startswith("1s")
+    times = table[JOB_TABLE_ROWS:].get_column_value("Times")[0].split("\n")
+    assert times[0].startswith("Start: ")
+    assert times[1].startswith("End: ")
+    assert times[2].startswith("Duration: 1s")
+
+
+def test_job_failure_exit_code_and_signal(hq_env: HqEnv):
+    hq_env.start_server()
+    hq_env.start_worker()
+    hq_env.command(["submit", "--", "bash", "-c", "exit 3"])
+    hq_env.command(["submit", "--", "bash", "-c", "kill -9 $$"])
+    hq_env.command(["submit", "--array=1-3", "--", "bash", "-c", "exit $HQ_TASK_ID"])
+    wait_for_job_state(hq_env, [1, 2, 3], "FAILED")
+
+    table = hq_env.command(["job", "2", "--tasks"], as_table=True)
+    assert "Program terminated by signal 9" in table[
+        JOB_TABLE_ROWS:
+    ].get_column_value("Message")[0]
+
+    table = hq_env.command(["jobs", "--exit-code=3"], as_table=True)
+    assert [row[0] for row in table[1:]] == ["1", "3"]
+
+    table = hq_env.command(["job", "3", "--tasks", "--exit-code=3"], as_table=True)
+    table = table[JOB_TABLE_ROWS:]
+    assert table.get_column_value("Task Id") == ["3"]
+
+
+def test_job_hold_release(hq_env: HqEnv):
+    hq_env.start_server()
+    hq_env.command(["submit", "--array=1-4", "--", "hostname"])
+    r = hq_env.command(["job", "hold", "1"])
+    assert "Job 1 held (4 tasks held)" in r
+    table = hq_env.command(["jobs"], as_table=True)
+    table.check_value_column("State", 0, "HELD")
+
+    hq_env.start_worker(cpus=2)
+    time.sleep(1)
+    table = hq_env.command(["jobs", "held"], as_table=True)
+    table.check_value_column("State", 0, "HELD")
+
+    r = hq_env.command(["job", "release", "1"])
+    assert "Job 1 released (4 tasks released)" in r
+    wait_for_job_state(hq_env, 1, "FINISHED")
+
+    r = hq_env.command(["job", "release", "1"])
+    assert "Releasing job 1 failed" in r
+
+
+def test_job_hold_keeps_running_tasks(hq_env: HqEnv):
+    hq_env.start_server()
+    hq_env.start_worker(cpus=1)
+    hq_env.command(["submit", "--array=1-3", "--", "sleep", "1"])
+    wait_for_job_state(hq_env, 1, "RUNNING")
+    hq_env.command(["job", "hold", "1"])
+    time.sleep(1.5)
+
+    table = hq_env.command(["job", "1", "--tasks"], as_table=True)
+    states = table[JOB_TABLE_ROWS:].get_column_value("State")
+    assert sorted(states) == ["FINISHED", "HELD", "HELD"]
+
+    hq_env.command(["job", "release", "all"])
+    wait_for_job_state(hq_env, 1, "FINISHED")
+
+
+def test_job_hold_cancel(hq_env: HqEnv):
+    hq_env.start_server()
+    hq_env.command(["submit", "hostname"])
+    hq_env.command(["job", "hold", "last"])
+    r = hq_env.command(["cancel", "1"])
+    assert "Job 1 canceled" in r
+    table = hq_env.command(["jobs"], as_table=True)
+    table.check_value_column("State", 0, "CANCELED")
+
+
+def test_job_update_priority(hq_env: HqEnv):
+    hq_env.start_server()
+    hq_env.command(["submit", "--", "bash", "-c", "echo first >> order.txt"])
+    hq_env.command(["submit", "--", "bash", "-c", "echo second >> order.txt"])
+    r = hq_env.command(["job", "update", "2", "--priority", "5"])
+    assert "Job 2 updated (1 waiting tasks reprioritized)" in r
+
+    table = hq_env.command(["job", "2"], as_table=True)
+    table.check_value_row("Priority", "5")
+
+    hq_env.start_worker(cpus=1)
+    wait_for_job_state(hq_env, [1, 2], "FINISHED")
+    with open(os.path.join(hq_env.work_path, "order.txt")) as f:
+        assert f.read().split() == ["second", "first"]
+
+
+def test_job_update_max_fails(hq_env: HqEnv):
+    hq_env.start_server()
+    hq_env.command(["submit", "--array=1-10", "--", "bash", "-c", "exit 1"])
+    hq_env.command(["job", "update", "1", "--max-fails", "0"])
+    hq_env.start_worker(cpus=1)
+    wait_for_job_state(hq_env, 1, "CANCELED")
+
+    hq_env.command(["job", "update", "1"], expect_fail="Nothing to update")
+
+
+def test_job_selectors(hq_env: HqEnv):
+    hq_env.start_server()
+    hq_env.command(["submit", "--name=prep-a", "hostname"])
+    hq_env.command(["submit", "--name=prep-b", "hostname"])
+    hq_env.command(["submit", "--name=compute", "hostname"])
+    hq_env.command(["submit", "--name=compute", "hostname"])
+
+    r = hq_env.command(["cancel", "3-4"])
+    assert "Job 3 canceled" in r
+    assert "Job 4 canceled" in r
+
+    table = hq_env.command(["job", "name:prep*"], as_table=True)
+    assert [row[1] for row in table if row[0] == "Id"] == ["1", "2"]
+
+    r = hq_env.command(["cancel", "last 3"])
+    assert "Job 2 canceled" in r
+    assert "Job 1 canceled" not in r
+
+    hq_env.command(["resubmit", "status:canceled"])
+    table = hq_env.command(["jobs"], as_table=True)
+    assert len(table.rows) == 1 + 7
+
+    hq_env.start_worker()
+    hq_env.command(["wait", "1,5-7"])
+    table = hq_env.command(["jobs", "finished"], as_table=True)
+    assert [row[0] for row in table[1:]] == ["1", "5", "6", "7"]
+
+
+def test_job_selector_huge_range(hq_env: HqEnv):
+    hq_env.start_server()
+    hq_env.command(["submit", "hostname"])
+    hq_env.command(["submit", "hostname"])
+
+    r = hq_env.command(["cancel", "2-4000000000"])
+    assert "Job 2 canceled" in r
+    assert "Job 3" not in r
+
+    r = hq_env.command(["job", "1-4000000000"])
+    assert "not found" not in r
+    r = hq_env.command(["job", "100-200"])
+    assert "Job 100 not found" in r
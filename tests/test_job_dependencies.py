@@ -0,0 +1,56 @@
+from .conftest import HqEnv
+from .utils import wait_for_job_state
+
+
+def test_job_after_ok(hq_env: HqEnv):
+    hq_env.start_server()
+    hq_env.command(["submit", "--", "bash", "-c", "sleep 1; echo first >> order.txt"])
+    hq_env.command(
+        ["submit", "--after=1", "--", "bash", "-c", "echo second >> order.txt"]
+    )
+
+    table = hq_env.command(["job", "2"], as_table=True)
+    table.check_value_row("State", "HELD")
+    table.check_value_row("Dependencies", "1 (after ok)")
+
+    hq_env.start_worker(cpus=4)
+    wait_for_job_state(hq_env, 2, "FINISHED")
+    with open("order.txt") as f:
+        assert f.read().split() == ["first", "second"]
+
+
+def test_job_after_ok_failed_dependency(hq_env: HqEnv):
+    hq_env.start_server()
+    hq_env.start_worker(cpus=2)
+    hq_env.command(["submit", "--", "bash", "-c", "exit 1"])
+    hq_env.command(["submit", "--after=1", "--", "hostname"])
+    wait_for_job_state(hq_env, 2, "CANCELED")
+    table = hq_env.command(["jobs"], as_table=True)
+    table.check_value_column("State", 0, "FAILED")
+
+
+def test_job_after_any_and_notok(hq_env: HqEnv):
+    hq_env.start_server()
+    hq_env.start_worker(cpus=2)
+    hq_env.command(["submit", "--", "bash", "-c", "exit 1"])
+    hq_env.command(["submit", "--after=1", "--after-mode=any", "--", "hostname"])
+    hq_env.command(["submit", "--after=1", "--after-mode=notok", "--", "hostname"])
+    hq_env.command(["submit", "--after=2", "--after-mode=notok", "--", "hostname"])
+    wait_for_job_state(hq_env, [2, 3], "FINISHED")
+    wait_for_job_state(hq_env, 4, "CANCELED")
+
+
+def test_job_after_chain_cancel(hq_env: HqEnv):
+    hq_env.start_server()
+    hq_env.command(["submit", "--", "sleep", "1"])
+    hq_env.command(["submit", "--after=1", "--", "hostname"])
+    hq_env.command(["submit", "--after=2", "--", "hostname"])
+    hq_env.command(["cancel", "1"])
+    wait_for_job_state(hq_env, [1, 2, 3], "CANCELED")
+
+
+def test_job_after_invalid_job(hq_env: HqEnv):
+    hq_env.start_server()
+    hq_env.command(
+        ["submit", "--after=5", "--", "hostname"], expect_fail="Job 5 does not exist"
+    )
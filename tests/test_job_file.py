@@ -0,0 +1,99 @@
+import json
+
+from .conftest import HqEnv
+from .utils import wait_for_job_state
+
+
+def test_job_file_groups(hq_env: HqEnv):
+    hq_env.start_server()
+    with open("job.toml", "w") as f:
+        f.write(
+            """
+name = "pipeline"
+
+[[group]]
+command = ["bash", "-c", "echo prepare-$HQ_TASK_ID-$MODE"]
+array = "1-3"
+env = { MODE = "fast" }
+
+[[group]]
+command = ["bash", "-c", "echo reduce-$HQ_TASK_ID-$MODE"]
+array = "10"
+cpus = "2"
+priority = 5
+"""
+        )
+    table = hq_env.command(
+        ["submit", "--env=MODE=slow", "--job-file", "job.toml"], as_table=True
+    )
+    table.check_value_row("Name", "pipeline")
+    table.check_value_row("Tasks", "4; Ids: 1-3, 10")
+
+    hq_env.start_worker(cpus=2)
+    wait_for_job_state(hq_env, 1, "FINISHED")
+    for task_id in (1, 2, 3):
+        with open(f"stdout.1.{task_id}") as f:
+            assert f.read().strip() == f"prepare-{task_id}-fast"
+    with open("stdout.1.10") as f:
+        assert f.read().strip() == "reduce-10-slow"
+
+
+def test_job_file_deps(hq_env: HqEnv, tmp_path):
+    hq_env.start_server()
+    with open("job.toml", "w") as f:
+        f.write(
+            f"""
+[[group]]
+command = ["bash", "-c", "sleep 1; touch {tmp_path}/$HQ_TASK_ID"]
+array = "1-2"
+
+[[group]]
+command = ["bash", "-c", "ls {tmp_path}"]
+array = "3"
+deps = "1-2"
+"""
+        )
+    hq_env.command(["submit", "--job-file", "job.toml"])
+    hq_env.start_worker(cpus=3)
+    wait_for_job_state(hq_env, 1, "FINISHED")
+    with open("stdout.1.3") as f:
+        assert f.read().split() == ["1", "2"]
+
+
+def test_job_file_json_single_group(hq_env: HqEnv):
+    hq_env.start_server()
+    with open("job.json", "w") as f:
+        json.dump({"group": [{"command": ["bash", "-c", "echo hello"]}]}, f)
+    table = hq_env.command(["submit", "--job-file", "job.json"], as_table=True)
+    table.check_value_row("Name", "bash")
+    table.check_value_row("Tasks", "1")
+
+    hq_env.start_worker(cpus=1)
+    wait_for_job_state(hq_env, 1, "FINISHED")
+    with open("stdout.1.0") as f:
+        assert f.read().strip() == "hello"
+
+
+def test_job_file_invalid(hq_env: HqEnv):
+    hq_env.start_server()
+    with open("job.toml", "w") as f:
+        f.write(
+            """
+[[group]]
+command = ["hostname"]
+array = "1-3"
+
+[[group]]
+command = ["hostname"]
+array = "3-4"
+"""
+        )
+    hq_env.command(
+        ["submit", "--job-file", "job.toml"],
+        expect_fail="Task 3 is defined in more task groups",
+    )
+    hq_env.command(
+        ["submit", "--job-file", "job.toml", "hostname"],
+        expect_fail="A command cannot be used together with --job-file",
+    )
+    hq_env.command(["submit"], expect_fail="No command was given")
@@ -1,6 +1,9 @@
+import json
+import os
 import time
 
 from .conftest import HqEnv
+from .utils import parse_duration
 
 
 def test_manager_autodetect(hq_env: HqEnv):
@@ -55,3 +58,85 @@ def test_manager_set_slurm(hq_env: HqEnv):
     hq_env.start_worker(cpus=1, args=args, env={"SLURM_JOB_ID": "abcd"})
     table = hq_env.command(["worker", "list"], as_table=True)
     table.check_value_columns(["Manager", "Manager Job Id"], 0, ["SLURM", "abcd"])
+
+
+def write_fake_command(path, output: str) -> str:
+    with open(path, "w") as f:
+        f.write(f"#!/bin/bash\ncat <<'EOF'\n{output}\nEOF\n")
+    os.chmod(path, 0o755)
+    return str(path)
+
+
+def test_manager_pbs_job_info(hq_env: HqEnv, tmp_path):
+    qstat = write_fake_command(
+        tmp_path / "qstat",
+        json.dumps(
+            {
+                "Jobs": {
+                    "x1234": {
+                        "queue": "qexp",
+                        "exec_host": "cn1/0*24+cn2/0*24",
+                        "Resource_List": {"walltime": "02:00:00"},
+                        "resources_used": {"walltime": "01:00:00"},
+                    }
+                }
+            }
+        ),
+    )
+    hq_env.start_server()
+    hq_env.start_worker(
+        cpus=1,
+        env={
+            "PBS_ENVIRONMENT": "PBS_BATCH",
+            "PBS_JOBID": "x1234",
+            "HQ_QSTAT_PATH": qstat,
+        },
+    )
+
+    table = hq_env.command(["worker", "info", "1"], as_table=True)
+    table.check_value_row("Manager", "PBS")
+    table.check_value_row("Manager queue", "qexp")
+    table.check_value_row("Manager nodes", "cn1,cn2")
+    table.check_value_row("Manager walltime", "2h")
+    assert 3500 < parse_duration(table.get_row_value("Remaining lifetime")) <= 3600
+
+
+def test_manager_slurm_job_info(hq_env: HqEnv, tmp_path):
+    scontrol = write_fake_command(
+        tmp_path / "scontrol",
+        "JobId=y5678 JobName=hq\n"
+        "   RunTime=00:00:00 TimeLimit=01:00:00 TimeMin=N/A\n"
+        "   Partition=cpu NodeList=cn[01-02]",
+    )
+    hq_env.start_server()
+    hq_env.start_worker(
+        cpus=1, env={"SLURM_JOB_ID": "y5678", "HQ_SCONTROL_PATH": scontrol}
+    )
+
+    table = hq_env.command(["worker", "info", "1"], as_table=True)
+    table.check_value_row("Manager", "SLURM")
+    table.check_value_row("Manager queue", "cpu")
+    table.check_value_row("Manager nodes", "cn[01-02]")
+    table.check_value_row("Manager walltime", "1h")
+    assert 3500 < parse_duration(table.get_row_value("Remaining lifetime")) <= 3600
+
+    # A shorter time limit of the worker wins over the walltime of the job
+    hq_env.start_worker(
+        cpus=1,
+        args=["--time-limit", "10m"],
+        env={"SLURM_JOB_ID": "y5678", "HQ_SCONTROL_PATH": scontrol},
+    )
+    table = hq_env.command(["worker", "info", "2"], as_table=True)
+    assert 500 < parse_duration(table.get_row_value("Remaining lifetime")) <= 600
+
+
+def test_manager_job_info_command_fails(hq_env: HqEnv):
+    hq_env.start_server()
+    hq_env.start_worker(
+        cpus=1, env={"SLURM_JOB_ID": "y5678", "HQ_SCONTROL_PATH": "/nonexistent"}
+    )
+
+    table = hq_env.command(["worker", "info", "1"], as_table=True)
+    table.check_value_row("Manager", "SLURM")
+    table.check_value_row("Manager walltime", "N/A")
+    table.check_value_row("Remaining lifetime", "N/A")
@@ -0,0 +1,66 @@
+import time
+
+from .conftest import HqEnv
+from .utils import wait_for_job_state
+
+
+def test_resource_pool_limits_tasks(hq_env: HqEnv):
+    hq_env.start_server()
+    hq_env.start_worker(cpus=4)
+    table = hq_env.command(["server", "resource", "add", "matlab=2"], as_table=True)
+    assert table[1] == ["matlab", "2", "0"]
+
+    table = hq_env.command(
+        ["submit", "--array=1-4", "--use", "matlab=1", "--", "sleep", "1"],
+        as_table=True,
+    )
+    table.check_value_row("Resource pools", "matlab=1")
+    time.sleep(0.5)
+
+    table = hq_env.command(["job", "1"], as_table=True)
+    states = table.get_row_value("State").split("\n")
+    assert "RUNNING (2)" in states
+    assert "WAITING (2)" in states
+
+    table = hq_env.command(["server", "info"], as_table=True)
+    table.check_value_row("Resource pools", "matlab: 2/2 used")
+
+    wait_for_job_state(hq_env, 1, "FINISHED")
+    table = hq_env.command(["server", "info"], as_table=True)
+    table.check_value_row("Resource pools", "matlab: 0/2 used")
+
+
+def test_resource_pool_shared_by_jobs(hq_env: HqEnv):
+    hq_env.start_server()
+    hq_env.start_worker(cpus=4)
+    hq_env.command(["server", "resource", "add", "db=1"])
+    hq_env.command(["submit", "--use", "db=1", "--", "sleep", "1"])
+    hq_env.command(["submit", "--use", "db=1", "--", "sleep", "1"])
+    time.sleep(0.5)
+
+    table = hq_env.command(["jobs"], as_table=True)
+    table.check_value_column("State", 0, "RUNNING")
+    table.check_value_column("State", 1, "WAITING")
+
+    # Enlarged pool is used immediately
+    hq_env.command(["server", "resource", "add", "db=2"])
+    time.sleep(0.5)
+    table = hq_env.command(["jobs"], as_table=True)
+    table.check_value_column("State", 1, "RUNNING")
+
+
+def test_resource_pool_invalid(hq_env: HqEnv):
+    hq_env.start_server()
+    hq_env.command(
+        ["submit", "--use", "matlab=1", "hostname"],
+        expect_fail="Resource pool matlab does not exist",
+    )
+    hq_env.command(["server", "resource", "add", "matlab=1"])
+    hq_env.command(
+        ["submit", "--use", "matlab=2", "hostname"],
+        expect_fail="requires 2 units of resource pool matlab",
+    )
+    hq_env.command(
+        ["submit", "--use", "matlab=1", "--use", "matlab=1", "hostname"],
+        expect_fail="Resource pool matlab is used more than once",
+    )
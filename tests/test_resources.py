@@ -0,0 +1,97 @@
+import time
+
+from .conftest import HqEnv
+from .utils import wait_for_job_state, wait_for_worker_state
+
+
+def test_generic_resource_values(hq_env: HqEnv):
+    hq_env.start_server()
+    hq_env.start_worker(cpus=4, args=["--resource", "gpu=[3,5]"])
+    wait_for_worker_state(hq_env, 1, "RUNNING")
+
+    table = hq_env.command(["worker", "info", "1"], as_table=True)
+    table.check_value_row("Generic resources", "gpu=[3,5]")
+
+    table = hq_env.command(
+        [
+            "submit",
+            "--array=1-2",
+            "--resource",
+            "gpu=1",
+            "--",
+            "bash",
+            "-c",
+            "sleep 1; echo $HQ_RESOURCE_VALUES_gpu",
+        ],
+        as_table=True,
+    )
+    table.check_value_row("Resources", "1 compact, gpu=1")
+    wait_for_job_state(hq_env, 1, "FINISHED")
+
+    values = set()
+    for task_id in (1, 2):
+        with open(f"stdout.1.{task_id}") as f:
+            values.add(f.read().strip())
+    assert values == {"3", "5"}
+
+
+def test_generic_resource_limits_tasks(hq_env: HqEnv):
+    hq_env.start_server()
+    hq_env.start_worker(cpus=4, args=["--resource", "mem=100"])
+    hq_env.command(
+        ["submit", "--array=1-3", "--resource", "mem=50", "--", "sleep", "1"]
+    )
+    time.sleep(0.5)
+
+    table = hq_env.command(["job", "1"], as_table=True)
+    states = table.get_row_value("State").split("\n")
+    assert "RUNNING (2)" in states
+    assert "WAITING (1)" in states
+
+    wait_for_job_state(hq_env, 1, "FINISHED")
+
+
+def test_generic_resource_waits_for_worker(hq_env: HqEnv):
+    hq_env.start_server()
+    hq_env.start_worker(cpus=2, args=["--resource", "mem=100"])
+    hq_env.command(
+        [
+            "submit",
+            "--resource",
+            "gpu=1",
+            "--",
+            "bash",
+            "-c",
+            "echo $HQ_RESOURCE_VALUES_gpu",
+        ]
+    )
+    time.sleep(1)
+
+    table = hq_env.command(["jobs"], as_table=True)
+    assert table.get_column_value("State")[0] == "WAITING"
+
+    hq_env.start_worker(cpus=2, args=["--resource", "gpu=range(1-2)"])
+    wait_for_job_state(hq_env, 1, "FINISHED")
+    with open("stdout.1.0") as f:
+        assert f.read().strip() == "1"
+
+
+def test_generic_resource_invalid(hq_env: HqEnv):
+    hq_env.start_server()
+    hq_env.command(
+        ["submit", "--resource", "gpu=1", "--resource", "gpu=2", "hostname"],
+        expect_fail="Resource gpu is requested more than once",
+    )
+    hq_env.command(
+        ["submit", "--resource", "gpu=0", "hostname"],
+        expect_fail="Requesting zero units is not allowed",
+    )
+
+    process = hq_env.start_worker(
+        args=["--resource", "mem=1", "--resource", "mem=2"]
+    )
+    time.sleep(0.3)
+    hq_env.check_process_exited(process, expected_code=1)
+    process = hq_env.start_worker(args=["--resource", "gpu=[]"])
+    time.sleep(0.3)
+    hq_env.check_process_exited(process, expected_code=1)
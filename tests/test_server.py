@@ -3,11 +3,12 @@ import os
 import signal
 import socket
 import subprocess
+import time
 
 import pytest
 
 from .conftest import HqEnv
-from .utils import parse_table
+from .utils import parse_table, wait_for_job_state
 
 
 def test_server_host(hq_env: HqEnv):
@@ -91,3 +92,72 @@ def test_delete_symlink_after_ctrl_c(hq_env: HqEnv):
     hq_env.check_process_exited(process, 0)
 
     assert not os.path.isdir(os.path.join(hq_env.server_dir, "hq-current"))
+
+
+def test_server_restore(hq_env: HqEnv):
+    process = hq_env.start_server()
+    hq_env.start_worker(cpus=1)
+    hq_env.command(["submit", "--", "bash", "-c", "echo 'hello'"])
+    wait_for_job_state(hq_env, 1, "FINISHED")
+    hq_env.kill_worker(1)
+
+    hq_env.command(["submit", "--array=1-4", "--", "bash", "-c", "echo $HQ_TASK_ID"])
+    hq_env.command(["server", "stop"])
+    process.wait()
+    hq_env.check_process_exited(process, 0)
+
+    hq_env.start_server(args=["--restore"])
+    table = hq_env.command("jobs", as_table=True)
+    assert len(table) == 3
+    table.check_value_columns(["Id", "State"], 0, ["1", "FINISHED"])
+    table.check_value_columns(["Id", "State"], 1, ["2", "WAITING"])
+
+    hq_env.start_worker(cpus=1)
+    wait_for_job_state(hq_env, 2, "FINISHED")
+    for task_id in range(1, 5):
+        with open(f"stdout.2.{task_id}") as f:
+            assert f.read().strip() == str(task_id)
+
+    hq_env.command(["submit", "--", "bash", "-c", "echo 'hello'"])
+    table = hq_env.command("jobs", as_table=True)
+    table.check_value_columns(["Id"], 2, ["3"])
+
+
+def test_server_restore_appends_log(hq_env: HqEnv, tmp_path):
+    process = hq_env.start_server()
+    hq_env.start_worker(cpus=1)
+    restored = tmp_path / "restored"
+    hq_env.command(
+        [
+            "submit",
+            "--log",
+            "mylog",
+            "--array=1-2",
+            "--",
+            "bash",
+            "-c",
+            f"echo out $HQ_TASK_ID; [ $HQ_TASK_ID = 1 ] || [ -f {restored} ] || sleep 100",
+        ]
+    )
+    time.sleep(1)
+    hq_env.command(["server", "stop"])
+    process.wait()
+    hq_env.check_process_exited(process, 0)
+
+    restored.touch()
+    hq_env.start_server(args=["--restore"])
+    hq_env.start_worker(cpus=1)
+    wait_for_job_state(hq_env, 1, "FINISHED")
+
+    result = hq_env.command(["log", "mylog", "cat", "stdout"])
+    assert result == "out 1\nout 2\n"
+
+
+def test_server_restore_without_journal(hq_env: HqEnv):
+    hq_env.server_dir = os.path.join(hq_env.work_path, "hq-server")
+    os.makedirs(hq_env.server_dir)
+    args = hq_env.server_args(hq_env.server_dir) + ["--restore"]
+    process = subprocess.Popen(args, stdout=subprocess.PIPE, stderr=subprocess.STDOUT)
+    stdout, _ = process.communicate(timeout=5)
+    assert process.returncode != 0
+    assert "Cannot restore server" in stdout.decode()
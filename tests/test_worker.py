@@ -2,7 +2,7 @@ import time
 from socket import gethostname
 
 from .conftest import HqEnv
-from .utils import wait_for_worker_state
+from .utils import parse_duration, wait_for_job_state, wait_for_worker_state
 
 
 def test_worker_list(hq_env: HqEnv):
@@ -72,6 +72,52 @@ def test_worker_stop_all(hq_env: HqEnv):
         hq_env.check_process_exited(process)
 
 
+def test_worker_drain(hq_env: HqEnv, tmp_path):
+    hq_env.start_server()
+    process = hq_env.start_worker(env={"WORKER_NAME": "w1"})
+    finished = tmp_path / "finished"
+    hq_env.command(["submit", "--", "bash", "-c", f"sleep 2; touch {finished}"])
+    wait_for_job_state(hq_env, 1, "RUNNING")
+
+    response = hq_env.command(["worker", "drain", "1"])
+    assert "Worker 1 is draining" in response
+    wait_for_worker_state(hq_env, 1, "DRAINING")
+
+    # A draining worker does not start new tasks
+    hq_env.command(["submit", "--", "bash", "-c", "echo $WORKER_NAME"])
+    time.sleep(0.5)
+    table = hq_env.command(["jobs"], as_table=True)
+    assert table.get_column_value("State")[1] != "FINISHED"
+
+    # The worker stops when its running task is finished
+    wait_for_job_state(hq_env, 1, "FINISHED")
+    assert finished.exists()
+    wait_for_worker_state(hq_env, 1, "STOPPED")
+    hq_env.check_process_exited(process)
+
+    hq_env.start_worker(env={"WORKER_NAME": "w2"})
+    wait_for_job_state(hq_env, 2, "FINISHED")
+    with open("stdout.2.0") as f:
+        assert f.read().strip() == "w2"
+
+    response = hq_env.command(["worker", "drain", "1"])
+    assert "worker is already stopped" in response
+    response = hq_env.command(["worker", "drain", "3"])
+    assert "worker not found" in response
+
+
+def test_worker_drain_all(hq_env: HqEnv):
+    hq_env.start_server()
+    processes = [hq_env.start_worker() for _ in range(2)]
+    wait_for_worker_state(hq_env, [1, 2], "RUNNING")
+
+    # Idle workers are stopped immediately
+    hq_env.command(["worker", "drain", "all"])
+    wait_for_worker_state(hq_env, [1, 2], "STOPPED")
+    for process in processes:
+        hq_env.check_process_exited(process)
+
+
 def test_worker_list_online_offline_state(hq_env: HqEnv):
     hq_env.start_server()
     hq_env.start_workers(2)
@@ -163,3 +209,190 @@ def test_worker_address(hq_env: HqEnv):
 
     output = hq_env.command(["worker", "address", "1"]).strip()
     assert output == gethostname()
+
+
+def test_worker_labels(hq_env: HqEnv):
+    hq_env.start_server()
+    hq_env.start_worker(args=["--label", "ssd", "--label", "bigmem"])
+    hq_env.start_worker(args=["--label", "ssd"])
+    hq_env.start_worker()
+    wait_for_worker_state(hq_env, [1, 2, 3], "RUNNING")
+
+    table = hq_env.command(["worker", "list"], as_table=True)
+    assert len(table) == 4
+    table.check_value_columns(["Id", "Labels"], 0, ["1", "bigmem, ssd"])
+    table.check_value_columns(["Id", "Labels"], 1, ["2", "ssd"])
+    table.check_value_columns(["Id", "Labels"], 2, ["3", "None"])
+
+    table = hq_env.command(["worker", "list", "--label", "ssd"], as_table=True)
+    assert len(table) == 3
+    table.check_value_column("Id", 0, "1")
+    table.check_value_column("Id", 1, "2")
+
+    table = hq_env.command(
+        ["worker", "list", "--label", "ssd", "--label", "bigmem"], as_table=True
+    )
+    assert len(table) == 2
+    table.check_value_column("Id", 0, "1")
+
+    table = hq_env.command(["worker", "info", "1"], as_table=True)
+    table.check_value_row("Labels", "bigmem, ssd")
+
+
+def test_task_waits_for_required_label(hq_env: HqEnv):
+    hq_env.start_server()
+    hq_env.start_worker(args=["--label", "ssd"], env={"WORKER_NAME": "w1"})
+    table = hq_env.command(
+        [
+            "submit",
+            "--require",
+            "bigmem",
+            "--require",
+            "ssd",
+            "--",
+            "bash",
+            "-c",
+            "echo $WORKER_NAME",
+        ],
+        as_table=True,
+    )
+    table.check_value_row("Required labels", "bigmem, ssd")
+    time.sleep(1)
+
+    table = hq_env.command(["jobs"], as_table=True)
+    assert table.get_column_value("State")[0] == "WAITING"
+
+    hq_env.start_worker(
+        args=["--label", "ssd", "--label", "bigmem"], env={"WORKER_NAME": "w2"}
+    )
+    wait_for_job_state(hq_env, 1, "FINISHED")
+    with open("stdout.1.0") as f:
+        assert f.read().strip() == "w2"
+
+
+def test_submit_invalid_required_label(hq_env: HqEnv):
+    hq_env.start_server()
+    hq_env.command(
+        ["submit", "--require", "a,b", "hostname"], expect_fail="Invalid label 'a,b'"
+    )
+
+
+def test_worker_invalid_label(hq_env: HqEnv):
+    hq_env.start_server()
+    process = hq_env.start_worker(args=["--label", "a,b"])
+    hq_env.check_process_exited(process, expected_code=1)
+
+
+def test_worker_time_limit(hq_env: HqEnv):
+    hq_env.start_server()
+    hq_env.start_worker(args=["--time-limit", "1h"])
+    w = hq_env.start_worker(args=["--time-limit", "1s"])
+    hq_env.start_worker()
+    wait_for_worker_state(hq_env, [1, 3], "RUNNING")
+
+    table = hq_env.command(["worker", "info", "1"], as_table=True)
+    assert table.get_row_value("Time limit") != "None"
+    assert 3500 < parse_duration(table.get_row_value("Remaining lifetime")) <= 3600
+
+    table = hq_env.command(["worker", "info", "3"], as_table=True)
+    table.check_value_row("Time limit", "None")
+    table.check_value_row("Remaining lifetime", "N/A")
+
+    time.sleep(1.5)
+    hq_env.check_process_exited(w)
+    wait_for_worker_state(hq_env, 2, "CONNECTION LOST")
+
+
+def test_worker_time_limit_kills_tasks(hq_env: HqEnv, tmp_path):
+    hq_env.start_server()
+    hq_env.start_worker(args=["--time-limit", "2s"])
+    finished = tmp_path / "finished"
+    hq_env.command(["submit", "--", "bash", "-c", f"sleep 3; touch {finished}"])
+    wait_for_job_state(hq_env, 1, "RUNNING")
+
+    wait_for_worker_state(hq_env, 1, "CONNECTION LOST")
+    # The task is scheduled again when its worker stops
+    wait_for_job_state(hq_env, 1, "WAITING")
+    time.sleep(2)
+    assert not finished.exists()
+
+
+def test_task_time_request(hq_env: HqEnv):
+    hq_env.start_server()
+    hq_env.start_worker(args=["--time-limit", "10m"], env={"WORKER_NAME": "w1"})
+    table = hq_env.command(
+        [
+            "submit",
+            "--time-request",
+            "1h",
+            "--",
+            "bash",
+            "-c",
+            "echo $WORKER_NAME",
+        ],
+        as_table=True,
+    )
+    table.check_value_row("Time request", "1h")
+    time.sleep(1)
+
+    table = hq_env.command(["jobs"], as_table=True)
+    assert table.get_column_value("State")[0] == "WAITING"
+
+    hq_env.start_worker(args=["--time-limit", "2h"], env={"WORKER_NAME": "w2"})
+    wait_for_job_state(hq_env, 1, "FINISHED")
+    with open("stdout.1.0") as f:
+        assert f.read().strip() == "w2"
+
+
+def test_worker_hooks(hq_env: HqEnv, tmp_path):
+    hq_env.start_server()
+    start_file = tmp_path / "started"
+    stop_file = tmp_path / "stopped"
+    process = hq_env.start_worker(
+        args=[
+            "--on-start",
+            f"echo start > {start_file}",
+            "--on-stop",
+            f"echo stop > {stop_file}",
+        ]
+    )
+    wait_for_worker_state(hq_env, 1, "RUNNING")
+    assert start_file.read_text() == "start\n"
+    assert not stop_file.exists()
+
+    hq_env.command(["worker", "stop", "1"])
+    wait_for_worker_state(hq_env, 1, "STOPPED")
+    time.sleep(0.5)
+    hq_env.check_process_exited(process)
+    assert stop_file.read_text() == "stop\n"
+
+
+def test_worker_start_hook_fails(hq_env: HqEnv, tmp_path):
+    hq_env.start_server()
+    stop_file = tmp_path / "stopped"
+    process = hq_env.start_worker(
+        args=["--on-start", "exit 3", "--on-stop", f"touch {stop_file}"]
+    )
+    time.sleep(0.3)
+    hq_env.check_process_exited(process, expected_code=1)
+    table = hq_env.command(["worker", "list"], as_table=True)
+    assert len(table) == 1
+    assert not stop_file.exists()
+
+
+def test_worker_stop_hook_runs_when_worker_fails(hq_env: HqEnv, tmp_path):
+    hq_env.start_server()
+    stop_file = tmp_path / "stopped"
+    process = hq_env.start_worker(
+        args=[
+            "--on-start",
+            "true",
+            "--on-stop",
+            f"touch {stop_file}",
+            "--label",
+            "a,b",
+        ]
+    )
+    time.sleep(0.3)
+    hq_env.check_process_exited(process, expected_code=1)
+    assert stop_file.exists()
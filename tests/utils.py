@@ -1,8 +1,9 @@
+import re
 import time
 from typing import List, Optional, Union
 
 DEFAULT_TIMEOUT = 5
-JOB_TABLE_ROWS = 12
+JOB_TABLE_ROWS = 14
 
 
 # TODO: create a pandas dataframe instead?
@@ -143,6 +144,23 @@ def wait_for_worker_state(
     wait_for_state(env, ids, target_states, ["worker", "list"], 1, **kwargs)
 
 
+DURATION_UNITS = {"d": 86400, "days": 86400, "day": 86400, "h": 3600, "m": 60, "s": 1}
+
+
+def parse_duration(text: str) -> int:
+    """
+    Parses a duration printed by HyperQueue (e.g. `59m 58s`), returns the number of seconds.
+    """
+    seconds = 0
+    for item in text.split():
+        match = re.fullmatch(r"(\d+)([a-z]+)", item)
+        assert match is not None, f"Invalid duration {text}"
+        value, unit = match.groups()
+        if unit in DURATION_UNITS:
+            seconds += int(value) * DURATION_UNITS[unit]
+    return seconds
+
+
 def print_table(table):
     for i, row in enumerate(table):
         print(i, row)
\ No newline at end of file